@@ -1,6 +1,7 @@
 * create plugin_xxx.rs in src/plugins/
 * add plugin_xxx in src/plugins/mod.rs
 * copy plugin_log.rs to plugin_xxx.rs and modify it
-* add plugin_xxx in src/plugins/plugins_main.rs
+* register plugin_xxx in src/plugins/registry.rs
+* if the node lists `plugins` in cfg.json, add xxx there
 * modify gui.scripts
-* modify cli.scripts
\ No newline at end of file
+* modify cli.scripts
@@ -1,13 +1,15 @@
 * 一開機就執行
-* 可指定日期，時間
-* 可指定週期性執行
-
-* 週期性執行
-p cron task 點眼藥 daily 8:00 reminder 5
-
-* 一次性執行
-p cron task 點眼藥 once 2025/09/29-9:00 reminder 5
-
+p cron add @boot p music init
 
+* 週期性執行, 標準 cron: 分 時 日 月 週
+p cron add "0 7 * * *" p weather update
+p cron add "*/30 8-18 * * mon-fri" p weather show
+p cron add @daily p music download https://youtu.be/xxxx
 
+* 管理
+p cron list
+p cron history 1
+p cron run-now 1
+p cron remove 1
 
+* 提醒 (不執行指令) 仍用 p todos
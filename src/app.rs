@@ -1,15 +1,25 @@
 use log::Level::Info;
 use tokio::sync::broadcast;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{self, Receiver, Sender};
 
-use crate::messages::{ACTION_INIT, Cmd, Data, Log, Messages, Msg};
+use crate::cfg;
+use crate::messages::{ACTION_INIT, Cmd, Command, Data, Log, Messages, Msg};
+use crate::plugins::plugins_main::Plugins;
+use crate::plugins::registry;
+use crate::record::{self, Recorder};
 use crate::utils;
 
 const MODULE: &str = "app";
+const REPLAY_SIZE: usize = 64;
+// they need a terminal
+const HEADLESS_SKIP: &[&str] = &["cli", "panels"];
 
 pub struct App {
+    msg_tx: Sender<Msg>,
     msgs: Messages,
     scripts_filename: String,
+    // the recording to play and where to feed it
+    replay: Option<(String, Sender<Msg>)>,
 }
 
 impl App {
@@ -18,10 +28,49 @@ impl App {
         msg_rx: Receiver<Msg>,
         shutdown_notify: broadcast::Sender<()>,
         scripts_filename: String,
+        recorder: Option<Recorder>,
+        replay_filename: Option<String>,
+        headless: bool,
     ) -> Self {
+        let (replay, replay_rx) = match replay_filename {
+            Some(filename) => {
+                let (replay_tx, replay_rx) = mpsc::channel::<Msg>(REPLAY_SIZE);
+                (Some((filename, replay_tx)), Some(replay_rx))
+            }
+            None => (None, None),
+        };
+
+        // cfg.json `plugins` picks what starts on this node
+        let mut selected = cfg::plugins();
+        if headless {
+            let names = selected.unwrap_or_else(|| {
+                registry::registry()
+                    .iter()
+                    .map(|registration| registration.name.to_string())
+                    .collect()
+            });
+            selected = Some(
+                names
+                    .into_iter()
+                    .filter(|name| !HEADLESS_SKIP.contains(&name.as_str()))
+                    .collect(),
+            );
+        }
+        let plugins = Plugins::new(msg_tx.clone(), shutdown_notify.clone(), selected).await;
+
         let app = Self {
-            msgs: Messages::new(msg_tx, msg_rx, shutdown_notify).await,
+            msg_tx: msg_tx.clone(),
+            msgs: Messages::new(
+                msg_tx,
+                msg_rx,
+                shutdown_notify,
+                plugins,
+                recorder,
+                replay_rx,
+            )
+            .await,
             scripts_filename,
+            replay,
         };
 
         // log
@@ -39,15 +88,48 @@ impl App {
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
+        if let Some((filename, replay_tx)) = &self.replay {
+            self.run_replay(filename.clone(), replay_tx.clone());
+            return Ok(());
+        }
+
         let msg = Msg {
             ts: utils::time::ts(),
             module: MODULE.to_string(),
-            data: Data::Cmd(Cmd {
-                cmd: format!("p scripts {ACTION_INIT} {}", self.scripts_filename),
-            }),
+            data: Data::Command(Command::new("scripts", ACTION_INIT).arg(&self.scripts_filename)),
         };
         self.msgs.send(msg).await;
 
         Ok(())
     }
+
+    // play the recording instead of the init script, then exit
+    fn run_replay(&self, filename: String, replay_tx: Sender<Msg>) {
+        let msg_tx = self.msg_tx.clone();
+        tokio::spawn(async move {
+            match record::replay(&filename, &replay_tx).await {
+                Ok(count) => {
+                    utils::msg::log_info(
+                        &msg_tx,
+                        MODULE,
+                        format!("[{MODULE}] Replayed {count} messages from `{filename}`"),
+                    )
+                    .await;
+                }
+                Err(e) => {
+                    utils::msg::log_warn(&msg_tx, MODULE, format!("[{MODULE}] Replay: {e:#}"))
+                        .await;
+                }
+            }
+
+            let msg = Msg {
+                ts: utils::time::ts(),
+                module: MODULE.to_string(),
+                data: Data::Cmd(Cmd {
+                    cmd: "exit".to_string(),
+                }),
+            };
+            let _ = replay_tx.send(msg).await;
+        });
+    }
 }
@@ -1,56 +1,733 @@
-use std::fs::{self, File};
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::path::Path;
 use std::sync::Mutex;
 
-use once_cell::sync::Lazy;
+use log::LevelFilter;
+use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::consts::{NAS_FOLDER, WEB_PORT};
+use crate::plugins::registry;
 
 const DEF_NAME: &str = "cng3_default";
-const CFG_FILE: &str = "./cfg.json";
+const DEF_LOG_LEVEL: &str = "info";
+const DEF_BROKER: &str = "broker.emqx.io";
+const DEF_BROKER_PORT: u16 = 1883;
+const DEF_RUNTIPI_MUSIC_DIR: &str = "~/runtipi/media/data/music/";
+const DEF_PUBLISH_INTERVAL: u64 = 300;
+const DEF_DEBOUNCE_DELAY: u64 = 10;
+const DEF_WEATHER_POLLING: u64 = 15 * 60;
+pub const CFG_FILE: &str = "./cfg.json";
+pub const SOURCE_DEFAULT: &str = "default";
+const SOURCE_COMMAND_LINE: &str = "command line";
+const ENV_PREFIX: &str = "CNG3_";
+// comma separated in the environment, e.g. CNG3_PLUGINS=log,scripts,mqtt
+const ENV_LISTS: &[&str] = &["plugins"];
+// settable although absent from the defaults
+const OPTIONAL: &[&str] = &["plugins", "music.dir"];
+
+// set once by `init`, or loaded from CFG_FILE on first use
+static INSTANCE: OnceCell<Mutex<Current>> = OnceCell::new();
+// what INSTANCE was loaded from, for `reload`
+static LAYERS: OnceCell<Layers> = OnceCell::new();
 
-static INSTANCE: Lazy<Mutex<Cfg>> = Lazy::new(|| Mutex::new(Cfg::new()));
+// where each setting came from, by path, e.g. `mqtt.broker` -> `./cfg.cng3_pi5.json`
+pub type Sources = BTreeMap<String, String>;
 
-fn default_name() -> String {
-    DEF_NAME.to_string()
+struct Current {
+    cfg: Cfg,
+    sources: Sources,
 }
 
-#[derive(Serialize, Deserialize)]
+// e.g.
+// {
+//   "name": "cng3_pi5",
+//   "log": { "level": "info" },
+//   "web": { "port": 9764 },
+//   "nas": { "dir": "./nas" },
+//   "mqtt": { "broker": "broker.emqx.io", "port": 1883 },
+//   "weather": { "polling": 900, "cities": [{ "name": "Taipei", "latitude": 25.03, "longitude": 121.56 }] },
+//   ...
+// }
+// every section and field may be left out for its default.
+// read from layers, see `Layers`, and never written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Cfg {
-    #[serde(default = "default_name")]
-    name: String,
+    pub name: String,
+    // plugins to start on this node, all of them when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<Vec<String>>,
+    pub log: LogCfg,
+    pub web: WebCfg,
+    pub nas: NasCfg,
+    pub music: MusicCfg,
+    pub runtipi: RuntipiCfg,
+    pub monitor: MonitorCfg,
+    pub mqtt: MqttCfg,
+    pub system: SystemCfg,
+    pub weather: WeatherCfg,
 }
 
-impl Cfg {
-    pub fn new() -> Self {
-        let path = Path::new(CFG_FILE);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogCfg {
+    // error/warn/info/debug/trace/off
+    pub level: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebCfg {
+    // also the port of the other nodes' web servers
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NasCfg {
+    pub dir: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MusicCfg {
+    // `<nas.dir>/music` when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntipiCfg {
+    // where downloaded music is copied on the runtipi server
+    pub music_dir: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorCfg {
+    // seconds of quiet before a change in the nas dir is handled
+    pub debounce_delay: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttCfg {
+    pub broker: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SystemCfg {
+    // seconds between publishing version, tailscale ip and uptime
+    pub publish_interval: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherCfg {
+    // seconds between fetches
+    pub polling: u64,
+    // added on init, on top of `p weather add`
+    pub cities: Vec<CityCfg>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CityCfg {
+    pub name: String,
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+impl Default for Cfg {
+    fn default() -> Self {
+        Self {
+            name: DEF_NAME.to_owned(),
+            plugins: None,
+            log: LogCfg::default(),
+            web: WebCfg::default(),
+            nas: NasCfg::default(),
+            music: MusicCfg::default(),
+            runtipi: RuntipiCfg::default(),
+            monitor: MonitorCfg::default(),
+            mqtt: MqttCfg::default(),
+            system: SystemCfg::default(),
+            weather: WeatherCfg::default(),
+        }
+    }
+}
 
-        let cfg = if !path.exists() {
-            Cfg {
-                name: DEF_NAME.to_owned(),
+impl Default for LogCfg {
+    fn default() -> Self {
+        Self {
+            level: DEF_LOG_LEVEL.to_owned(),
+        }
+    }
+}
+
+impl Default for WebCfg {
+    fn default() -> Self {
+        Self { port: WEB_PORT }
+    }
+}
+
+impl Default for NasCfg {
+    fn default() -> Self {
+        Self {
+            dir: NAS_FOLDER.to_owned(),
+        }
+    }
+}
+
+impl Default for RuntipiCfg {
+    fn default() -> Self {
+        Self {
+            music_dir: DEF_RUNTIPI_MUSIC_DIR.to_owned(),
+        }
+    }
+}
+
+impl Default for MonitorCfg {
+    fn default() -> Self {
+        Self {
+            debounce_delay: DEF_DEBOUNCE_DELAY,
+        }
+    }
+}
+
+impl Default for MqttCfg {
+    fn default() -> Self {
+        Self {
+            broker: DEF_BROKER.to_owned(),
+            port: DEF_BROKER_PORT,
+        }
+    }
+}
+
+impl Default for SystemCfg {
+    fn default() -> Self {
+        Self {
+            publish_interval: DEF_PUBLISH_INTERVAL,
+        }
+    }
+}
+
+impl Default for WeatherCfg {
+    fn default() -> Self {
+        Self {
+            polling: DEF_WEATHER_POLLING,
+            cities: vec![],
+        }
+    }
+}
+
+// what is wrong and where, e.g. `./cfg.json: weather.cities[1].latitude: 123 is not in -90..=90`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfgError {
+    // the file, the environment variable or the command line
+    pub source: String,
+    // empty for the source as a whole
+    pub path: String,
+    pub msg: String,
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}: {}", self.source, self.msg)
+        } else {
+            write!(f, "{}: {}: {}", self.source, self.path, self.msg)
+        }
+    }
+}
+
+// from the command line, on top of everything else
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub name: Option<String>,
+    pub web_port: Option<u16>,
+    pub nas_dir: Option<String>,
+    pub log_level: Option<String>,
+}
+
+// a cfg is built from, later ones winning:
+// - the built-in defaults
+// - the shared base file, e.g. `./cfg.json`
+// - the file of this host next to it, e.g. `./cfg.cng3_pi5.json`
+// - CNG3_* environment variables, e.g. CNG3_MQTT_BROKER for `mqtt.broker`,
+//   the ones that are no setting are ignored
+// - the command line
+// missing files are skipped
+#[derive(Debug, Clone)]
+pub struct Layers {
+    pub cfg_file: String,
+    pub host: Option<String>,
+    pub env: Vec<(String, String)>,
+    pub overrides: Overrides,
+}
+
+impl Layers {
+    // with the host name and the environment of this process
+    pub fn new(cfg_file: &str, overrides: Overrides) -> Self {
+        Self {
+            cfg_file: cfg_file.to_string(),
+            host: sysinfo::System::host_name(),
+            env: std::env::vars()
+                .filter(|(key, _)| key.starts_with(ENV_PREFIX))
+                .collect(),
+            overrides,
+        }
+    }
+
+    pub fn host_file(&self) -> Option<String> {
+        let host = self.host.as_ref()?;
+        let path = Path::new(&self.cfg_file);
+        let stem = path.file_stem()?.to_string_lossy();
+        let file_name = match path.extension() {
+            Some(ext) => format!("{stem}.{host}.{}", ext.to_string_lossy()),
+            None => format!("{stem}.{host}"),
+        };
+        Some(path.with_file_name(file_name).display().to_string())
+    }
+
+    pub fn files(&self) -> Vec<String> {
+        std::iter::once(self.cfg_file.clone())
+            .chain(self.host_file())
+            .collect()
+    }
+
+    // the CNG3_* variables that are no setting, e.g. of another tool
+    pub fn ignored_env(&self) -> Vec<String> {
+        let paths = env_paths();
+        self.env
+            .iter()
+            .filter(|(key, _)| !paths.iter().any(|path| env_name(path) == *key))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    pub fn load(&self) -> Result<(Cfg, Sources), Vec<CfgError>> {
+        let mut errors = vec![];
+
+        let mut layers: Vec<(Value, Sources)> = vec![];
+        for file in self.files() {
+            match read_file(&file) {
+                Ok(Some(value)) => {
+                    let sources = leaves(&value)
+                        .into_iter()
+                        .map(|path| (path, file.clone()))
+                        .collect();
+                    layers.push((value, sources));
+                }
+                Ok(None) => (),
+                Err(e) => errors.push(e),
+            }
+        }
+        layers.push(env_layer(&self.env));
+        layers.push(overrides_layer(&self.overrides));
+
+        // each on its own first, to blame the right one
+        for (value, sources) in &layers {
+            if let Err(e) = serde_path_to_error::deserialize::<_, Cfg>(value.clone()) {
+                errors.push(path_error(sources, e));
             }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut merged = serde_json::to_value(Cfg::default()).unwrap();
+        let mut sources = default_sources();
+        for (value, layer_sources) in layers {
+            merge(&mut merged, value);
+            sources.extend(layer_sources);
+        }
+
+        let cfg: Cfg =
+            serde_path_to_error::deserialize(merged).map_err(|e| vec![path_error(&sources, e)])?;
+        cfg.validate().map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|e| CfgError {
+                    source: source_of(&sources, &e.path),
+                    ..e
+                })
+                .collect::<Vec<_>>()
+        })?;
+
+        Ok((cfg, sources))
+    }
+}
+
+// None when there is no such file
+fn read_file(file: &str) -> Result<Option<Value>, CfgError> {
+    let error = |msg: String| CfgError {
+        source: file.to_string(),
+        path: String::new(),
+        msg,
+    };
+
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(error(e.to_string())),
+    };
+    let value: Value = serde_json::from_str(&content).map_err(|e| error(e.to_string()))?;
+
+    Ok(Some(value))
+}
+
+fn env_name(path: &str) -> String {
+    format!("{ENV_PREFIX}{}", path.replace('.', "_").to_uppercase())
+}
+
+// every path a variable can set
+fn env_paths() -> Vec<String> {
+    let defaults = serde_json::to_value(Cfg::default()).unwrap();
+    leaves(&defaults)
+        .into_iter()
+        .chain(OPTIONAL.iter().map(|path| path.to_string()))
+        .collect()
+}
+
+// the unknown variables are skipped, see `Layers::ignored_env`
+fn env_layer(env: &[(String, String)]) -> (Value, Sources) {
+    let defaults = serde_json::to_value(Cfg::default()).unwrap();
+    let paths = env_paths();
+
+    let mut value = Value::Object(Map::new());
+    let mut sources = Sources::new();
+    for (key, raw) in env {
+        let Some(path) = paths.iter().find(|path| env_name(path) == *key) else {
+            continue;
+        };
+
+        let leaf = if ENV_LISTS.contains(&path.as_str()) {
+            Value::from(raw.split(',').map(str::trim).collect::<Vec<_>>())
+        } else if value_at(&defaults, path).is_none_or(Value::is_string) {
+            Value::from(raw.as_str())
         } else {
-            let file_content = fs::read_to_string(CFG_FILE).unwrap();
-            serde_json::from_str(&file_content).unwrap()
+            serde_json::from_str(raw).unwrap_or_else(|_| Value::from(raw.as_str()))
         };
+        set_at(&mut value, path, leaf);
+        sources.insert(path.clone(), key.clone());
+    }
+
+    (value, sources)
+}
+
+fn default_sources() -> Sources {
+    leaves(&serde_json::to_value(Cfg::default()).unwrap())
+        .into_iter()
+        .map(|path| (path, SOURCE_DEFAULT.to_string()))
+        .collect()
+}
+
+fn overrides_layer(overrides: &Overrides) -> (Value, Sources) {
+    let mut value = Value::Object(Map::new());
+    let leaves = [
+        ("name", overrides.name.clone().map(Value::from)),
+        ("web.port", overrides.web_port.map(Value::from)),
+        ("nas.dir", overrides.nas_dir.clone().map(Value::from)),
+        ("log.level", overrides.log_level.clone().map(Value::from)),
+    ];
 
-        let file_content = serde_json::to_string_pretty(&cfg).unwrap();
-        let mut file = File::create(CFG_FILE).unwrap();
-        file.write_all(file_content.as_bytes()).unwrap();
+    let mut sources = Sources::new();
+    for (path, leaf) in leaves {
+        if let Some(leaf) = leaf {
+            set_at(&mut value, path, leaf);
+            sources.insert(path.to_string(), SOURCE_COMMAND_LINE.to_string());
+        }
+    }
+
+    (value, sources)
+}
 
-        cfg
+// the paths of everything but objects, e.g. `mqtt.port` and `weather.cities`
+fn leaves(value: &Value) -> Vec<String> {
+    fn walk(value: &Value, prefix: &str, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    walk(value, &path, out);
+                }
+            }
+            _ => out.push(prefix.to_string()),
+        }
     }
 
-    fn get_instance() -> std::sync::MutexGuard<'static, Cfg> {
-        INSTANCE.lock().unwrap()
+    let mut out = vec![];
+    walk(value, "", &mut out);
+    out
+}
+
+fn value_at<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn set_at(value: &mut Value, path: &str, leaf: Value) {
+    let mut node = value;
+    for key in path.split('.') {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node = node
+            .as_object_mut()
+            .unwrap()
+            .entry(key)
+            .or_insert(Value::Null);
     }
+    *node = leaf;
+}
 
-    fn name(&self) -> &str {
-        &self.name
+// objects are merged, anything else is replaced
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
     }
 }
 
+// the source of the setting at `path` or above it
+fn source_of(sources: &Sources, path: &str) -> String {
+    sources
+        .iter()
+        .filter(|(key, _)| {
+            path == key.as_str()
+                || path
+                    .strip_prefix(key.as_str())
+                    .is_some_and(|rest| rest.starts_with('.') || rest.starts_with('['))
+        })
+        .max_by_key(|(key, _)| key.len())
+        .or_else(|| sources.iter().next())
+        .map(|(_, source)| source.clone())
+        .unwrap_or_else(|| SOURCE_DEFAULT.to_string())
+}
+
+fn path_error(sources: &Sources, e: serde_path_to_error::Error<serde_json::Error>) -> CfgError {
+    let path = e.path().to_string();
+    let path = if path == "." { String::new() } else { path };
+    CfgError {
+        source: source_of(sources, &path),
+        path,
+        msg: e.into_inner().to_string(),
+    }
+}
+
+impl Cfg {
+    // the errors come without a source, `Layers::load` fills it in
+    pub fn validate(&self) -> Result<(), Vec<CfgError>> {
+        let mut errors = vec![];
+        let mut check = |ok: bool, path: &str, msg: String| {
+            if !ok {
+                errors.push(CfgError {
+                    source: String::new(),
+                    path: path.to_string(),
+                    msg,
+                });
+            }
+        };
+        check(
+            !self.name.is_empty(),
+            "name",
+            "must not be empty".to_string(),
+        );
+        if let Some(plugins) = &self.plugins {
+            let known: Vec<&str> = registry::registry()
+                .iter()
+                .map(|registration| registration.name)
+                .collect();
+            for (idx, plugin) in plugins.iter().enumerate() {
+                check(
+                    known.contains(&plugin.as_str()),
+                    &format!("plugins[{idx}]"),
+                    format!("unknown plugin `{plugin}`"),
+                );
+            }
+        }
+        check(
+            self.log.level.parse::<LevelFilter>().is_ok(),
+            "log.level",
+            format!(
+                "`{}` is not error/warn/info/debug/trace/off",
+                self.log.level
+            ),
+        );
+        check(self.web.port != 0, "web.port", "must not be 0".to_string());
+        check(
+            !self.nas.dir.is_empty(),
+            "nas.dir",
+            "must not be empty".to_string(),
+        );
+        check(
+            self.music.dir.as_ref().is_none_or(|dir| !dir.is_empty()),
+            "music.dir",
+            "must not be empty".to_string(),
+        );
+        check(
+            !self.mqtt.broker.is_empty(),
+            "mqtt.broker",
+            "must not be empty".to_string(),
+        );
+        check(
+            self.mqtt.port != 0,
+            "mqtt.port",
+            "must not be 0".to_string(),
+        );
+        check(
+            self.system.publish_interval != 0,
+            "system.publish_interval",
+            "must not be 0".to_string(),
+        );
+        check(
+            self.weather.polling != 0,
+            "weather.polling",
+            "must not be 0".to_string(),
+        );
+        for (idx, city) in self.weather.cities.iter().enumerate() {
+            check(
+                !city.name.is_empty(),
+                &format!("weather.cities[{idx}].name"),
+                "must not be empty".to_string(),
+            );
+            check(
+                (-90.0..=90.0).contains(&city.latitude),
+                &format!("weather.cities[{idx}].latitude"),
+                format!("{} is not in -90..=90", city.latitude),
+            );
+            check(
+                (-180.0..=180.0).contains(&city.longitude),
+                &format!("weather.cities[{idx}].longitude"),
+                format!("{} is not in -180..=180", city.longitude),
+            );
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn layers() -> &'static Layers {
+    LAYERS.get_or_init(|| Layers::new(CFG_FILE, Overrides::default()))
+}
+
+// without `init`, e.g. in tests, an invalid layer falls back to the defaults
+fn current() -> std::sync::MutexGuard<'static, Current> {
+    INSTANCE
+        .get_or_init(|| {
+            let (cfg, sources) = layers()
+                .load()
+                .unwrap_or_else(|_| (Cfg::default(), default_sources()));
+            Mutex::new(Current { cfg, sources })
+        })
+        .lock()
+        .unwrap()
+}
+
+// load `cfg_file` instead of CFG_FILE, before anything reads the cfg
+pub fn init(cfg_file: &str, overrides: Overrides) -> Result<(), Vec<CfgError>> {
+    let layers = Layers::new(cfg_file, overrides);
+    let (cfg, sources) = layers.load()?;
+    let _ = INSTANCE.set(Mutex::new(Current { cfg, sources }));
+    let _ = LAYERS.set(layers);
+
+    Ok(())
+}
+
+// the base file and the one of this host
+pub fn files() -> Vec<String> {
+    layers().files()
+}
+
+pub fn ignored_env() -> Vec<String> {
+    layers().ignored_env()
+}
+
+// read the layers again. the new cfg, None when nothing changed; an invalid
+// one leaves the running cfg as it is
+pub fn reload() -> Result<Option<Cfg>, Vec<CfgError>> {
+    let (cfg, sources) = layers().load()?;
+
+    let mut current = current();
+    current.sources = sources;
+    if current.cfg == cfg {
+        return Ok(None);
+    }
+    current.cfg = cfg.clone();
+
+    Ok(Some(cfg))
+}
+
+pub fn get() -> Cfg {
+    current().cfg.clone()
+}
+
+// every setting with its value and where it came from
+pub fn show() -> Vec<(String, Value, String)> {
+    let current = current();
+    let value = serde_json::to_value(&current.cfg).unwrap();
+    leaves(&value)
+        .into_iter()
+        .map(|path| {
+            let leaf = value_at(&value, &path).cloned().unwrap_or(Value::Null);
+            let source = source_of(&current.sources, &path);
+            (path, leaf, source)
+        })
+        .collect()
+}
+
 pub fn name() -> String {
-    let cfg = Cfg::get_instance();
-    cfg.name().to_owned()
+    current().cfg.name.clone()
+}
+
+pub fn plugins() -> Option<Vec<String>> {
+    current().cfg.plugins.clone()
+}
+
+pub fn web_port() -> u16 {
+    current().cfg.web.port
+}
+
+pub fn nas_dir() -> String {
+    current().cfg.nas.dir.clone()
+}
+
+pub fn upload_dir() -> String {
+    format!("{}/upload", nas_dir())
+}
+
+pub fn music_dir() -> String {
+    let cfg = &current().cfg;
+    cfg.music
+        .dir
+        .clone()
+        .unwrap_or_else(|| format!("{}/music", cfg.nas.dir))
+}
+
+pub fn log_level() -> String {
+    current().cfg.log.level.clone()
 }
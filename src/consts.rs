@@ -1,5 +1,3 @@
 pub const NAS_FOLDER: &str = "./nas";
 pub const NAS_NAME: &str = "/nas";
-pub const UPLOAD_FOLDER: &str = "./nas/upload";
 pub const WEB_PORT: u16 = 9764;
-pub const MUSIC_FOLDER: &str = "./nas/music";
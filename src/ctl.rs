@@ -0,0 +1,153 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::exec::{self, ExecLog};
+use crate::messages::Msg;
+use crate::utils;
+
+const MODULE: &str = "ctl";
+pub const CTL_SOCKET: &str = "./cng3.sock";
+
+// one JSON line each: the logs of a command, then whether it went fine
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    Log(ExecLog),
+    Done { ok: bool },
+}
+
+// commands from `cng3 ctl ...` on the same machine, one per line, e.g.
+// `p nas show`. only the owner of the daemon can connect
+pub struct Ctl {
+    msg_tx: Sender<Msg>,
+    shutdown_tx: broadcast::Sender<()>,
+    path: PathBuf,
+}
+
+impl Ctl {
+    pub async fn new(msg_tx: Sender<Msg>, shutdown_tx: broadcast::Sender<()>, path: &str) -> Self {
+        utils::msg::log_new(&msg_tx, MODULE).await;
+
+        Self {
+            msg_tx,
+            shutdown_tx,
+            path: PathBuf::from(path),
+        }
+    }
+
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+
+        // a socket file left behind by a crash is removed, a live one is not
+        if UnixStream::connect(&self.path).await.is_ok() {
+            anyhow::bail!("`{}` is in use by another instance", self.path.display());
+        }
+        let _ = fs::remove_file(&self.path);
+        let listener = UnixListener::bind(&self.path)?;
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+
+        utils::msg::log_info(
+            &self.msg_tx,
+            MODULE,
+            format!("[{MODULE}] Listening on `{}`", self.path.display()),
+        )
+        .await;
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let msg_tx = self.msg_tx.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(&msg_tx, stream).await {
+                                utils::msg::log_warn(
+                                    &msg_tx,
+                                    MODULE,
+                                    format!("[{MODULE}] Connection dropped. Err: {e}"),
+                                )
+                                .await;
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        utils::msg::log_warn(
+                            &self.msg_tx,
+                            MODULE,
+                            format!("[{MODULE}] Failed to accept. Err: {e}"),
+                        )
+                        .await;
+                    }
+                },
+                _ = shutdown_rx.recv() => break,
+            }
+        }
+
+        let _ = fs::remove_file(&self.path);
+
+        Ok(())
+    }
+}
+
+async fn serve(msg_tx: &Sender<Msg>, stream: UnixStream) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let cmd = line.trim();
+        if cmd.is_empty() {
+            continue;
+        }
+
+        // each log as it is routed, then `Done`
+        let (log_tx, mut log_rx) = mpsc::unbounded_channel::<ExecLog>();
+        let exec = exec::exec_streamed(msg_tx, cmd, log_tx);
+        tokio::pin!(exec);
+        let ok = loop {
+            tokio::select! {
+                Some(log) = log_rx.recv() => write_reply(&mut writer, &Reply::Log(log)).await?,
+                ok = &mut exec => break ok,
+            }
+        };
+        while let Ok(log) = log_rx.try_recv() {
+            write_reply(&mut writer, &Reply::Log(log)).await?;
+        }
+        write_reply(&mut writer, &Reply::Done { ok }).await?;
+    }
+
+    Ok(())
+}
+
+async fn write_reply(writer: &mut OwnedWriteHalf, reply: &Reply) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(reply)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    Ok(())
+}
+
+// the client side: run `cmd` on the daemon behind `path`, print its logs as
+// they come and tell whether it went fine
+pub async fn ctl(path: &str, cmd: &str) -> anyhow::Result<bool> {
+    let stream = UnixStream::connect(Path::new(path)).await?;
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(format!("{cmd}\n").as_bytes()).await?;
+    writer.shutdown().await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<Reply>(&line)? {
+            Reply::Log(log) => println!("{}", log.render()),
+            Reply::Done { ok } => return Ok(ok),
+        }
+    }
+
+    anyhow::bail!("`{path}` closed before `{cmd}` was done")
+}
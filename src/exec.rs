@@ -0,0 +1,127 @@
+use log::Level::Warn;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::messages::{self, ACTION_LIST, Cmd, Command, Data, Msg};
+use crate::utils;
+
+const MODULE: &str = "exec";
+// they complain about commands on the target plugin's behalf
+const ROUTER_MODULES: &[&str] = &["messages", "plugins"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecLog {
+    pub ts: u64,
+    pub level: String,
+    pub module: String,
+    pub msg: String,
+}
+
+impl ExecLog {
+    pub fn render(&self) -> String {
+        format!(
+            "{} [{}] {}",
+            utils::time::ts_str(self.ts),
+            self.level,
+            self.msg
+        )
+    }
+}
+
+// what one command line produced; not ok once it logged a warning or worse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecResult {
+    pub cmd: String,
+    pub ok: bool,
+    pub logs: Vec<ExecLog>,
+}
+
+// run a line as typed in the cli, e.g. `p devices show`, and collect its logs.
+// a round trip through the target plugin's mailbox and then one through the
+// router mean everything the handler logged has been routed by the time we
+// look. logs from tasks the handler spawned may come too late.
+pub async fn exec(msg_tx: &Sender<Msg>, cmd: &str) -> ExecResult {
+    let (log_tx, mut log_rx) = mpsc::unbounded_channel::<ExecLog>();
+    let ok = exec_streamed(msg_tx, cmd, log_tx).await;
+
+    let mut logs = vec![];
+    while let Ok(log) = log_rx.try_recv() {
+        logs.push(log);
+    }
+
+    ExecResult {
+        cmd: cmd.to_string(),
+        ok,
+        logs,
+    }
+}
+
+// the same, each log sent on `log_tx` as soon as it is routed. true when
+// nothing warned
+pub async fn exec_streamed(
+    msg_tx: &Sender<Msg>,
+    cmd: &str,
+    log_tx: mpsc::UnboundedSender<ExecLog>,
+) -> bool {
+    let (tap_tx, mut tap_rx) = mpsc::unbounded_channel::<Msg>();
+    send(msg_tx, Data::Tap(tap_tx)).await;
+    send(
+        msg_tx,
+        Data::Cmd(Cmd {
+            cmd: cmd.to_string(),
+        }),
+    )
+    .await;
+
+    let plugin = messages::split(cmd)
+        .and_then(Command::from_words)
+        .map(|command| command.plugin)
+        .ok();
+    let barriers = async {
+        if let Some(plugin) = &plugin {
+            // any query does, the answer is not needed
+            let _ = utils::msg::query(msg_tx, MODULE, Command::new(plugin, ACTION_LIST)).await;
+        }
+        let _ = utils::msg::query(msg_tx, MODULE, Command::new("plugins", ACTION_LIST)).await;
+    };
+    tokio::pin!(barriers);
+
+    let mut ok = true;
+    let mut forward = |msg: Msg| {
+        let Data::Log(log) = msg.data else {
+            return;
+        };
+        let module = msg.module.as_str();
+        if plugin.as_deref() != Some(module) && !ROUTER_MODULES.contains(&module) {
+            return;
+        }
+
+        ok &= log.level > Warn;
+        let _ = log_tx.send(ExecLog {
+            ts: msg.ts,
+            level: log.level.to_string(),
+            module: msg.module,
+            msg: log.msg,
+        });
+    };
+    loop {
+        tokio::select! {
+            Some(msg) = tap_rx.recv() => forward(msg),
+            _ = &mut barriers => break,
+        }
+    }
+    while let Ok(msg) = tap_rx.try_recv() {
+        forward(msg);
+    }
+
+    ok
+}
+
+async fn send(msg_tx: &Sender<Msg>, data: Data) {
+    let msg = Msg {
+        ts: utils::time::ts(),
+        module: MODULE.to_string(),
+        data,
+    };
+    let _ = msg_tx.send(msg).await;
+}
@@ -0,0 +1,150 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::messages::{Cmd, Command, Data, Event, Messages, Msg, QueryResult};
+use crate::plugins::plugins_main::Plugins;
+use crate::record::{MemorySink, Record, RecordData, Recorder};
+use crate::utils;
+
+const MODULE: &str = "harness";
+const MSG_SIZE: usize = 4096;
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+const WAIT_POLL: Duration = Duration::from_millis(10);
+
+// a headless instance for tests: only the chosen plugins (plus the required
+// ones) are started, and every Msg the router sees is kept in memory.
+// nothing goes to the log file unless a test asks with `p log file <path>`
+pub struct Harness {
+    msg_tx: Sender<Msg>,
+    shutdown_tx: broadcast::Sender<()>,
+    records: MemorySink,
+}
+
+impl Harness {
+    pub async fn start(plugins: &[&str]) -> Self {
+        let (msg_tx, msg_rx) = mpsc::channel::<Msg>(MSG_SIZE);
+        let (shutdown_tx, _) = broadcast::channel::<()>(1);
+        let records: MemorySink = Arc::new(Mutex::new(vec![]));
+        utils::log_file::set_default_path(None);
+
+        let selected = plugins.iter().map(|name| name.to_string()).collect();
+        let plugins = Plugins::new(msg_tx.clone(), shutdown_tx.clone(), Some(selected)).await;
+        Messages::new(
+            msg_tx.clone(),
+            msg_rx,
+            shutdown_tx.clone(),
+            plugins,
+            Some(Recorder::memory(records.clone())),
+            None,
+        )
+        .await;
+
+        Self {
+            msg_tx,
+            shutdown_tx,
+            records,
+        }
+    }
+
+    pub fn msg_tx(&self) -> Sender<Msg> {
+        self.msg_tx.clone()
+    }
+
+    pub fn shutdown_tx(&self) -> broadcast::Sender<()> {
+        self.shutdown_tx.clone()
+    }
+
+    // a line as typed in the cli, e.g. `p devices show`
+    pub async fn cmd(&self, cmd: &str) {
+        let msg = Msg {
+            ts: utils::time::ts(),
+            module: MODULE.to_string(),
+            data: Data::Cmd(Cmd {
+                cmd: cmd.to_string(),
+            }),
+        };
+        let _ = self.msg_tx.send(msg).await;
+    }
+
+    pub async fn command(&self, cmd: Command) {
+        utils::msg::command(&self.msg_tx, MODULE, cmd).await;
+    }
+
+    pub async fn query(&self, cmd: Command) -> QueryResult {
+        utils::msg::query(&self.msg_tx, MODULE, cmd).await
+    }
+
+    pub async fn emit(&self, event: Event) {
+        utils::msg::emit(&self.msg_tx, MODULE, event).await;
+    }
+
+    pub fn records(&self) -> Vec<Record> {
+        self.records.lock().unwrap().clone()
+    }
+
+    // (level, msg) of every log so far
+    pub fn logs(&self) -> Vec<(String, String)> {
+        self.records()
+            .into_iter()
+            .filter_map(|record| match record.data {
+                RecordData::Log { level, msg } => Some((level, msg)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // every structured Command routed so far, from any module
+    pub fn commands(&self) -> Vec<Command> {
+        self.records()
+            .into_iter()
+            .filter_map(|record| match record.data {
+                RecordData::Command(cmd) => Some(cmd),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.records()
+            .into_iter()
+            .filter_map(|record| match record.data {
+                RecordData::Event(event) => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // polls the records until `f` holds; false after WAIT_TIMEOUT
+    pub async fn wait_for(&self, f: impl Fn(&[Record]) -> bool) -> bool {
+        let wait = async {
+            loop {
+                if f(&self.records.lock().unwrap()) {
+                    return;
+                }
+                tokio::time::sleep(WAIT_POLL).await;
+            }
+        };
+        tokio::time::timeout(WAIT_TIMEOUT, wait).await.is_ok()
+    }
+
+    pub async fn wait_for_log(&self, pattern: &str) -> bool {
+        self.wait_for(|records| {
+            records.iter().any(|record| {
+                matches!(&record.data, RecordData::Log { msg, .. } if msg.contains(pattern))
+            })
+        })
+        .await
+    }
+
+    // same path as `exit` in the cli: plugins stop in reverse order
+    pub async fn shutdown(self) -> bool {
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        self.cmd("exit").await;
+        tokio::time::timeout(WAIT_TIMEOUT, shutdown_rx.recv())
+            .await
+            .is_ok()
+    }
+}
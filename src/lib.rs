@@ -0,0 +1,12 @@
+// the binary and the tests share everything from here
+pub mod app;
+pub mod cfg;
+pub mod consts;
+pub mod ctl;
+pub mod exec;
+pub mod harness;
+pub mod messages;
+pub mod plugins;
+pub mod record;
+pub mod utils;
+pub mod web;
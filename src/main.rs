@@ -1,21 +1,83 @@
 use std::env;
+use std::str::FromStr;
 
 use anyhow::Result;
+use log::LevelFilter;
 use tokio::sync::{broadcast, mpsc};
 
-mod app;
-mod cfg;
-mod consts;
-mod messages;
-mod plugins;
-mod utils;
-mod web;
-
-use messages::Msg;
+use cng3::exec::{self, ExecResult};
+use cng3::messages::{Cmd, Data, Msg};
+use cng3::plugins::plugins_main;
+use cng3::{app, cfg, ctl, record, utils, web};
 
 const SCRIPTS_FILENAME: &str = "./init.scripts";
 const MSG_SIZE: usize = 4096;
 const SCRIPT_FLAG: &str = "--script";
+const RECORD_FLAG: &str = "--record";
+const REPLAY_FLAG: &str = "--replay";
+const NAME_FLAG: &str = "--name";
+const CONFIG_FLAG: &str = "--config";
+const WEB_PORT_FLAG: &str = "--web-port";
+const NAS_DIR_FLAG: &str = "--nas-dir";
+const HEADLESS_FLAG: &str = "--headless";
+const LOG_LEVEL_FLAG: &str = "--log-level";
+const EXEC_FLAG: &str = "--exec";
+const DAEMON_FLAG: &str = "--daemon";
+const CTL_SOCKET_FLAG: &str = "--ctl-socket";
+const CHECK_SCRIPT_FLAG: &str = "--check-script";
+const CTL_SUBCOMMAND: &str = "ctl";
+const VERSION_FLAG: &str = "--version";
+const HELP_FLAG: &str = "--help";
+
+// exit codes of --exec and ctl, and of bad arguments or cfg
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_UNREACHABLE: i32 = 3;
+
+const USAGE: &str = "\
+Usage: cng3 [options]
+       cng3 [--ctl-socket <path>] ctl <cmd>...
+
+Runs cng3, or with `ctl` runs a command such as `p nas show` on the one
+already running here and prints its logs.
+
+Options:
+  --name <name>          Device name, overrides cfg.json
+  --config <path>        Base config file, cfg.<host>.json next to it is read
+                         on top [default: ./cfg.json]
+  --web-port <port>      Web server port, overrides cfg.json
+  --nas-dir <dir>        NAS folder, overrides cfg.json
+  --log-level <level>    error/warn/info/debug/trace/off, overrides cfg.json
+  --headless             No cli and panels, e.g. for a service
+  --script <path>        Init script [default: ./init.scripts]
+  --record <path>        Record every routed message to path
+  --replay <path>        Replay a recording headless, then exit
+  --exec <cmd>           Run a command, e.g. \"p devices show\", print its logs
+                         and exit, 1 if it warned. Repeatable
+  --daemon               With --exec, run on the daemon at --ctl-socket
+                         instead of a fresh instance, 3 if it is not reachable
+  --ctl-socket <path>    Control socket for ctl [default: ./cng3.sock]
+  --check-script <path>  Print the steps of a script for this host and its
+                         errors without running it, 1 if there are any
+  --version              Print the version
+  --help                 Print this help";
+
+struct Args {
+    scripts_filename: String,
+    record_filename: Option<String>,
+    replay_filename: Option<String>,
+    cfg_filename: String,
+    overrides: cfg::Overrides,
+    headless: bool,
+    exec: Vec<String>,
+    daemon: bool,
+    ctl_socket: String,
+    // the command of `cng3 ctl ...`
+    ctl: Option<String>,
+    check_script: Option<String>,
+    version: bool,
+    help: bool,
+}
 
 fn handle_panic() {
     std::panic::set_hook(Box::new(|info| {
@@ -33,24 +95,163 @@ fn handle_panic() {
 
         eprintln!("💥 Panic occurred: '{message}' {location}");
 
-        std::process::exit(1);
+        // a plugin handler is restarted by its supervisor. anything else, e.g.
+        // a receive loop, would leave a node that is up but deaf
+        if !plugins_main::handling() {
+            std::process::exit(1);
+        }
     }));
 }
 
-fn parse_args(args: &mut impl Iterator<Item = String>) -> Result<String, &'static str> {
-    let mut scripts_filename = SCRIPTS_FILENAME.to_string();
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value after `{flag}`"))
+}
+
+fn parse_args(args: &mut impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        scripts_filename: SCRIPTS_FILENAME.to_string(),
+        record_filename: None,
+        replay_filename: None,
+        cfg_filename: cfg::CFG_FILE.to_string(),
+        overrides: cfg::Overrides::default(),
+        headless: false,
+        exec: vec![],
+        daemon: false,
+        ctl_socket: ctl::CTL_SOCKET.to_string(),
+        ctl: None,
+        check_script: None,
+        version: false,
+        help: false,
+    };
 
     while let Some(arg) = args.next() {
-        if arg == SCRIPT_FLAG {
-            if let Some(path) = args.next() {
-                scripts_filename = path;
-            } else {
-                return Err("Missing value after `--script`");
+        match arg.as_str() {
+            SCRIPT_FLAG => parsed.scripts_filename = value(args, &arg)?,
+            RECORD_FLAG => parsed.record_filename = Some(value(args, &arg)?),
+            REPLAY_FLAG => parsed.replay_filename = Some(value(args, &arg)?),
+            NAME_FLAG => parsed.overrides.name = Some(value(args, &arg)?),
+            CONFIG_FLAG => parsed.cfg_filename = value(args, &arg)?,
+            WEB_PORT_FLAG => {
+                let port = value(args, &arg)?;
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| format!("Invalid port `{port}` for `{arg}`"))?;
+                parsed.overrides.web_port = Some(port);
+            }
+            NAS_DIR_FLAG => parsed.overrides.nas_dir = Some(value(args, &arg)?),
+            LOG_LEVEL_FLAG => {
+                let level = value(args, &arg)?;
+                LevelFilter::from_str(&level)
+                    .map_err(|_| format!("Invalid level `{level}` for `{arg}`"))?;
+                parsed.overrides.log_level = Some(level);
+            }
+            HEADLESS_FLAG => parsed.headless = true,
+            EXEC_FLAG => parsed.exec.push(value(args, &arg)?),
+            DAEMON_FLAG => parsed.daemon = true,
+            CTL_SOCKET_FLAG => parsed.ctl_socket = value(args, &arg)?,
+            CHECK_SCRIPT_FLAG => parsed.check_script = Some(value(args, &arg)?),
+            CTL_SUBCOMMAND => {
+                // quoted again, `"New York"` stays one argument
+                let cmd = shell_words::join(args);
+                if cmd.is_empty() {
+                    return Err(format!(
+                        "`{CTL_SUBCOMMAND}` needs a command, e.g. `p nas show`"
+                    ));
+                }
+                parsed.ctl = Some(cmd);
+                break;
+            }
+            VERSION_FLAG => parsed.version = true,
+            HELP_FLAG => parsed.help = true,
+            _ => return Err(format!("Unknown argument `{arg}`. See `cng3 --help`.")),
+        }
+    }
+    if parsed.daemon && parsed.exec.is_empty() {
+        return Err(format!("`{DAEMON_FLAG}` needs `{EXEC_FLAG}`"));
+    }
+
+    Ok(parsed)
+}
+
+fn print_result(result: &ExecResult) {
+    for log in &result.logs {
+        println!("{}", log.render());
+    }
+}
+
+// for the host of the cfg, as the scripts plugin would load it
+fn check_script(scripts_filename: &str) -> i32 {
+    match utils::script::check(scripts_filename, &cfg::name()) {
+        Ok(lines) => {
+            for line in &lines {
+                println!("{}:{} {}", line.file, line.no, line.step);
+            }
+            println!("✅ {} steps, no errors", lines.len());
+            0
+        }
+        Err(errors) => {
+            for e in errors {
+                eprintln!("❌ {e}");
             }
+            EXIT_FAILED
         }
     }
+}
 
-    Ok(scripts_filename)
+// on the daemon through its ctl socket, which prints the logs as usual too
+async fn exec_daemon(ctl_socket: &str, cmds: &[String]) -> i32 {
+    let mut code = 0;
+    for cmd in cmds {
+        match ctl::ctl(ctl_socket, cmd).await {
+            Ok(true) => (),
+            Ok(false) => code = EXIT_FAILED,
+            Err(e) => {
+                eprintln!("❌ Error: {e:#}");
+                return EXIT_UNREACHABLE;
+            }
+        }
+    }
+    code
+}
+
+// on a fresh headless instance, without the init script and the web server
+async fn exec_fresh(cmds: &[String]) -> Result<i32> {
+    let (msg_tx, msg_rx) = mpsc::channel::<Msg>(MSG_SIZE);
+    let (shutdown_notify, _) = broadcast::channel::<()>(1);
+    let mut shutdown_rx = shutdown_notify.subscribe();
+
+    let _app = app::App::new(
+        msg_tx.clone(),
+        msg_rx,
+        shutdown_notify,
+        SCRIPTS_FILENAME.to_string(),
+        None,
+        None,
+        true,
+    )
+    .await;
+
+    let mut code = 0;
+    for cmd in cmds {
+        let result = exec::exec(&msg_tx, cmd).await;
+        print_result(&result);
+        if !result.ok {
+            code = EXIT_FAILED;
+        }
+    }
+
+    let msg = Msg {
+        ts: utils::time::ts(),
+        module: "main".to_string(),
+        data: Data::Cmd(Cmd {
+            cmd: "exit".to_string(),
+        }),
+    };
+    msg_tx.send(msg).await?;
+    let _ = shutdown_rx.recv().await;
+
+    Ok(code)
 }
 
 #[actix_web::main]
@@ -58,24 +259,97 @@ async fn main() -> Result<()> {
     handle_panic();
 
     let mut args = env::args().skip(1);
-    let scripts_filename = parse_args(&mut args).unwrap_or_else(|e| {
+    let mut args = parse_args(&mut args).unwrap_or_else(|e| {
         eprintln!("❌ Error: {e}");
-        std::process::exit(1);
+        std::process::exit(EXIT_USAGE);
     });
 
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    if args.version {
+        println!("cng3 {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    if let Some(cmd) = &args.ctl {
+        let code = match ctl::ctl(&args.ctl_socket, cmd).await {
+            Ok(true) => 0,
+            Ok(false) => EXIT_FAILED,
+            Err(e) => {
+                eprintln!("❌ Error: {e:#}");
+                EXIT_UNREACHABLE
+            }
+        };
+        std::process::exit(code);
+    }
+
+    // only the results on stdout, unless asked for the rest
+    if !args.exec.is_empty() && !args.daemon && args.overrides.log_level.is_none() {
+        args.overrides.log_level = Some(LevelFilter::Off.to_string());
+    }
+
+    if let Err(errors) = cfg::init(&args.cfg_filename, args.overrides) {
+        for e in errors {
+            eprintln!("❌ Error: {e}");
+        }
+        std::process::exit(EXIT_USAGE);
+    }
+
+    if let Some(scripts_filename) = &args.check_script {
+        std::process::exit(check_script(scripts_filename));
+    }
+
+    if !args.exec.is_empty() {
+        let code = if args.daemon {
+            exec_daemon(&args.ctl_socket, &args.exec).await
+        } else {
+            exec_fresh(&args.exec).await?
+        };
+        std::process::exit(code);
+    }
+
+    let recorder = args.record_filename.as_deref().map(|filename| {
+        record::Recorder::create(filename).unwrap_or_else(|e| {
+            eprintln!("❌ Error: {e:#}");
+            std::process::exit(EXIT_USAGE);
+        })
+    });
+    let replaying = args.replay_filename.is_some();
+
     let (msg_tx, msg_rx) = mpsc::channel::<Msg>(MSG_SIZE);
     let (shutdown_notify, _) = broadcast::channel::<()>(1);
+    let mut shutdown_rx = shutdown_notify.subscribe();
 
     app::App::new(
         msg_tx.clone(),
         msg_rx,
         shutdown_notify.clone(),
-        scripts_filename,
+        args.scripts_filename,
+        recorder,
+        args.replay_filename,
+        args.headless || replaying,
     )
     .await
     .run()
     .await?;
 
+    // headless: no web server, done once the recording is played
+    if replaying {
+        let _ = shutdown_rx.recv().await;
+        return Ok(());
+    }
+
+    let ctl = ctl::Ctl::new(msg_tx.clone(), shutdown_notify.clone(), &args.ctl_socket).await;
+    let ctl_msg_tx = msg_tx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = ctl.run().await {
+            utils::msg::log_warn(&ctl_msg_tx, "ctl", format!("[ctl] Not started. Err: {e:#}"))
+                .await;
+        }
+    });
+
     web::Web::new(msg_tx.clone(), shutdown_notify.clone())
         .await
         .run()
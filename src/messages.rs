@@ -1,36 +1,95 @@
+use std::fmt;
+use std::str::FromStr;
+
 use log::Level::{Info, Warn};
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedSender};
+use tokio::sync::oneshot;
 
+use crate::cfg::Cfg;
 use crate::plugins::plugins_main::Plugins;
-use crate::utils;
+use crate::record::Recorder;
+use crate::utils::{
+    self,
+    dev_info::DevInfo,
+    nas_info::{NasInfo, NasState},
+    stats::Stats,
+    task::Task,
+    weather::City,
+};
 
 const MODULE: &str = "messages";
 
 pub const ACTION_ADD: &str = "add";
 pub const ACTION_APP_UPTIME: &str = "app_uptime";
 pub const ACTION_ARROW: &str = "arrow";
+pub const ACTION_CANCEL: &str = "cancel";
+pub const ACTION_CHECK: &str = "check";
 pub const ACTION_CLI: &str = "cli";
 pub const ACTION_CREATE: &str = "create";
 pub const ACTION_DEVICES: &str = "devices";
-pub const ACTION_FILE_MODIFY: &str = "file_modify";
-pub const ACTION_FILE_REMOVE: &str = "file_remove";
+pub const ACTION_DISABLE: &str = "disable";
+pub const ACTION_DONE: &str = "done";
+pub const ACTION_ENABLE: &str = "enable";
+pub const ACTION_FILE: &str = "file";
 pub const ACTION_GUI: &str = "gui";
+pub const ACTION_HELP: &str = "help";
+pub const ACTION_HISTORY: &str = "history";
 pub const ACTION_INIT: &str = "init";
+pub const ACTION_JOBS: &str = "jobs";
+pub const ACTION_JSON: &str = "json";
+pub const ACTION_LEVEL: &str = "level";
+pub const ACTION_LIST: &str = "list";
 pub const ACTION_LOG: &str = "log";
 pub const ACTION_NAS_STATE: &str = "nas_state";
 pub const ACTION_ONBOARD: &str = "onboard";
 pub const ACTION_PUBLISH: &str = "publish";
+pub const ACTION_RELOAD: &str = "reload";
+pub const ACTION_REMOVE: &str = "remove";
+pub const ACTION_RESULT: &str = "result";
+pub const ACTION_RUN: &str = "run";
+pub const ACTION_RUN_NOW: &str = "run-now";
 pub const ACTION_SELF_NAS_STATE: &str = "self_nas_state";
 pub const ACTION_SHOW: &str = "show";
+pub const ACTION_STATE: &str = "state";
+pub const ACTION_STATS: &str = "stats";
+pub const ACTION_STATUS: &str = "status";
+pub const ACTION_TAIL: &str = "tail";
 pub const ACTION_TAILSCALE_IP: &str = "tailscale_ip";
 pub const ACTION_TEMPERATURE: &str = "temperature";
+pub const ACTION_UNDONE: &str = "undone";
 pub const ACTION_VERSION: &str = "version";
 
+pub const EVENT_CFG_CHANGED: &str = "cfg.changed";
+pub const EVENT_DEVICE_APP_UPTIME: &str = "device.app_uptime";
+pub const EVENT_DEVICE_ONBOARD: &str = "device.onboard";
+pub const EVENT_DEVICE_TAILSCALE_IP: &str = "device.tailscale_ip";
+pub const EVENT_DEVICE_TEMPERATURE: &str = "device.temperature";
+pub const EVENT_DEVICE_VERSION: &str = "device.version";
+pub const EVENT_FILE_MODIFIED: &str = "file.modified";
+pub const EVENT_FILE_REMOVED: &str = "file.removed";
+pub const EVENT_MQTT_CONNECTED: &str = "mqtt.connected";
+pub const EVENT_NAS_SERVER: &str = "nas.server";
+pub const EVENT_NAS_STATE: &str = "nas.state";
+pub const EVENT_PLUGIN_DISABLED: &str = "plugin.disabled";
+pub const EVENT_PLUGIN_ENABLED: &str = "plugin.enabled";
+pub const EVENT_TODO_UPDATED: &str = "todo.updated";
+pub const EVENT_WEATHER_UPDATED: &str = "weather.updated";
+
+const CMD_PREFIX: &str = "p";
+pub const QUERY_PREFIX: &str = "query";
+pub const EXIT_CMDS: &[&str] = &["exit", "q", "quit"];
+
 #[derive(Debug)]
 pub enum Data {
     Log(Log),
     Cmd(Cmd),
+    Command(Command),
+    Query(Query),
+    Event(Event),
+    // gets a copy of every log from now on, until the receiver is dropped
+    Tap(UnboundedSender<Msg>),
 }
 
 #[derive(Debug)]
@@ -51,40 +110,400 @@ pub struct Cmd {
     pub cmd: String,
 }
 
+// Command: `p <plugin> <action> [args...]` in a structured form
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+    pub plugin: String,
+    pub action: String,
+    pub args: Vec<String>,
+}
+
+// Query: a Command whose answer goes back through `reply_tx`
+#[derive(Debug)]
+pub struct Query {
+    pub cmd: Command,
+    pub reply_tx: oneshot::Sender<QueryResult>,
+}
+
+pub type QueryResult = Result<Reply, CommandError>;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    Plugins(Vec<String>),
+    Devices(Vec<DevInfo>),
+    Nas {
+        nas_server: String,
+        nas_state: NasState,
+        nas_infos: Vec<NasInfo>,
+    },
+    Cities(Vec<City>),
+    Tasks(Vec<Task>),
+    Stats(Stats),
+}
+
+// Event: published once, delivered to every plugin subscribed to its topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    FileModified {
+        filename: String,
+    },
+    FileRemoved {
+        filename: String,
+    },
+    DeviceOnboard {
+        name: String,
+        onboard: bool,
+    },
+    DeviceVersion {
+        name: String,
+        version: String,
+    },
+    DeviceTailscaleIp {
+        name: String,
+        tailscale_ip: String,
+    },
+    DeviceTemperature {
+        name: String,
+        temperature: f32,
+    },
+    DeviceAppUptime {
+        name: String,
+        app_uptime: u64,
+    },
+    WeatherUpdated {
+        city: City,
+    },
+    NasServer {
+        nas_server: String,
+    },
+    // name is None for the state of this client
+    NasState {
+        name: Option<String>,
+        nas_state: NasState,
+    },
+    TodoUpdated {
+        task: Task,
+    },
+    // the broker accepted the connection, also after a reconnect
+    MqttConnected {
+        broker: String,
+    },
+    // cfg.json was edited, delivered to every plugin as `reconfigure`
+    CfgChanged {
+        cfg: Box<Cfg>,
+    },
+    // sent as it leaves the routing, before the logs of the disable
+    PluginDisabled {
+        name: String,
+    },
+    // sent once it is routed to, at start and on enable
+    PluginEnabled {
+        name: String,
+    },
+}
+
+impl Event {
+    pub fn topic(&self) -> &'static str {
+        match self {
+            Event::FileModified { .. } => EVENT_FILE_MODIFIED,
+            Event::FileRemoved { .. } => EVENT_FILE_REMOVED,
+            Event::DeviceOnboard { .. } => EVENT_DEVICE_ONBOARD,
+            Event::DeviceVersion { .. } => EVENT_DEVICE_VERSION,
+            Event::DeviceTailscaleIp { .. } => EVENT_DEVICE_TAILSCALE_IP,
+            Event::DeviceTemperature { .. } => EVENT_DEVICE_TEMPERATURE,
+            Event::DeviceAppUptime { .. } => EVENT_DEVICE_APP_UPTIME,
+            Event::WeatherUpdated { .. } => EVENT_WEATHER_UPDATED,
+            Event::NasServer { .. } => EVENT_NAS_SERVER,
+            Event::NasState { .. } => EVENT_NAS_STATE,
+            Event::TodoUpdated { .. } => EVENT_TODO_UPDATED,
+            Event::MqttConnected { .. } => EVENT_MQTT_CONNECTED,
+            Event::CfgChanged { .. } => EVENT_CFG_CHANGED,
+            Event::PluginDisabled { .. } => EVENT_PLUGIN_DISABLED,
+            Event::PluginEnabled { .. } => EVENT_PLUGIN_ENABLED,
+        }
+    }
+}
+
+// `device.onboard` matches `device.onboard`, `device.*` and `*`
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => topic.starts_with(prefix),
+        None => pattern == topic,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Syntax(String),
+    NotCommand(String),
+    MissingPlugin,
+    UnknownPlugin(String),
+    MissingAction,
+    UnknownAction(String),
+    MissingArg(String),
+    InvalidArg {
+        name: String,
+        value: String,
+        expected: String,
+    },
+    NoReply,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Syntax(e) => write!(f, "Syntax error ({e})"),
+            CommandError::NotCommand(word) => write!(f, "Unknown command ({word})"),
+            CommandError::MissingPlugin => write!(f, "Missing plugin name"),
+            CommandError::UnknownPlugin(plugin) => write!(f, "Unknown plugin name (`{plugin}`)"),
+            CommandError::MissingAction => write!(f, "Missing action"),
+            CommandError::UnknownAction(action) => write!(f, "Unknown action ({action})"),
+            CommandError::MissingArg(name) => write!(f, "Missing argument `{name}`"),
+            CommandError::InvalidArg {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid argument `{name}` (got `{value}`, expected {expected})"
+            ),
+            CommandError::NoReply => write!(f, "No reply"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl Command {
+    pub fn new(plugin: &str, action: &str) -> Self {
+        Self {
+            plugin: plugin.to_string(),
+            action: action.to_string(),
+            args: vec![],
+        }
+    }
+
+    pub fn arg(mut self, arg: impl ToString) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn from_words(words: Vec<String>) -> Result<Self, CommandError> {
+        let mut words = words.into_iter();
+        match words.next() {
+            Some(prefix) if prefix == CMD_PREFIX => (),
+            Some(word) => return Err(CommandError::NotCommand(word)),
+            None => return Err(CommandError::MissingPlugin),
+        }
+        Self::from_args(words)
+    }
+
+    // `<plugin> <action> [args...]`, without the leading prefix
+    pub fn from_args(mut words: impl Iterator<Item = String>) -> Result<Self, CommandError> {
+        let plugin = words.next().ok_or(CommandError::MissingPlugin)?;
+        let action = words.next().ok_or(CommandError::MissingAction)?;
+
+        Ok(Self {
+            plugin,
+            action,
+            args: words.collect(),
+        })
+    }
+
+    pub fn get_str(&self, idx: usize, name: &str) -> Result<&str, CommandError> {
+        self.args
+            .get(idx)
+            .map(|arg| arg.as_str())
+            .ok_or_else(|| CommandError::MissingArg(name.to_string()))
+    }
+
+    pub fn get<T: FromStr>(&self, idx: usize, name: &str) -> Result<T, CommandError> {
+        let value = self.get_str(idx, name)?;
+        value.parse::<T>().map_err(|_| CommandError::InvalidArg {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: short_type_name::<T>(),
+        })
+    }
+
+    pub fn get_bool(&self, idx: usize, name: &str) -> Result<bool, CommandError> {
+        match self.get_str(idx, name)? {
+            "1" | "true" | "on" => Ok(true),
+            "0" | "false" | "off" => Ok(false),
+            value => Err(CommandError::InvalidArg {
+                name: name.to_string(),
+                value: value.to_string(),
+                expected: "bool".to_string(),
+            }),
+        }
+    }
+
+    // render back to the text form, quoting args when needed
+    pub fn render(&self) -> String {
+        let words = [CMD_PREFIX, &self.plugin, &self.action]
+            .into_iter()
+            .chain(self.args.iter().map(|arg| arg.as_str()));
+        shell_words::join(words)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+// an action a plugin accepts, e.g. `add <name> <latitude> <longitude>`.
+// usage: `<arg>` is required, a bare word is a required keyword, and
+// everything from the first `[` on is optional
+#[derive(Debug, Clone, Copy)]
+pub struct ActionSpec {
+    pub action: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+}
+
+impl ActionSpec {
+    pub const fn new(action: &'static str, usage: &'static str, help: &'static str) -> Self {
+        Self {
+            action,
+            usage,
+            help,
+        }
+    }
+
+    pub fn find<'a>(specs: &'a [ActionSpec], action: &str) -> Option<&'a ActionSpec> {
+        specs.iter().find(|spec| spec.action == action)
+    }
+
+    // the first required argument the command lacks, by name
+    pub fn validate(&self, cmd: &Command) -> Result<(), CommandError> {
+        let mut required = self
+            .usage
+            .split_whitespace()
+            .take_while(|word| !word.starts_with('['));
+        match required.nth(cmd.args.len()) {
+            Some(word) => Err(CommandError::MissingArg(
+                word.trim_start_matches('<')
+                    .trim_end_matches("...")
+                    .trim_end_matches('>')
+                    .to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // the most arguments the usage takes, None when the last one repeats
+    pub fn max_args(&self) -> Option<usize> {
+        let words: Vec<&str> = self.usage.split_whitespace().collect();
+        if words.iter().any(|word| word.contains("...")) {
+            None
+        } else {
+            Some(words.len())
+        }
+    }
+
+    pub fn render(&self) -> String {
+        format!("{} {}", self.action, self.usage)
+            .trim_end()
+            .to_string()
+    }
+}
+
+// split the text form into words, dropping `# comments`
+pub fn split(text: &str) -> Result<Vec<String>, CommandError> {
+    shell_words::split(text).map_err(|e| CommandError::Syntax(e.to_string()))
+}
+
+fn short_type_name<T>() -> String {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name).to_string()
+}
+
 pub struct Messages {
     msg_tx: Sender<Msg>,
 }
 
 impl Messages {
+    // with `replay_rx`, the recording drives the plugins: live messages other
+    // than logs and queries are dropped, so every plugin sees the recorded
+    // input again instead of adding its own output to it
     pub async fn new(
         msg_tx: Sender<Msg>,
         mut msg_rx: Receiver<Msg>,
         shutdown_notify: broadcast::Sender<()>,
+        mut plugins: Plugins,
+        mut recorder: Option<Recorder>,
+        mut replay_rx: Option<Receiver<Msg>>,
     ) -> Self {
-        let mut plugins = Plugins::new(msg_tx.clone(), shutdown_notify.clone()).await;
-
         let msg_tx_clone = msg_tx.clone();
+        let replaying = replay_rx.is_some();
+        let mut taps: Vec<UnboundedSender<Msg>> = vec![];
 
+        // subscribe up front: the signal comes while a message is being handled
+        let mut shutdown_rx = shutdown_notify.subscribe();
         tokio::spawn(async move {
             loop {
-                let shutdown_notify_clone = shutdown_notify.clone();
-                let mut shutdown_rx = shutdown_notify_clone.subscribe();
-
-                tokio::select! {
+                let msg = tokio::select! {
                     maybe_msg = msg_rx.recv() => {
-                        if let Some(msg) = maybe_msg {
-                            match msg.data {
-                                Data::Log(ref log) => parse_log(log, msg.ts, &msg.module, &msg_tx_clone).await,
-                                Data::Cmd(ref _cmd) => parse_cmd(&msg, &msg_tx_clone, &mut plugins, shutdown_notify_clone).await,
+                        match maybe_msg {
+                            Some(msg) if replaying && !matches!(msg.data, Data::Log(_) | Data::Query(_) | Data::Tap(_)) => continue,
+                            Some(msg) => msg,
+                            None => break, // msg_rx channel closed
+                        }
+                    }
+
+                    maybe_msg = recv_replay(&mut replay_rx) => {
+                        match maybe_msg {
+                            Some(msg) => msg,
+                            None => {
+                                replay_rx = None;
+                                continue;
                             }
-                        } else {
-                            break; // msg_rx channel closed
                         }
                     }
 
                     _ = shutdown_rx.recv() => {
                         break;
                     }
+                };
+
+                plugins.sample_queue(msg_tx_clone.max_capacity() - msg_tx_clone.capacity());
+
+                #[allow(clippy::collapsible_if)]
+                if let Some(rec) = &mut recorder {
+                    if let Err(e) = rec.record(&msg) {
+                        warn(
+                            &msg_tx_clone,
+                            format!(
+                                "[{MODULE}] Stop recording to `{}`. Err: {e}",
+                                rec.filename()
+                            ),
+                        )
+                        .await;
+                        recorder = None;
+                    }
+                }
+
+                match msg.data {
+                    Data::Log(ref log) => {
+                        taps.retain(|tap| {
+                            tap.send(Msg {
+                                ts: msg.ts,
+                                module: msg.module.clone(),
+                                data: Data::Log(log.clone()),
+                            })
+                            .is_ok()
+                        });
+                        parse_log(log, msg.ts, &msg.module, &mut plugins).await
+                    }
+                    Data::Cmd(_) => parse_cmd(&msg, &msg_tx_clone, &mut plugins).await,
+                    Data::Command(_) => plugins.handle_cmd(msg).await,
+                    Data::Event(_) => plugins.handle_event(msg),
+                    Data::Query(_) => plugins.handle_query(msg),
+                    Data::Tap(tap) => taps.push(tap),
                 }
             }
         });
@@ -107,51 +526,96 @@ impl Messages {
     }
 }
 
-async fn parse_log(log: &Log, ts: u64, module: &str, msg_tx: &Sender<Msg>) {
+async fn recv_replay(replay_rx: &mut Option<Receiver<Msg>>) -> Option<Msg> {
+    match replay_rx {
+        Some(replay_rx) => replay_rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+// straight to the log plugin's mailbox, keeping logs in order with everything else
+async fn parse_log(log: &Log, ts: u64, module: &str, plugins: &mut Plugins) {
     let msg = Msg {
         ts,
         module: module.to_string(),
-        data: Data::Cmd(Cmd {
-            cmd: format!("p log {ACTION_LOG} {} '{}'", log.level, log.msg),
-        }),
+        data: Data::Command(Command::new("log", ACTION_LOG).arg(log.level).arg(&log.msg)),
     };
-    let _ = msg_tx.send(msg).await;
+    plugins.handle_cmd(msg).await;
 }
 
-async fn parse_cmd(
-    msg: &Msg,
-    msg_tx: &Sender<Msg>,
-    plugins: &mut Plugins,
-    shutdown_notify: broadcast::Sender<()>,
-) {
+async fn parse_cmd(msg: &Msg, msg_tx: &Sender<Msg>, plugins: &mut Plugins) {
     if let Data::Cmd(cmd) = &msg.data {
-        let cmd = &cmd.cmd;
-        let cmd = cmd
-            .split_once('#')
-            .map(|(before, _)| before.trim_end())
-            .unwrap_or(cmd);
-        let cmd_parts: Vec<&str> = cmd.split_whitespace().collect();
-        if cmd_parts.is_empty() {
+        let words = match split(&cmd.cmd) {
+            Ok(words) => words,
+            Err(e) => {
+                warn(msg_tx, format!("[{MODULE}] {e} for cmd `{}`.", cmd.cmd)).await;
+                return;
+            }
+        };
+        let Some(command) = words.first() else {
             return;
-        }
+        };
 
-        let command = cmd_parts[0];
-        match command {
-            "p" => plugins.handle_cmd(msg).await,
-            "exit" | "q" | "quit" => {
-                let _ = shutdown_notify.send(());
-            }
-            _ => {
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Log(Log {
-                        level: Warn,
-                        msg: format!("[{MODULE}] Unknown command: {command}"),
-                    }),
-                };
-                let _ = msg_tx.send(msg).await;
-            }
+        match command.as_str() {
+            CMD_PREFIX => match Command::from_words(words) {
+                Ok(command) => {
+                    let msg = Msg {
+                        ts: msg.ts,
+                        module: msg.module.clone(),
+                        data: Data::Command(command),
+                    };
+                    plugins.handle_cmd(msg).await;
+                }
+                Err(e) => warn(msg_tx, format!("[{MODULE}] {e} for cmd `{}`.", cmd.cmd)).await,
+            },
+            QUERY_PREFIX => match Command::from_args(words.into_iter().skip(1)) {
+                Ok(command) => {
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    plugins.handle_query(Msg {
+                        ts: msg.ts,
+                        module: msg.module.clone(),
+                        data: Data::Query(Query {
+                            cmd: command.clone(),
+                            reply_tx,
+                        }),
+                    });
+
+                    // wait for the reply off the dispatcher
+                    let msg_tx = msg_tx.clone();
+                    tokio::spawn(async move {
+                        match reply_rx.await.unwrap_or(Err(CommandError::NoReply)) {
+                            Ok(reply) => {
+                                let reply = serde_json::to_string(&reply).unwrap_or_default();
+                                info(&msg_tx, format!("[{MODULE}] {command}: {reply}")).await;
+                            }
+                            Err(e) => {
+                                warn(&msg_tx, format!("[{MODULE}] {e} for query `{command}`."))
+                                    .await
+                            }
+                        }
+                    });
+                }
+                Err(e) => warn(msg_tx, format!("[{MODULE}] {e} for cmd `{}`.", cmd.cmd)).await,
+            },
+            command if EXIT_CMDS.contains(&command) => plugins.shutdown().await,
+            _ => warn(msg_tx, format!("[{MODULE}] Unknown command: {command}")).await,
         }
     }
 }
+
+async fn log(msg_tx: &Sender<Msg>, level: log::Level, msg: String) {
+    let msg = Msg {
+        ts: utils::time::ts(),
+        module: MODULE.to_string(),
+        data: Data::Log(Log { level, msg }),
+    };
+    let _ = msg_tx.send(msg).await;
+}
+
+async fn info(msg_tx: &Sender<Msg>, msg: String) {
+    log(msg_tx, Info, msg).await;
+}
+
+async fn warn(msg_tx: &Sender<Msg>, msg: String) {
+    log(msg_tx, Warn, msg).await;
+}
@@ -1,4 +1,6 @@
+pub mod plugin_cfg;
 pub mod plugin_cli;
+pub mod plugin_cron;
 pub mod plugin_devices;
 pub mod plugin_infos;
 pub mod plugin_log;
@@ -13,3 +15,4 @@ pub mod plugin_system;
 pub mod plugin_todos;
 pub mod plugin_weather;
 pub mod plugins_main;
+pub mod registry;
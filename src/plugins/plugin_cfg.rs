@@ -0,0 +1,168 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use async_trait::async_trait;
+use notify::RecursiveMode;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::Sender;
+use tokio::time::{Duration, sleep};
+
+use crate::cfg;
+use crate::messages::{
+    ACTION_RELOAD, ACTION_SHOW, ActionSpec, Command, CommandError, Data, Event, Msg,
+};
+use crate::plugins::plugins_main::{self, Plugin};
+use crate::utils;
+
+const MODULE: &str = "cfg";
+// editors write a file in a few steps, wait for the last one
+const RELOAD_DELAY: Duration = Duration::from_millis(300);
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_RELOAD,
+        "",
+        "Read the cfg files again, done by itself when they change",
+    ),
+    ActionSpec::new(
+        ACTION_SHOW,
+        "",
+        "List the settings and where they came from",
+    ),
+];
+
+#[derive(Debug)]
+pub struct PluginUnit {
+    name: String,
+    msg_tx: Sender<Msg>,
+}
+
+impl PluginUnit {
+    pub async fn new(msg_tx: Sender<Msg>, shutdown_tx: broadcast::Sender<()>) -> Self {
+        utils::msg::log_new(&msg_tx, MODULE).await;
+
+        for key in cfg::ignored_env() {
+            utils::msg::log_warn(
+                &msg_tx,
+                MODULE,
+                format!("[{MODULE}] Ignored {key}: unknown setting"),
+            )
+            .await;
+        }
+
+        watch(&msg_tx, shutdown_tx.subscribe()).await;
+
+        Self {
+            name: MODULE.to_owned(),
+            msg_tx,
+        }
+    }
+
+    async fn handle_cmd_reload(&mut self) -> Result<(), CommandError> {
+        let files = cfg::files().join("`, `");
+        match cfg::reload() {
+            Ok(Some(cfg)) => {
+                self.info(MODULE, format!("[{MODULE}] Reloaded `{files}`"))
+                    .await;
+                self.emit(MODULE, Event::CfgChanged { cfg: Box::new(cfg) })
+                    .await;
+            }
+            Ok(None) => {
+                self.debug(MODULE, format!("[{MODULE}] `{files}` unchanged"))
+                    .await;
+            }
+            Err(errors) => {
+                for e in errors {
+                    self.warn(MODULE, format!("[{MODULE}] Rejected. {e}")).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
+        for (path, value, source) in cfg::show() {
+            self.info(MODULE, format!("[{MODULE}] {path} = {value} ({source})"))
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+// the directory is watched rather than the files, editors often replace them.
+// the host file sits next to the base one
+async fn watch(msg_tx: &Sender<Msg>, shutdown_rx: broadcast::Receiver<()>) {
+    let files = cfg::files();
+    let file = &files[0];
+    let path = Path::new(file);
+    let file_names: Vec<OsString> = files
+        .iter()
+        .filter_map(|file| Path::new(file).file_name().map(|name| name.to_owned()))
+        .collect();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut rx = match utils::watch::watch(dir, RecursiveMode::NonRecursive, shutdown_rx) {
+        Ok(rx) => rx,
+        Err(e) => {
+            utils::msg::log_warn(
+                msg_tx,
+                MODULE,
+                format!("[{MODULE}] Failed to watch `{file}`. Err: {e}"),
+            )
+            .await;
+            return;
+        }
+    };
+
+    let msg_tx = msg_tx.clone();
+    tokio::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if !event.kind.is_modify() && !event.kind.is_create() {
+                continue;
+            }
+            if !event.paths.iter().any(|path| {
+                path.file_name()
+                    .is_some_and(|name| file_names.iter().any(|file_name| file_name == name))
+            }) {
+                continue;
+            }
+
+            sleep(RELOAD_DELAY).await;
+            while rx.try_recv().is_ok() {}
+            utils::msg::command(&msg_tx, MODULE, Command::new(MODULE, ACTION_RELOAD)).await;
+        }
+    });
+}
+
+#[async_trait]
+impl plugins_main::Plugin for PluginUnit {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    async fn send(&self, msg: Msg) {
+        let _ = self.msg_tx.send(msg).await;
+    }
+
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    async fn handle_cmd(&mut self, msg: &Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_RELOAD => self.handle_cmd_reload().await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
+            }
+        }
+    }
+}
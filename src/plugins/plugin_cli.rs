@@ -12,11 +12,28 @@ use tokio::task;
 use tokio::time::{Duration, sleep};
 
 use crate::cfg;
-use crate::messages::{ACTION_ARROW, ACTION_GUI, ACTION_INIT, Cmd, Data, Log, Msg};
-use crate::plugins::plugins_main;
-use crate::utils::{self, mode::Mode, panel};
+use crate::messages::{
+    ACTION_ARROW, ACTION_CLI, ACTION_GUI, ACTION_INIT, ActionSpec, Cmd, Command, CommandError,
+    Data, Log, Msg,
+};
+use crate::plugins::plugins_main::{self, Plugin};
+use crate::utils::{self, line_edit::LineEdit, mode::Mode, panel};
 
 const MODULE: &str = "cli";
+const PROMPT: &str = "> ";
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_INIT,
+        "<mode> [gui_panel]",
+        "Read commands, mode: gui <gui_panel> or cli",
+    ),
+    ActionSpec::new(
+        ACTION_ARROW,
+        "<arrow>",
+        "Arrow key, up/down walk the history, left/right move the cursor",
+    ),
+];
 
 fn prompt() {
     print!("{} > ", utils::time::ts_str(utils::time::ts()));
@@ -64,7 +81,7 @@ async fn start_input_loop_cli(msg_tx: Sender<Msg>, mut shutdown_rx: broadcast::R
 }
 
 async fn start_input_loop_gui(
-    output: Arc<Mutex<String>>,
+    line: Arc<Mutex<LineEdit>>,
     history: Arc<Mutex<Vec<String>>>,
     history_index: Arc<Mutex<usize>>,
     msg_tx: Sender<Msg>,
@@ -72,7 +89,7 @@ async fn start_input_loop_gui(
     gui_panel: String,
 ) {
     // 建立 channel 傳送 key event（spawn_blocking 到 async）
-    let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<KeyEvent>(32);
+    let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<Event>(32);
     use std::sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
@@ -86,9 +103,9 @@ async fn start_input_loop_gui(
             // 非同步 poll，避免卡住
             if event::poll(std::time::Duration::from_millis(100)).unwrap_or(false) {
                 #[allow(clippy::collapsible_if)]
-                if let Ok(Event::Key(key)) = event::read() {
-                    // 把 key 傳出去給 async task 處理
-                    if input_tx.blocking_send(key).is_err() {
+                if let Ok(event @ (Event::Key(_) | Event::Paste(_))) = event::read() {
+                    // 把 key 或 paste 傳出去給 async task 處理
+                    if input_tx.blocking_send(event).is_err() {
                         break;
                     }
                 }
@@ -103,57 +120,67 @@ async fn start_input_loop_gui(
 
     loop {
         tokio::select! {
-            Some(key) = input_rx.recv() => {
+            Some(event) = input_rx.recv() => {
+                let key = match event {
+                    Event::Paste(text) => {
+                        let mut line = line.lock().await;
+                        line.insert_str(&text);
+                        update_line(&msg_tx, &gui_panel, &line).await;
+                        continue;
+                    }
+                    Event::Key(key) => key,
+                    _ => continue,
+                };
+                {
+                    let mut line = line.lock().await;
+                    if edit(&mut line, &key) {
+                        update_line(&msg_tx, &gui_panel, &line).await;
+                        continue;
+                    }
+                }
+
                 if key.modifiers == KeyModifiers::CONTROL {
                     let action = match key.code {
-                        KeyCode::Up => Some("location up"),
-                        KeyCode::Down => Some("location down"),
-                        KeyCode::Left => Some("location left"),
-                        KeyCode::Right => Some("location right"),
-                        KeyCode::Char('d') => Some("size +x"),
-                        KeyCode::Char('a') => Some("size -x"),
-                        KeyCode::Char('s') => Some("size +y"),
-                        KeyCode::Char('w') => Some("size -y"),
-                        KeyCode::Char('c') => Some("output_clear"),
+                        KeyCode::Up => Some(Command::new("panels", "location").arg("up")),
+                        KeyCode::Down => Some(Command::new("panels", "location").arg("down")),
+                        KeyCode::Left => Some(Command::new("panels", "location").arg("left")),
+                        KeyCode::Right => Some(Command::new("panels", "location").arg("right")),
+                        KeyCode::Char('d') => Some(Command::new("panels", "size").arg("+x")),
+                        KeyCode::Char('a') => Some(Command::new("panels", "size").arg("-x")),
+                        KeyCode::Char('s') => Some(Command::new("panels", "size").arg("+y")),
+                        // ctrl-w deletes a word, x sits below s
+                        KeyCode::Char('x') => Some(Command::new("panels", "size").arg("-y")),
+                        KeyCode::Char('c') => Some(Command::new("panels", "output_clear")),
                         _ => None
                     };
                     if let Some(action) = action {
-                        cmd(&msg_tx, format!("p panels {action}")).await;
+                        utils::msg::command(&msg_tx, MODULE, action).await;
                     }
                 } else {
                     match key.code {
-                        KeyCode::Tab => cmd(&msg_tx, "p panels tab".to_string()).await,
-                        KeyCode::Char(c) => {
-                            let mut output = output.lock().await;
-                            output.push(c);
-                            panel::output_update_gui_simple(MODULE, &msg_tx, &gui_panel, format!("> {output}")).await;
-                        }
-                        KeyCode::Backspace => {
-                            let mut output = output.lock().await;
-                            output.pop();
-                            panel::output_update_gui_simple(MODULE, &msg_tx, &gui_panel, format!("> {output}")).await;
-                        }
+                        KeyCode::Tab => utils::msg::command(&msg_tx, MODULE, Command::new("panels", "tab")).await,
                         KeyCode::Enter => {
-                            let mut output = output.lock().await;
+                            let mut line = line.lock().await;
                             let mut history = history.lock().await;
                             let mut history_index = history_index.lock().await;
 
+                            let output = line.take();
                             // ignore if the input is as the same as the last one
                             if history.is_empty()
-                                || *history.last().unwrap() != *output
+                                || *history.last().unwrap() != output
                             {
                                 history.push(output.clone());
                                 *history_index = history.len();
                             }
 
-                            cmd(&msg_tx, output.clone()).await;
-                            output.clear();
-                            panel::output_update_gui_simple(MODULE, &msg_tx, &gui_panel, format!("> {output}")).await;
+                            cmd(&msg_tx, output).await;
+                            update_line(&msg_tx, &gui_panel, &line).await;
                         }
-                        KeyCode::Left => cmd(&msg_tx, format!("p panels {ACTION_ARROW} left")).await,
-                        KeyCode::Right => cmd(&msg_tx, format!("p panels {ACTION_ARROW} right")).await,
-                        KeyCode::Up => cmd(&msg_tx, format!("p panels {ACTION_ARROW} up")).await,
-                        KeyCode::Down => cmd(&msg_tx, format!("p panels {ACTION_ARROW} down")).await,
+                        // left/right come back to move the cursor when the command panel is active
+                        KeyCode::Left => utils::msg::command(&msg_tx, MODULE, Command::new("panels", ACTION_ARROW).arg("left")).await,
+                        KeyCode::Right => utils::msg::command(&msg_tx, MODULE, Command::new("panels", ACTION_ARROW).arg("right")).await,
+                        KeyCode::Up => utils::msg::command(&msg_tx, MODULE, Command::new("panels", ACTION_ARROW).arg("up")).await,
+                        KeyCode::Down => utils::msg::command(&msg_tx, MODULE, Command::new("panels", ACTION_ARROW).arg("down")).await,
                         _ => {}
                     }
                 }
@@ -170,6 +197,34 @@ async fn start_input_loop_gui(
     let _ = input_task.await;
 }
 
+// the keys editing the line, false for the others
+fn edit(line: &mut LineEdit, key: &KeyEvent) -> bool {
+    match (key.modifiers, key.code) {
+        (KeyModifiers::CONTROL, KeyCode::Char('u')) => line.delete_to_start(),
+        (KeyModifiers::CONTROL, KeyCode::Char('w')) => line.delete_word(),
+        (KeyModifiers::CONTROL, _) => return false,
+        (_, KeyCode::Char(c)) => line.insert(c),
+        (_, KeyCode::Backspace) => line.backspace(),
+        (_, KeyCode::Delete) => line.delete(),
+        (_, KeyCode::Home) => line.home(),
+        (_, KeyCode::End) => line.end(),
+        _ => return false,
+    }
+
+    true
+}
+
+async fn update_line(msg_tx: &Sender<Msg>, gui_panel: &str, line: &LineEdit) {
+    panel::output_update_gui_cursor(
+        MODULE,
+        msg_tx,
+        gui_panel,
+        format!("{PROMPT}{}", line.text()),
+        PROMPT.len() + line.cursor_width(),
+    )
+    .await;
+}
+
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
@@ -178,7 +233,7 @@ pub struct PluginUnit {
     mode: Mode,
     started: bool,
     gui_panel: String,
-    output: Arc<Mutex<String>>,
+    line: Arc<Mutex<LineEdit>>,
     history: Arc<Mutex<Vec<String>>>,
     history_index: Arc<Mutex<usize>>,
 }
@@ -194,58 +249,139 @@ impl PluginUnit {
             mode: Mode::ModeGui,
             started: false,
             gui_panel: String::new(),
-            output: Arc::new(Mutex::new(String::new())),
+            line: Arc::new(Mutex::new(LineEdit::new())),
             history: Arc::new(Mutex::new(vec![])),
             history_index: Arc::new(Mutex::new(0)),
         }
     }
 
-    async fn handle_cmd_arrow(&mut self, cmd_parts: &[String]) {
-        if let Some(arrow) = cmd_parts.get(3) {
-            match arrow.as_str() {
-                "up" => {
-                    let mut output = self.output.lock().await;
-                    let history = self.history.lock().await;
-                    let mut history_index = self.history_index.lock().await;
-
-                    if *history_index > 0 {
-                        *history_index -= 1;
-                        *output = history[*history_index].clone();
+    async fn handle_cmd_arrow(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let arrow = cmd.get_str(0, "arrow")?;
+        let mut line = self.line.lock().await;
+        match arrow {
+            "up" => {
+                let history = self.history.lock().await;
+                let mut history_index = self.history_index.lock().await;
+
+                if *history_index > 0 {
+                    *history_index -= 1;
+                    line.set(&history[*history_index]);
+                }
+            }
+            "down" => {
+                let history = self.history.lock().await;
+                let mut history_index = self.history_index.lock().await;
+
+                if *history_index < history.len() {
+                    *history_index += 1;
+                    if *history_index < history.len() {
+                        line.set(&history[*history_index]);
+                    } else {
+                        line.take();
                     }
+                }
+            }
+            "left" => line.left(),
+            "right" => line.right(),
+            _ => return Ok(()),
+        }
+        update_line(&self.msg_tx, &self.gui_panel, &line).await;
 
-                    panel::output_update_gui_simple(
+        Ok(())
+    }
+
+    async fn handle_cmd_init(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        match cmd.get_str(0, "mode")? {
+            ACTION_GUI => {
+                // avoid re-entry
+                if self.started && self.mode == Mode::ModeGui {
+                    self.warn(
                         MODULE,
-                        &self.msg_tx,
-                        &self.gui_panel,
-                        format!("> {output}"),
+                        format!("[{MODULE}] Started and GUI mode already. Ignore."),
                     )
                     .await;
+                    return Ok(());
                 }
-                "down" => {
-                    let mut output = self.output.lock().await;
-                    let history = self.history.lock().await;
-                    let mut history_index = self.history_index.lock().await;
 
-                    if *history_index < history.len() {
-                        *history_index += 1;
-                        if *history_index < history.len() {
-                            *output = history[*history_index].clone();
-                        } else {
-                            output.clear();
+                let gui_panel = cmd.get_str(1, "gui_panel")?;
+                self.started = true;
+                self.mode = Mode::ModeGui;
+                self.gui_panel = gui_panel.to_string();
+
+                // update prompt
+                update_line(&self.msg_tx, &self.gui_panel, &*self.line.lock().await).await;
+
+                let shutdown_rx = self.shutdown_tx.subscribe();
+                let line_clone = Arc::clone(&self.line);
+                let history_clone = Arc::clone(&self.history);
+                let history_index_clone = Arc::clone(&self.history_index);
+                tokio::spawn(start_input_loop_gui(
+                    line_clone,
+                    history_clone,
+                    history_index_clone,
+                    self.msg_tx.clone(),
+                    shutdown_rx,
+                    self.gui_panel.clone(),
+                ));
+
+                self.info(
+                    MODULE,
+                    format!("[{MODULE}] init gui mode (panel: `{gui_panel}`)"),
+                )
+                .await;
+
+                // update sub_title
+                let msg_tx_clone = self.msg_tx.clone();
+                let mut shutdown_rx = self.shutdown_tx.subscribe();
+                let gui_panel_clone = self.gui_panel.clone();
+                tokio::spawn(async move {
+                    loop {
+                        select! {
+                            _ = sleep(Duration::from_secs(1)) => {
+                                let ts = utils::time::ts();
+                                let sub_title = format!(" - {} - {}", cfg::name(), utils::time::ts_str(ts));
+                                utils::msg::command(
+                                    &msg_tx_clone,
+                                    MODULE,
+                                    Command::new("panels", "sub_title").arg(&gui_panel_clone).arg(sub_title),
+                                )
+                                .await;
+                            }
+                            _ = shutdown_rx.recv() => {
+                                break;
+                            }
                         }
                     }
-
-                    panel::output_update_gui_simple(
+                });
+            }
+            ACTION_CLI => {
+                // avoid re-entry
+                if self.started && self.mode == Mode::ModeCli {
+                    self.warn(
                         MODULE,
-                        &self.msg_tx,
-                        &self.gui_panel,
-                        format!("> {output}"),
+                        format!("[{MODULE}] Started and CLI mode already. Ignore."),
                     )
                     .await;
+                    return Ok(());
                 }
-                _ => (),
+                self.started = true;
+                self.mode = Mode::ModeCli;
+
+                let shutdown_rx = self.shutdown_tx.subscribe();
+                tokio::spawn(start_input_loop_cli(self.msg_tx.clone(), shutdown_rx));
+
+                self.info(MODULE, format!("[{MODULE}] init cli mode")).await;
+            }
+            mode => {
+                return Err(CommandError::InvalidArg {
+                    name: "mode".to_string(),
+                    value: mode.to_string(),
+                    expected: format!("{ACTION_GUI}/{ACTION_CLI}"),
+                });
             }
         }
+
+        Ok(())
     }
 }
 
@@ -259,126 +395,23 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
-    async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let (Some(action), Some(mode)) = (cmd_parts.get(2), cmd_parts.get(3)) {
-                match action.as_str() {
-                    ACTION_INIT => match mode.as_str() {
-                        ACTION_GUI => {
-                            // avoid re-entry
-                            if self.started && self.mode == Mode::ModeGui {
-                                self.warn(
-                                    MODULE,
-                                    format!("[{MODULE}] Started and GUI mode already. Ignore."),
-                                )
-                                .await;
-                                return;
-                            }
-
-                            if let Some(gui_panel) = cmd_parts.get(4) {
-                                self.started = true;
-                                self.mode = Mode::ModeGui;
-                                self.gui_panel = gui_panel.to_string();
-
-                                // update prompt
-                                panel::output_update_gui_simple(
-                                    MODULE,
-                                    &self.msg_tx,
-                                    &self.gui_panel,
-                                    "> ".to_string(),
-                                )
-                                .await;
-
-                                let shutdown_rx = self.shutdown_tx.subscribe();
-                                let output_clone = Arc::clone(&self.output);
-                                let history_clone = Arc::clone(&self.history);
-                                let history_index_clone = Arc::clone(&self.history_index);
-                                tokio::spawn(start_input_loop_gui(
-                                    output_clone,
-                                    history_clone,
-                                    history_index_clone,
-                                    self.msg_tx.clone(),
-                                    shutdown_rx,
-                                    self.gui_panel.clone(),
-                                ));
-
-                                self.info(
-                                    MODULE,
-                                    format!("[{MODULE}] init gui mode (panel: `{gui_panel}`)"),
-                                )
-                                .await;
-
-                                // update sub_title
-                                let msg_tx_clone = self.msg_tx.clone();
-                                let mut shutdown_rx = self.shutdown_tx.subscribe();
-                                let gui_panel_clone = self.gui_panel.clone();
-                                tokio::spawn(async move {
-                                    loop {
-                                        select! {
-                                            _ = sleep(Duration::from_secs(1)) => {
-                                                let ts = utils::time::ts();
-                                                let sub_title = format!(" - {} - {}", cfg::name(), utils::time::ts_str(ts));
-                                                let msg = Msg {
-                                                    ts,
-                                                    module: MODULE.to_string(),
-                                                    data: Data::Cmd(Cmd { cmd: format!("p panels sub_title {gui_panel_clone} '{sub_title}'") }),
-                                                };
-                                                let _ = msg_tx_clone.send(msg).await;
-                                            }
-                                            _ = shutdown_rx.recv() => {
-                                                println!("Shutdown signal received. Exiting task.");
-                                                break;
-                                            }
-                                        }
-                                    }
-                                });
-                            }
-                        }
-                        "cli" => {
-                            // avoid re-entry
-                            if self.started && self.mode == Mode::ModeCli {
-                                self.warn(
-                                    MODULE,
-                                    format!("[{MODULE}] Started and CLI mode already. Ignore."),
-                                )
-                                .await;
-                                return;
-                            }
-                            self.started = true;
-                            self.mode = Mode::ModeCli;
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
 
-                            let shutdown_rx = self.shutdown_tx.subscribe();
-                            tokio::spawn(start_input_loop_cli(self.msg_tx.clone(), shutdown_rx));
+    fn replays_init(&self) -> bool {
+        true
+    }
 
-                            self.info(MODULE, format!("[{MODULE}] init cli mode")).await;
-                        }
-                        _ => {
-                            self.warn(
-                                MODULE,
-                                format!("[{MODULE}] Unknown mode ({mode}) for cmd `{}`.", cmd.cmd),
-                            )
-                            .await;
-                        }
-                    },
-                    ACTION_ARROW => self.handle_cmd_arrow(&cmd_parts).await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+    async fn handle_cmd(&mut self, msg: &Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init(cmd).await,
+                ACTION_ARROW => self.handle_cmd_arrow(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Local, TimeZone};
+use log::Level;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::Sender;
+use tokio::{
+    select,
+    time::{Duration, sleep},
+};
+
+use crate::exec::{self, ExecResult};
+use crate::messages::{
+    ACTION_ADD, ACTION_CHECK, ACTION_HISTORY, ACTION_LIST, ACTION_REMOVE, ACTION_RESULT,
+    ACTION_RUN_NOW, ActionSpec, Command, CommandError, Data, Msg,
+};
+use crate::plugins::plugins_main::{self, Plugin};
+use crate::utils::{self, cron::Schedule};
+
+const MODULE: &str = "cron";
+// runs kept per job
+const HISTORY_SIZE: usize = 10;
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_ADD,
+        "<schedule> <cmd>...",
+        "Run a command on a cron schedule or @boot, e.g. \"0 7 * * *\" p weather update",
+    ),
+    ActionSpec::new(ACTION_LIST, "", "List the jobs, next and last runs"),
+    ActionSpec::new(ACTION_REMOVE, "<id>", "Remove a job"),
+    ActionSpec::new(ACTION_RUN_NOW, "<id>", "Run a job now, off its schedule"),
+    ActionSpec::new(ACTION_HISTORY, "<id>", "Show the last runs of a job"),
+    ActionSpec::new(ACTION_CHECK, "", "Run the jobs due, done every minute"),
+    ActionSpec::new(
+        ACTION_RESULT,
+        "<id> <ok> <summary>",
+        "Record how a run went, sent by the run itself",
+    ),
+];
+
+#[derive(Debug)]
+struct Run {
+    ts: u64,
+    ok: bool,
+    // the warning of a failed run, the last log of a good one
+    summary: String,
+}
+
+#[derive(Debug)]
+struct CronJob {
+    id: u32,
+    schedule: Schedule,
+    cmd: String,
+    // None for @boot once it ran
+    next: Option<u64>,
+    runs: u64,
+    // oldest first
+    history: VecDeque<Run>,
+}
+
+#[derive(Debug)]
+pub struct PluginUnit {
+    name: String,
+    msg_tx: Sender<Msg>,
+    jobs: Vec<CronJob>,
+    last_id: u32,
+}
+
+impl PluginUnit {
+    pub async fn new(msg_tx: Sender<Msg>, shutdown_tx: broadcast::Sender<()>) -> Self {
+        utils::msg::log_new(&msg_tx, MODULE).await;
+
+        start_checking(&msg_tx, shutdown_tx.subscribe());
+
+        Self {
+            name: MODULE.to_owned(),
+            msg_tx,
+            jobs: vec![],
+            last_id: 0,
+        }
+    }
+
+    async fn handle_cmd_add(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let schedule_str = cmd.get_str(0, "schedule")?;
+        let schedule =
+            schedule_str
+                .parse::<Schedule>()
+                .map_err(|expected| CommandError::InvalidArg {
+                    name: "schedule".to_string(),
+                    value: schedule_str.to_string(),
+                    expected,
+                })?;
+        // `p weather update` as one argument or as several
+        let job_cmd = match &cmd.args[1..] {
+            [job_cmd] => job_cmd.clone(),
+            words => shell_words::join(words),
+        };
+        if let Err(e) = utils::script::check_cmd(&job_cmd) {
+            self.warn(MODULE, format!("[{MODULE}] {e} for cmd `{job_cmd}`"))
+                .await;
+            return Ok(());
+        }
+
+        self.last_id += 1;
+        let job = CronJob {
+            id: self.last_id,
+            next: next_ts(&schedule, utils::time::ts()),
+            schedule,
+            cmd: job_cmd,
+            runs: 0,
+            history: VecDeque::new(),
+        };
+        self.info(
+            MODULE,
+            format!(
+                "[{MODULE}] Add {}: `{}` `{}`, next {}",
+                job.id,
+                job.schedule,
+                job.cmd,
+                next_str(job.next)
+            ),
+        )
+        .await;
+
+        // at boot is when the init script adds it
+        if job.schedule.is_boot() {
+            run(&self.msg_tx, job.id, &job.cmd);
+        }
+        self.jobs.push(job);
+
+        Ok(())
+    }
+
+    async fn handle_cmd_list(&mut self) -> Result<(), CommandError> {
+        self.info(
+            MODULE,
+            format!(
+                "{:<4} {:<16} {:<16} {:<20} {}",
+                "Id", "Schedule", "Next", "Last", "Cmd"
+            ),
+        )
+        .await;
+        for job in &self.jobs {
+            let last = match job.history.back() {
+                Some(run) => format!(
+                    "{} {} ({})",
+                    utils::time::ts_str(run.ts),
+                    ok_str(run.ok),
+                    job.runs
+                ),
+                None => "-".to_string(),
+            };
+            self.info(
+                MODULE,
+                format!(
+                    "{:<4} {:<16} {:<16} {last:<20} {}",
+                    job.id,
+                    job.schedule.to_string(),
+                    next_str(job.next),
+                    job.cmd
+                ),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_remove(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let id = cmd.get::<u32>(0, "id")?;
+        let Some(idx) = self.jobs.iter().position(|job| job.id == id) else {
+            self.warn(MODULE, format!("[{MODULE}] No job {id}")).await;
+            return Ok(());
+        };
+
+        let job = self.jobs.remove(idx);
+        self.info(
+            MODULE,
+            format!("[{MODULE}] Remove {id}: `{}` `{}`", job.schedule, job.cmd),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn handle_cmd_run_now(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let id = cmd.get::<u32>(0, "id")?;
+        match self.jobs.iter().find(|job| job.id == id) {
+            Some(job) => run(&self.msg_tx, job.id, &job.cmd),
+            None => self.warn(MODULE, format!("[{MODULE}] No job {id}")).await,
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_history(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let id = cmd.get::<u32>(0, "id")?;
+        let Some(job) = self.jobs.iter().find(|job| job.id == id) else {
+            self.warn(MODULE, format!("[{MODULE}] No job {id}")).await;
+            return Ok(());
+        };
+
+        self.info(
+            MODULE,
+            format!(
+                "[{MODULE}] {id}: `{}` `{}`, {} runs",
+                job.schedule, job.cmd, job.runs
+            ),
+        )
+        .await;
+        for run in &job.history {
+            self.info(
+                MODULE,
+                format!(
+                    "{} {:<6} {}",
+                    utils::time::ts_str_no_tz_no_sec(run.ts),
+                    ok_str(run.ok),
+                    run.summary
+                ),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    // each job due runs once, however many minutes it missed
+    async fn handle_cmd_check(&mut self) -> Result<(), CommandError> {
+        let now = utils::time::ts();
+        for job in &mut self.jobs {
+            if job.next.is_some_and(|next| next <= now) {
+                run(&self.msg_tx, job.id, &job.cmd);
+                job.next = next_ts(&job.schedule, now);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_result(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let id = cmd.get::<u32>(0, "id")?;
+        let ok = cmd.get_bool(1, "ok")?;
+        let summary = cmd.get_str(2, "summary")?.to_string();
+        // removed while running
+        let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+            return Ok(());
+        };
+
+        let msg = match summary.as_str() {
+            "" => format!("[{MODULE}] Ran {id} `{}`: {}", job.cmd, ok_str(ok)),
+            _ => format!(
+                "[{MODULE}] Ran {id} `{}`: {}, {summary}",
+                job.cmd,
+                ok_str(ok)
+            ),
+        };
+        job.runs += 1;
+        job.history.push_back(Run {
+            ts: utils::time::ts(),
+            ok,
+            summary,
+        });
+        if job.history.len() > HISTORY_SIZE {
+            job.history.pop_front();
+        }
+
+        if ok {
+            self.info(MODULE, msg).await;
+        } else {
+            self.warn(MODULE, msg).await;
+        }
+
+        Ok(())
+    }
+}
+
+// at the start of every minute
+fn start_checking(msg_tx: &Sender<Msg>, mut shutdown_rx: broadcast::Receiver<()>) {
+    let msg_tx_clone = msg_tx.clone();
+    tokio::spawn(async move {
+        loop {
+            let secs = 60 - utils::time::ts() % 60;
+            select! {
+                _ = sleep(Duration::from_secs(secs)) => {
+                    utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, ACTION_CHECK)).await;
+                }
+                _ = shutdown_rx.recv() => {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+// off the mailbox: the command may well be for cron itself
+fn run(msg_tx: &Sender<Msg>, id: u32, cmd: &str) {
+    let msg_tx = msg_tx.clone();
+    let cmd = cmd.to_string();
+    tokio::spawn(async move {
+        let result = exec::exec(&msg_tx, &cmd).await;
+        utils::msg::command(
+            &msg_tx,
+            MODULE,
+            Command::new(MODULE, ACTION_RESULT)
+                .arg(id)
+                .arg(result.ok)
+                .arg(summary(&result)),
+        )
+        .await;
+    });
+}
+
+fn summary(result: &ExecResult) -> String {
+    let failed = |level: &str| {
+        level
+            .parse::<Level>()
+            .is_ok_and(|level| level <= Level::Warn)
+    };
+    let log = if result.ok {
+        result.logs.last()
+    } else {
+        result.logs.iter().find(|log| failed(&log.level))
+    };
+    log.map(|log| log.msg.clone()).unwrap_or_default()
+}
+
+// in local time; a minute skipped by daylight saving moves on to the next
+fn next_ts(schedule: &Schedule, after: u64) -> Option<u64> {
+    let mut after = DateTime::from_timestamp(after as i64, 0)?
+        .with_timezone(&Local)
+        .naive_local();
+    loop {
+        let next = schedule.next_after(after)?;
+        match Local.from_local_datetime(&next).earliest() {
+            Some(next) => return Some(next.timestamp() as u64),
+            None => after = next,
+        }
+    }
+}
+
+fn next_str(next: Option<u64>) -> String {
+    next.map(utils::time::ts_str_no_tz_no_sec)
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn ok_str(ok: bool) -> &'static str {
+    if ok { "ok" } else { "failed" }
+}
+
+#[async_trait]
+impl plugins_main::Plugin for PluginUnit {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    async fn send(&self, msg: Msg) {
+        let _ = self.msg_tx.send(msg).await;
+    }
+
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    async fn handle_cmd(&mut self, msg: &Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_ADD => self.handle_cmd_add(cmd).await,
+                ACTION_LIST => self.handle_cmd_list().await,
+                ACTION_REMOVE => self.handle_cmd_remove(cmd).await,
+                ACTION_RUN_NOW => self.handle_cmd_run_now(cmd).await,
+                ACTION_HISTORY => self.handle_cmd_history(cmd).await,
+                ACTION_CHECK => self.handle_cmd_check().await,
+                ACTION_RESULT => self.handle_cmd_result(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
+            }
+        }
+    }
+}
@@ -2,8 +2,9 @@ use async_trait::async_trait;
 use tokio::sync::mpsc::Sender;
 
 use crate::messages::{
-    ACTION_APP_UPTIME, ACTION_DEVICES, ACTION_ONBOARD, ACTION_PUBLISH, ACTION_SHOW,
-    ACTION_TAILSCALE_IP, ACTION_TEMPERATURE, ACTION_VERSION, Data, Msg,
+    ACTION_APP_UPTIME, ACTION_LIST, ACTION_ONBOARD, ACTION_SHOW, ACTION_TAILSCALE_IP,
+    ACTION_TEMPERATURE, ACTION_VERSION, ActionSpec, Command, CommandError, Data, Event, Msg,
+    QueryResult, Reply,
 };
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{
@@ -13,6 +14,35 @@ use crate::utils::{
 
 const MODULE: &str = "devices";
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_SHOW, "", "List the devices"),
+    ActionSpec::new(
+        ACTION_ONBOARD,
+        "<name> <onboard>",
+        "Set a device on or off board",
+    ),
+    ActionSpec::new(
+        ACTION_VERSION,
+        "<name> <version>",
+        "Set a device's app version",
+    ),
+    ActionSpec::new(
+        ACTION_TAILSCALE_IP,
+        "<name> <tailscale_ip>",
+        "Set a device's tailscale ip",
+    ),
+    ActionSpec::new(
+        ACTION_TEMPERATURE,
+        "<name> <temperature>",
+        "Set a device's temperature",
+    ),
+    ActionSpec::new(
+        ACTION_APP_UPTIME,
+        "<name> <app_uptime>",
+        "Set a device's app uptime",
+    ),
+];
+
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
@@ -31,7 +61,7 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         for device in &self.devices {
             self.info(MODULE, format!("[{MODULE}] {}", device.name))
                 .await;
@@ -96,145 +126,146 @@ impl PluginUnit {
             )
             .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_onboard(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(onboard)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            let ts = utils::time::ts();
-            let onbard_str = onboard.clone();
-            let onboard = onboard == "1";
-
-            let changed =
-                if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
-                    let changed = onboard != device.onboard;
-
-                    device.ts = ts;
-                    device.onboard = onboard;
-                    changed
-                } else {
-                    let device_add = DevInfo {
-                        ts,
-                        name: name.to_string(),
-                        onboard,
-                        version: None,
-                        tailscale_ip: None,
-                        temperature: None,
-                        app_uptime: None,
-                    };
-                    self.devices.push(device_add.clone());
-                    true
-                };
+    async fn handle_cmd_onboard(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let onboard = cmd.get_bool(1, "onboard")?;
+        let ts = utils::time::ts();
 
-            if changed {
-                self.info(
-                    MODULE,
-                    format!(
-                        "[{MODULE}] {name} {} at {}",
-                        dev_info::onboard_str(onboard),
-                        utils::time::ts_str_full(ts),
-                    ),
-                )
-                .await;
+        let changed =
+            if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
+                let changed = onboard != device.onboard;
 
-                // someone onboard, publish immediately
-                if onboard {
-                    self.cmd(MODULE, format!("p system {ACTION_PUBLISH}")).await;
-                }
+                device.ts = ts;
+                device.onboard = onboard;
+                changed
+            } else {
+                let device_add = DevInfo {
+                    ts,
+                    name: name.to_string(),
+                    onboard,
+                    version: None,
+                    tailscale_ip: None,
+                    temperature: None,
+                    app_uptime: None,
+                };
+                self.devices.push(device_add.clone());
+                true
+            };
 
-                // update infos
-                self.cmd(
-                    MODULE,
-                    format!("p infos {ACTION_DEVICES} onboard {name} {onbard_str}"),
-                )
-                .await;
+        if changed {
+            self.info(
+                MODULE,
+                format!(
+                    "[{MODULE}] {name} {} at {}",
+                    dev_info::onboard_str(onboard),
+                    utils::time::ts_str_full(ts),
+                ),
+            )
+            .await;
 
-                // update nas
-                self.cmd(
-                    MODULE,
-                    format!("p nas {ACTION_DEVICES} onboard {name} {onbard_str}"),
-                )
-                .await;
-            }
+            self.emit(
+                MODULE,
+                Event::DeviceOnboard {
+                    name: name.to_string(),
+                    onboard,
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_version(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(version)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            let ts = utils::time::ts();
+    async fn handle_cmd_version(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let version = cmd.get_str(1, "version")?;
+        let ts = utils::time::ts();
 
-            if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
-                device.ts = ts;
-                device.version = Some(version.to_string());
+        if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
+            device.ts = ts;
+            device.version = Some(version.to_string());
 
-                // update infos
-                self.cmd(
-                    MODULE,
-                    format!("p infos {ACTION_DEVICES} version {name} {version}"),
-                )
-                .await;
-            }
+            self.emit(
+                MODULE,
+                Event::DeviceVersion {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                },
+            )
+            .await;
         }
-    }
 
-    async fn handle_cmd_tailscale_ip(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(tailscale_ip)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            let ts = utils::time::ts();
+        Ok(())
+    }
 
-            if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
-                device.ts = ts;
-                device.tailscale_ip = Some(tailscale_ip.to_string());
+    async fn handle_cmd_tailscale_ip(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let tailscale_ip = cmd.get_str(1, "tailscale_ip")?;
+        let ts = utils::time::ts();
 
-                // update infos
-                self.cmd(
-                    MODULE,
-                    format!("p infos {ACTION_DEVICES} {ACTION_TAILSCALE_IP} {name} {tailscale_ip}"),
-                )
-                .await;
+        if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
+            device.ts = ts;
+            device.tailscale_ip = Some(tailscale_ip.to_string());
 
-                // update nas
-                self.cmd(
-                    MODULE,
-                    format!("p nas {ACTION_DEVICES} {ACTION_TAILSCALE_IP} {name} {tailscale_ip}"),
-                )
-                .await;
-            }
+            self.emit(
+                MODULE,
+                Event::DeviceTailscaleIp {
+                    name: name.to_string(),
+                    tailscale_ip: tailscale_ip.to_string(),
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_temperature(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(temperature)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            let ts = utils::time::ts();
+    async fn handle_cmd_temperature(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let temperature = cmd.get::<f32>(1, "temperature")?;
+        let ts = utils::time::ts();
 
-            if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
-                device.ts = ts;
-                device.temperature = Some(temperature.parse::<f32>().unwrap());
+        if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
+            device.ts = ts;
+            device.temperature = Some(temperature);
 
-                // update infos
-                self.cmd(
-                    MODULE,
-                    format!("p infos {ACTION_DEVICES} {ACTION_TEMPERATURE} {name} {temperature}"),
-                )
-                .await;
-            }
+            self.emit(
+                MODULE,
+                Event::DeviceTemperature {
+                    name: name.to_string(),
+                    temperature,
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_app_uptime(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(app_uptime)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            let ts = utils::time::ts();
+    async fn handle_cmd_app_uptime(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let app_uptime = cmd.get::<u64>(1, "app_uptime")?;
+        let ts = utils::time::ts();
 
-            if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
-                device.ts = ts;
-                device.app_uptime = Some(app_uptime.parse::<u64>().unwrap());
+        if let Some(device) = self.devices.iter_mut().find(|device| device.name == *name) {
+            device.ts = ts;
+            device.app_uptime = Some(app_uptime);
 
-                // update infos
-                self.cmd(
-                    MODULE,
-                    format!("p infos {ACTION_DEVICES} {ACTION_APP_UPTIME} {name} {app_uptime}"),
-                )
-                .await;
-            }
+            self.emit(
+                MODULE,
+                Event::DeviceAppUptime {
+                    name: name.to_string(),
+                    app_uptime,
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 }
 
@@ -248,34 +279,30 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    async fn query(&mut self, cmd: &Command) -> QueryResult {
+        match cmd.action.as_str() {
+            ACTION_LIST => Ok(Reply::Devices(self.devices.clone())),
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        }
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_ONBOARD => self.handle_cmd_onboard(&cmd_parts).await,
-                    ACTION_VERSION => self.handle_cmd_version(&cmd_parts).await,
-                    ACTION_TAILSCALE_IP => self.handle_cmd_tailscale_ip(&cmd_parts).await,
-                    ACTION_TEMPERATURE => self.handle_cmd_temperature(&cmd_parts).await,
-                    ACTION_APP_UPTIME => self.handle_cmd_app_uptime(&cmd_parts).await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_ONBOARD => self.handle_cmd_onboard(cmd).await,
+                ACTION_VERSION => self.handle_cmd_version(cmd).await,
+                ACTION_TAILSCALE_IP => self.handle_cmd_tailscale_ip(cmd).await,
+                ACTION_TEMPERATURE => self.handle_cmd_temperature(cmd).await,
+                ACTION_APP_UPTIME => self.handle_cmd_app_uptime(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -5,9 +5,8 @@ use unicode_width::UnicodeWidthChar;
 
 use crate::cfg;
 use crate::messages::{
-    ACTION_ADD, ACTION_APP_UPTIME, ACTION_ARROW, ACTION_DEVICES, ACTION_GUI, ACTION_NAS_STATE,
-    ACTION_ONBOARD, ACTION_SHOW, ACTION_TAILSCALE_IP, ACTION_TEMPERATURE, ACTION_VERSION, Cmd,
-    Data, Msg,
+    ACTION_ARROW, ACTION_GUI, ACTION_LIST, ACTION_SHOW, ACTION_STATE, ActionSpec, Command,
+    CommandError, Data, EVENT_TODO_UPDATED, EVENT_WEATHER_UPDATED, Event, Msg, Reply,
 };
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{
@@ -16,21 +15,24 @@ use crate::utils::{
     nas_info::{NasInfo, NasState},
     panel,
     task::Task,
-    weather::{self, City, Weather, WeatherDaily},
+    weather::{self, City},
 };
 
 const MODULE: &str = "infos";
 const PAGES: u16 = 5;
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_GUI, "<gui_panel>", "Draw to a panel"),
+    ActionSpec::new(ACTION_SHOW, "", "Print the current page"),
+    ActionSpec::new(ACTION_ARROW, "<left_right>", "Switch pages: left/right"),
+];
+
+// the devices and nas pages are asked from their plugins on each draw
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
     msg_tx: Sender<Msg>,
     gui_panel: String,
-    devices: Vec<DevInfo>,
-    nas_server: String,
-    nas_state: NasState,     // For client
-    nas_infos: Vec<NasInfo>, // For server
     page_idx: u16,
     cities: Vec<City>,
     tasks: Vec<Task>,
@@ -44,27 +46,42 @@ impl PluginUnit {
             name: MODULE.to_owned(),
             msg_tx,
             gui_panel: String::new(),
-            devices: vec![],
-            nas_server: String::new(),
-            nas_state: NasState::Unsync,
-            nas_infos: vec![],
             page_idx: 0,
             cities: vec![],
             tasks: vec![],
         }
     }
 
+    // none while devices is not running
+    async fn devices(&self) -> Vec<DevInfo> {
+        match utils::msg::query(&self.msg_tx, MODULE, Command::new("devices", ACTION_LIST)).await {
+            Ok(Reply::Devices(devices)) => devices,
+            _ => vec![],
+        }
+    }
+
+    // the nas server, the state of this client and the infos of a server
+    async fn nas(&self) -> Option<(String, NasState, Vec<NasInfo>)> {
+        match utils::msg::query(&self.msg_tx, MODULE, Command::new("nas", ACTION_STATE)).await {
+            Ok(Reply::Nas {
+                nas_server,
+                nas_state,
+                nas_infos,
+            }) => Some((nas_server, nas_state, nas_infos)),
+            _ => None,
+        }
+    }
+
     async fn panel_output_update(&mut self) {
         // update sub_title
         let sub_title = format!(" - {}/{PAGES}", self.page_idx + 1);
-        let msg = Msg {
-            ts: utils::time::ts(),
-            module: MODULE.to_string(),
-            data: Data::Cmd(Cmd {
-                cmd: format!("p panels sub_title {} '{sub_title}'", self.gui_panel),
-            }),
-        };
-        let _ = self.msg_tx.send(msg).await;
+        self.command(
+            MODULE,
+            Command::new("panels", "sub_title")
+                .arg(&self.gui_panel)
+                .arg(sub_title),
+        )
+        .await;
 
         let mut output = String::new();
         match self.page_idx {
@@ -79,7 +96,7 @@ impl PluginUnit {
                     "App Uptime",
                     "Last Update"
                 );
-                for device in &self.devices {
+                for device in &self.devices().await {
                     output += &format!(
                         "\n{:<12} {:<7} {:<10} {:16} {:<7} {:13} {:<16}",
                         device.name,
@@ -92,10 +109,10 @@ impl PluginUnit {
                     );
                 }
             }
-            1 => match self.nas_server == cfg::name() {
-                true => {
+            1 => match self.nas().await {
+                Some((nas_server, _, nas_infos)) if nas_server == cfg::name() => {
                     output = format!("{:<12} {:<7} {:10}", "Name", "Onboard", "NAS State");
-                    for nas_info in &self.nas_infos {
+                    for nas_info in &nas_infos {
                         output += &format!(
                             "\n{:<12} {:<7} {:10?}",
                             nas_info.name,
@@ -104,8 +121,11 @@ impl PluginUnit {
                         );
                     }
                 }
-                false => {
-                    output = format!("Nas State: {:?}", self.nas_state);
+                Some((_, nas_state, _)) => {
+                    output = format!("Nas State: {nas_state:?}");
+                }
+                None => {
+                    output = "Nas State: n/a".to_string();
                 }
             },
             2 => {
@@ -126,7 +146,7 @@ impl PluginUnit {
                     };
 
                     let name_width: usize = city.name.chars().map(|c| c.width().unwrap_or(0)).sum();
-                    let name_space = " ".repeat(12 - name_width);
+                    let name_space = " ".repeat(12_usize.saturating_sub(name_width));
 
                     output += &format!(
                         "\n{}{name_space} {update:<11} {temperature:7} {weather:20}",
@@ -153,7 +173,7 @@ impl PluginUnit {
 
                 for city in &self.cities {
                     let name_width: usize = city.name.chars().map(|c| c.width().unwrap_or(0)).sum();
-                    let name_space = " ".repeat(12 - name_width);
+                    let name_space = " ".repeat(12_usize.saturating_sub(name_width));
 
                     output.push_str(&format!("\n{}{name_space} ", city.name));
                     if let Some(weather) = &city.weather {
@@ -179,7 +199,10 @@ impl PluginUnit {
                                 "{weather_emoji} {precipitation_probability_max:4} {temperature:6} "
                             ));
                             output.push_str(&weather);
-                            output.push_str(" ".repeat(13 - weather.len() * 2 / 3).as_str());
+                            output.push_str(
+                                " ".repeat(13_usize.saturating_sub(weather.len() * 2 / 3))
+                                    .as_str(),
+                            );
                         }
                     }
                 }
@@ -192,7 +215,7 @@ impl PluginUnit {
 
                 for (idx, task) in self.tasks.iter().enumerate() {
                     let name_width: usize = task.name.chars().map(|c| c.width().unwrap_or(0)).sum();
-                    let name_space = " ".repeat(12 - name_width);
+                    let name_space = " ".repeat(12_usize.saturating_sub(name_width));
                     let done_str = if task.done { "✓" } else { "✗" };
                     let dued_str = if task.dued { "✓" } else { "✗" };
 
@@ -211,163 +234,14 @@ impl PluginUnit {
         panel::output_update_gui_simple(MODULE, &self.msg_tx, &self.gui_panel, output).await;
     }
 
-    async fn handle_cmd_devices(&mut self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(3) {
-            let ts = utils::time::ts();
-            match action.as_str() {
-                ACTION_ONBOARD => {
-                    if let (Some(name), Some(onboard)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        let onboard = onboard == "1";
-                        if let Some(device) =
-                            self.devices.iter_mut().find(|device| device.name == *name)
-                        {
-                            device.ts = ts;
-                            device.onboard = onboard;
-                        } else {
-                            let device_add = DevInfo {
-                                ts,
-                                name: name.to_string(),
-                                onboard,
-                                version: None,
-                                tailscale_ip: None,
-                                temperature: None,
-                                app_uptime: None,
-                            };
-                            self.devices.push(device_add.clone());
-                        }
-                    }
-                }
-                ACTION_VERSION => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(version)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        if let Some(device) =
-                            self.devices.iter_mut().find(|device| device.name == *name)
-                        {
-                            device.ts = ts;
-                            device.version = Some(version.to_string());
-                        }
-                    }
-                }
-                ACTION_TAILSCALE_IP => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(tailscale_ip)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        if let Some(device) =
-                            self.devices.iter_mut().find(|device| device.name == *name)
-                        {
-                            device.ts = ts;
-                            device.tailscale_ip = Some(tailscale_ip.to_string());
-                        }
-                    }
-                }
-                ACTION_TEMPERATURE => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(temperature)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        if let Some(device) =
-                            self.devices.iter_mut().find(|device| device.name == *name)
-                        {
-                            device.ts = ts;
-                            device.temperature = Some(temperature.parse::<f32>().unwrap());
-                        }
-                    }
-                }
-                ACTION_APP_UPTIME => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(app_uptime)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        if let Some(device) =
-                            self.devices.iter_mut().find(|device| device.name == *name)
-                        {
-                            device.ts = ts;
-                            device.app_uptime = Some(app_uptime.parse::<u64>().unwrap());
-                        }
-                    }
-                }
-                _ => (),
-            }
+    // what devices and nas changed is only on the first two pages
+    async fn handle_event_device_or_nas(&mut self) {
+        if !self.gui_panel.is_empty() && self.page_idx <= 1 {
             self.panel_output_update().await;
         }
     }
 
-    async fn handle_cmd_nas(&mut self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(3) {
-            let ts = utils::time::ts();
-            match action.as_str() {
-                ACTION_ONBOARD => {
-                    if let (Some(name), Some(onboard)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        let onboard = onboard == "1";
-                        if let Some(nas_info) = self
-                            .nas_infos
-                            .iter_mut()
-                            .find(|nas_info| nas_info.name == *name)
-                        {
-                            nas_info.ts = ts;
-                            nas_info.onboard = onboard;
-                        } else {
-                            let nas_info_add = NasInfo {
-                                ts,
-                                name: name.to_string(),
-                                onboard,
-                                nas_state: NasState::Unsync,
-                                tailscale_ip: None,
-                            };
-                            self.nas_infos.push(nas_info_add.clone());
-                        }
-                    }
-                }
-                "nas_server" => {
-                    if let Some(nas_server) = cmd_parts.get(4) {
-                        self.nas_server = nas_server.clone();
-                    }
-                }
-                ACTION_NAS_STATE => {
-                    // server
-                    if let (Some(name), Some(nas_state)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        let nas_state = match nas_state.as_str() {
-                            "Unsync" => NasState::Unsync,
-                            "Syncing" => NasState::Syncing,
-                            "Synced" => NasState::Synced,
-                            "Err" => NasState::Err,
-                            _ => todo!(),
-                        };
-
-                        if let Some(nas_info) = self
-                            .nas_infos
-                            .iter_mut()
-                            .find(|nas_info| nas_info.name == *name)
-                        {
-                            nas_info.ts = ts;
-                            nas_info.nas_state = nas_state;
-                        }
-                    }
-                    // client
-                    else if let Some(nas_state) = cmd_parts.get(4) {
-                        match nas_state.as_str() {
-                            "Unsync" => self.nas_state = NasState::Unsync,
-                            "Syncing" => self.nas_state = NasState::Syncing,
-                            "Synced" => self.nas_state = NasState::Synced,
-                            "Err" => self.nas_state = NasState::Err,
-                            _ => todo!(),
-                        }
-                    } else {
-                        self.warn(
-                            MODULE,
-                            format!("[{MODULE}] Missing {ACTION_NAS_STATE} or name/{ACTION_NAS_STATE} for cmd `{cmd_parts:?}`."),
-                        )
-                        .await;
-                    }
-                }
-                _ => {
-                    self.warn(
-                        MODULE,
-                        format!("[{MODULE}] Unknown action ({action}) for cmd `{cmd_parts:?}`."),
-                    )
-                    .await
-                }
-            }
-            self.panel_output_update().await;
-        }
-    }
-
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(
             MODULE,
             format!(
@@ -376,7 +250,7 @@ impl PluginUnit {
             ),
         )
         .await;
-        for device in &self.devices {
+        for device in &self.devices().await {
             self.info(
                 MODULE,
                 format!(
@@ -395,7 +269,8 @@ impl PluginUnit {
             format!("{:<12} {:<7} {:10}", "Name", "Onboard", "NAS State"),
         )
         .await;
-        for nas_info in &self.nas_infos {
+        let nas_infos = self.nas().await.map(|(_, _, nas_infos)| nas_infos);
+        for nas_info in nas_infos.iter().flatten() {
             self.info(
                 MODULE,
                 format!(
@@ -407,200 +282,50 @@ impl PluginUnit {
             )
             .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_weather(&mut self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(3) {
-            match action.as_str() {
-                ACTION_ADD => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(latitude), Some(longitude)) =
-                        (cmd_parts.get(4), cmd_parts.get(5), cmd_parts.get(6))
-                    {
-                        if !self.cities.iter().any(|city| city.name == *name) {
-                            self.cities.push(City {
-                                name: name.to_string(),
-                                latitude: latitude.parse::<f32>().unwrap(),
-                                longitude: longitude.parse::<f32>().unwrap(),
-                                weather: None,
-                            });
-                        }
-                    }
-                }
-                "update_item" => {
-                    if let Some(class) = cmd_parts.get(4) {
-                        match class.as_str() {
-                            "summary" => {
-                                #[allow(clippy::collapsible_if)]
-                                if let (
-                                    Some(name),
-                                    Some(time),
-                                    Some(temperature),
-                                    Some(weathercode),
-                                ) = (
-                                    cmd_parts.get(5),
-                                    cmd_parts.get(6),
-                                    cmd_parts.get(7),
-                                    cmd_parts.get(8),
-                                ) {
-                                    if let Some(city) =
-                                        self.cities.iter_mut().find(|city| city.name == *name)
-                                    {
-                                        let time = time.to_string();
-                                        let temperature = temperature.parse::<f32>().unwrap();
-                                        let weathercode = weathercode.parse::<u8>().unwrap();
-
-                                        if let Some(weather) = city.weather.as_mut() {
-                                            weather.time = time;
-                                            weather.temperature = temperature;
-                                            weather.weathercode = weathercode;
-                                        } else {
-                                            city.weather = Some(Weather {
-                                                time,
-                                                temperature,
-                                                weathercode,
-                                                daily: vec![],
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                            "daily" => {
-                                #[allow(clippy::collapsible_if)]
-                                if let (
-                                    Some(name),
-                                    Some(idx),
-                                    Some(time),
-                                    Some(temperature_2m_max),
-                                    Some(temperature_2m_min),
-                                    Some(precipitation_probability_max),
-                                    Some(weather_code),
-                                ) = (
-                                    cmd_parts.get(5),
-                                    cmd_parts.get(6),
-                                    cmd_parts.get(7),
-                                    cmd_parts.get(8),
-                                    cmd_parts.get(9),
-                                    cmd_parts.get(10),
-                                    cmd_parts.get(11),
-                                ) {
-                                    if let Some(city) =
-                                        self.cities.iter_mut().find(|city| city.name == *name)
-                                    {
-                                        let idx = idx.parse::<usize>().unwrap();
-                                        let daily = WeatherDaily {
-                                            time: time.to_string(),
-                                            temperature_2m_max: temperature_2m_max
-                                                .parse::<f32>()
-                                                .unwrap(),
-                                            temperature_2m_min: temperature_2m_min
-                                                .parse::<f32>()
-                                                .unwrap(),
-                                            precipitation_probability_max:
-                                                precipitation_probability_max.parse::<u8>().unwrap(),
-                                            weather_code: weather_code.parse::<u8>().unwrap(),
-                                        };
-
-                                        if let Some(weather) = city.weather.as_mut() {
-                                            if weather.daily.len() <= idx {
-                                                weather.daily.resize_with(idx + 1, || {
-                                                    WeatherDaily {
-                                                        time: String::new(),
-                                                        temperature_2m_max: 0.0,
-                                                        temperature_2m_min: 0.0,
-                                                        precipitation_probability_max: 0,
-                                                        weather_code: 0,
-                                                    }
-                                                });
-                                            }
-
-                                            weather.daily[idx] = daily;
-                                        }
-                                    }
-                                }
-                            }
-                            _ => (),
-                        }
-                    }
-                }
-                _ => {
-                    self.warn(
-                        MODULE,
-                        format!("[{MODULE}] Unknown action ({action}) for cmd `{cmd_parts:?}`.",),
-                    )
-                    .await;
-                }
-            }
+    async fn handle_event_weather_updated(&mut self, updated: &City) {
+        if let Some(city) = self
+            .cities
+            .iter_mut()
+            .find(|city| city.name == updated.name)
+        {
+            city.weather = updated.weather.clone();
+        } else {
+            self.cities.push(updated.clone());
         }
     }
 
-    async fn handle_cmd_todos(&mut self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(3) {
-            #[allow(clippy::single_match)]
-            match action.as_str() {
-                "update" => {
-                    if let (
-                        Some(id),
-                        Some(parent),
-                        Some(name),
-                        Some(time),
-                        Some(done),
-                        Some(dued),
-                        Some(reminder),
-                        Some(reminded),
-                    ) = (
-                        cmd_parts.get(4),
-                        cmd_parts.get(5),
-                        cmd_parts.get(6),
-                        cmd_parts.get(7),
-                        cmd_parts.get(8),
-                        cmd_parts.get(9),
-                        cmd_parts.get(10),
-                        cmd_parts.get(11),
-                    ) {
-                        let id = id.parse::<uuid::Uuid>().unwrap();
-                        let parent = parent.parse::<uuid::Uuid>().unwrap();
-                        let time = time.parse::<u64>().unwrap();
-                        let done = done == "true";
-                        let dued = dued == "true";
-                        let reminder = reminder.parse::<u32>().unwrap();
-                        let reminded = reminded == "true";
-
-                        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == id) {
-                            task.name = name.to_string();
-                            task.time = time;
-                            task.done = done;
-                            task.dued = dued;
-                            task.reminder = reminder;
-                            task.parent = parent;
-                            task.reminded = reminded;
-                        } else {
-                            self.tasks.push(Task {
-                                id,
-                                name: name.to_string(),
-                                time,
-                                done,
-                                dued,
-                                reminder,
-                                parent,
-                                reminded,
-                            });
-                        }
-                    } else {
-                        self.warn(
-                            MODULE,
-                            format!("[{MODULE}] Missing parameters for cmd `{cmd_parts:?}`.",),
-                        )
-                        .await;
-                    }
+    async fn handle_event_todo_updated(&mut self, updated: &Task) {
+        if let Some(task) = self.tasks.iter_mut().find(|task| task.id == updated.id) {
+            *task = updated.clone();
+        } else {
+            self.tasks.push(updated.clone());
+        }
 
-                    self.tasks.sort_by_key(|e| e.time);
+        self.tasks.sort_by_key(|e| e.time);
 
-                    self.panel_output_update().await;
-                }
-                _ => (),
-            }
+        self.panel_output_update().await;
+    }
+
+    async fn handle_cmd_gui(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        self.gui_panel = cmd.get_str(0, "gui_panel")?.to_string();
+
+        Ok(())
+    }
+
+    async fn handle_cmd_arrow(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        match cmd.get_str(0, "left_right")? {
+            "right" => self.page_idx = (self.page_idx + 1) % PAGES,
+            "left" => self.page_idx = (self.page_idx + PAGES - 1) % PAGES,
+            _ => (),
         }
+
+        self.panel_output_update().await;
+
+        Ok(())
     }
 }
 
@@ -614,49 +339,37 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
-    async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_GUI => {
-                        if let Some(gui_panel) = cmd_parts.get(3) {
-                            self.gui_panel = gui_panel.to_string();
-                        }
-                    }
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_DEVICES => self.handle_cmd_devices(&cmd_parts).await,
-                    "nas" => self.handle_cmd_nas(&cmd_parts).await,
-                    ACTION_ARROW => {
-                        if let Some(left_right) = cmd_parts.get(3) {
-                            match left_right.as_str() {
-                                "right" => self.page_idx = (self.page_idx + 1) % PAGES,
-                                "left" => self.page_idx = (self.page_idx + PAGES - 1) % PAGES,
-                                _ => (),
-                            }
-                        }
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
 
-                        self.panel_output_update().await;
-                    }
-                    "weather" => self.handle_cmd_weather(&cmd_parts).await,
-                    "todos" => self.handle_cmd_todos(&cmd_parts).await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+    fn subscriptions(&self) -> &[&str] {
+        &[
+            "device.*",
+            "nas.*",
+            EVENT_WEATHER_UPDATED,
+            EVENT_TODO_UPDATED,
+        ]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::WeatherUpdated { city } => self.handle_event_weather_updated(city).await,
+            Event::TodoUpdated { task } => self.handle_event_todo_updated(task).await,
+            _ => self.handle_event_device_or_nas().await,
+        }
+    }
+
+    async fn handle_cmd(&mut self, msg: &Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_GUI => self.handle_cmd_gui(cmd).await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_ARROW => self.handle_cmd_arrow(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
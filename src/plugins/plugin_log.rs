@@ -1,28 +1,237 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
+use log::{Level, LevelFilter};
 use tokio::sync::mpsc::Sender;
 
-use crate::messages::{ACTION_ARROW, ACTION_GUI, ACTION_LOG, Cmd, Data, Msg};
-use crate::plugins::plugins_main;
-use crate::utils;
+use crate::cfg::{self, Cfg};
+use crate::messages::{
+    ACTION_ARROW, ACTION_FILE, ACTION_GUI, ACTION_JSON, ACTION_LEVEL, ACTION_LIST, ACTION_LOG,
+    ACTION_TAIL, ActionSpec, Command, CommandError, Data, EVENT_PLUGIN_DISABLED,
+    EVENT_PLUGIN_ENABLED, Event, Msg, Reply,
+};
+use crate::plugins::plugins_main::{self, Plugin};
+use crate::utils::{self, log_file::LogFile};
 
 const MODULE: &str = "log";
+const ALL_MODULES: &str = "*";
+const OFF: &str = "off";
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_LOG,
+        "<level> <msg>",
+        "Print a log line, sent by the router",
+    ),
+    ActionSpec::new(
+        ACTION_GUI,
+        "<gui_panel>",
+        "Print to a panel instead of stdout",
+    ),
+    ActionSpec::new(ACTION_ARROW, "<arrow>", "Arrow key from the panel, ignored"),
+    ActionSpec::new(
+        ACTION_LEVEL,
+        "<module> <level>",
+        "Show a module up to error/warn/info/debug/trace/off, * for all",
+    ),
+    ActionSpec::new(
+        ACTION_TAIL,
+        "<n> [module]",
+        "Print the last n lines of the log file",
+    ),
+    ActionSpec::new(
+        ACTION_FILE,
+        "<path>",
+        "Write the log file to path, off to stop",
+    ),
+    ActionSpec::new(
+        ACTION_JSON,
+        "<path>",
+        "Also write JSON lines to path, off to stop",
+    ),
+];
 
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
     msg_tx: Sender<Msg>,
+    // asked for by `gui`, printed to while panels runs
     gui_panel: String,
+    panels: bool,
+    // per sending module, `default_level` for the rest
+    levels: HashMap<String, LevelFilter>,
+    default_level: LevelFilter,
+    // log.level of the cfg, to tell whether a reload changed it
+    cfg_level: LevelFilter,
+    log_file: Option<LogFile>,
+    // JSON lines for log shipping, off unless asked for
+    json_file: Option<LogFile>,
 }
 
 impl PluginUnit {
     pub async fn new(msg_tx: Sender<Msg>) -> Self {
         utils::msg::log_new(&msg_tx, MODULE).await;
 
+        let cfg_level = cfg::log_level().parse().unwrap_or(LevelFilter::Info);
+
         Self {
             name: MODULE.to_owned(),
             msg_tx,
             gui_panel: String::new(),
+            panels: false,
+            levels: HashMap::new(),
+            default_level: cfg_level,
+            cfg_level,
+            log_file: utils::log_file::default_path().map(|path| LogFile::new(&path)),
+            json_file: None,
+        }
+    }
+
+    fn enabled(&self, module: &str, level: Level) -> bool {
+        level <= *self.levels.get(module).unwrap_or(&self.default_level)
+    }
+
+    // stdout, or the panel in gui mode
+    async fn output(&self, line: String) {
+        if self.gui_panel.is_empty() || !self.panels {
+            println!("{line}");
+        } else {
+            self.command(
+                MODULE,
+                Command::new("panels", "output_push")
+                    .arg(&self.gui_panel)
+                    .arg(line),
+            )
+            .await;
+        }
+    }
+
+    async fn handle_cmd_log(&mut self, msg: &Msg, cmd: &Command) -> Result<(), CommandError> {
+        let level = cmd.get::<Level>(0, "level")?;
+        let text = cmd.get_str(1, "msg")?;
+
+        if !self.enabled(&msg.module, level) {
+            return Ok(());
+        }
+
+        for sink in [&mut self.log_file, &mut self.json_file] {
+            #[allow(clippy::collapsible_if)]
+            if let Some(log_file) = sink {
+                if let Err(e) = log_file.write(msg.ts, level, &msg.module, text) {
+                    let path = log_file.path().display().to_string();
+                    *sink = None;
+                    utils::msg::log_warn(
+                        &self.msg_tx,
+                        MODULE,
+                        format!("[{MODULE}] Stop writing to `{path}`. Err: {e}"),
+                    )
+                    .await;
+                }
+            }
+        }
+
+        self.output(format!("{} [{level}] {text}", utils::time::ts_str(msg.ts)))
+            .await;
+
+        Ok(())
+    }
+
+    // the panel is kept while panels is not running, e.g. not yet or headless,
+    // see `handle_event`
+    async fn handle_cmd_gui(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        self.gui_panel = cmd.get_str(0, "gui_panel")?.to_string();
+
+        let plugins =
+            utils::msg::query(&self.msg_tx, MODULE, Command::new("plugins", ACTION_LIST)).await;
+        self.panels = matches!(plugins, Ok(Reply::Plugins(names)) if names.iter().any(|name| name == "panels"));
+        if !self.panels {
+            self.info(
+                MODULE,
+                format!(
+                    "[{MODULE}] `{}` waits for panels, printing to stdout meanwhile",
+                    self.gui_panel
+                ),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_level(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let module = cmd.get_str(0, "module")?;
+        let level = cmd.get::<LevelFilter>(1, "level")?;
+
+        if module == ALL_MODULES {
+            self.levels.clear();
+            self.default_level = level;
+        } else {
+            self.levels.insert(module.to_string(), level);
+        }
+        self.info(MODULE, format!("[{MODULE}] Level of {module}: {level}"))
+            .await;
+
+        Ok(())
+    }
+
+    async fn handle_cmd_tail(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let n = cmd.get::<usize>(0, "n")?;
+        let module = cmd.args.get(1).map(|module| module.as_str());
+
+        let Some(log_file) = &self.log_file else {
+            self.warn(MODULE, format!("[{MODULE}] No log file to tail."))
+                .await;
+            return Ok(());
+        };
+
+        match log_file.tail(n, module) {
+            Ok(lines) => {
+                for line in lines {
+                    self.output(line).await;
+                }
+            }
+            Err(e) => {
+                let path = log_file.path().display().to_string();
+                self.warn(
+                    MODULE,
+                    format!("[{MODULE}] Failed to read `{path}`. Err: {e}"),
+                )
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_file(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let path = cmd.get_str(0, "path")?;
+
+        if path == OFF {
+            self.log_file = None;
+            self.info(MODULE, format!("[{MODULE}] Log file off")).await;
+        } else {
+            self.log_file = Some(LogFile::new(path));
+            self.info(MODULE, format!("[{MODULE}] Log file: {path}"))
+                .await;
         }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_json(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let path = cmd.get_str(0, "path")?;
+
+        if path == OFF {
+            self.json_file = None;
+            self.info(MODULE, format!("[{MODULE}] JSON log file off"))
+                .await;
+        } else {
+            self.json_file = Some(LogFile::json(path, &cfg::name()));
+            self.info(MODULE, format!("[{MODULE}] JSON log file: {path}"))
+                .await;
+        }
+
+        Ok(())
     }
 }
 
@@ -36,61 +245,50 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn subscriptions(&self) -> &[&str] {
+        &[EVENT_PLUGIN_ENABLED, EVENT_PLUGIN_DISABLED]
+    }
+
+    // print to the panel of `gui` while panels runs
+    async fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::PluginEnabled { name } if name == "panels" => self.panels = true,
+            Event::PluginDisabled { name } if name == "panels" => self.panels = false,
+            _ => (),
+        }
+    }
+
+    async fn reconfigure(&mut self, cfg: &Cfg) {
+        let level = cfg.log.level.parse().unwrap_or(LevelFilter::Info);
+        if level != self.cfg_level {
+            self.cfg_level = level;
+            self.default_level = level;
+            self.info(
+                MODULE,
+                format!("[{MODULE}] Level of {ALL_MODULES}: {level}"),
+            )
+            .await;
+        }
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_LOG => {
-                        let ts = msg.ts;
-                        if let (Some(level), Some(msg)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-                            if self.gui_panel.is_empty() {
-                                println!("{} [{level}] {msg}", utils::time::ts_str(ts));
-                            } else {
-                                let msg = Msg {
-                                    ts: utils::time::ts(),
-                                    module: MODULE.to_string(),
-                                    data: Data::Cmd(Cmd {
-                                        cmd: format!(
-                                            "p panels output_push {} '{} [{level}] {msg}'",
-                                            self.gui_panel,
-                                            utils::time::ts_str(ts)
-                                        ),
-                                    }),
-                                };
-                                let _ = self.msg_tx.send(msg).await;
-                            }
-                        } else {
-                            self.warn(
-                                MODULE,
-                                format!("[{MODULE}] Missing level/msg for cmd `{}`.", cmd.cmd),
-                            )
-                            .await;
-                        }
-                    }
-                    ACTION_GUI => {
-                        if let Some(gui_panel) = cmd_parts.get(3) {
-                            self.gui_panel = gui_panel.to_string();
-                        }
-                    }
-                    ACTION_ARROW => (),
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_LOG => self.handle_cmd_log(msg, cmd).await,
+                ACTION_GUI => self.handle_cmd_gui(cmd).await,
+                ACTION_ARROW => Ok(()),
+                ACTION_LEVEL => self.handle_cmd_level(cmd).await,
+                ACTION_TAIL => self.handle_cmd_tail(cmd).await,
+                ACTION_FILE => self.handle_cmd_file(cmd).await,
+                ACTION_JSON => self.handle_cmd_json(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
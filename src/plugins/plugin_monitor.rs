@@ -1,28 +1,26 @@
 use std::path::Path;
-use std::thread;
 use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
-use base64::{Engine as _, engine::general_purpose};
 use log::Level::Info;
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{Event, EventKind, RecursiveMode};
 use tokio::{
-    sync::{
-        Mutex, broadcast,
-        mpsc::{self, Sender},
-        oneshot,
-    },
-    task,
+    sync::{Mutex, broadcast, mpsc::Sender},
     time::{Duration, sleep},
 };
 
-use crate::consts::NAS_FOLDER;
-use crate::messages::{ACTION_FILE_MODIFY, ACTION_FILE_REMOVE, ACTION_INIT, Cmd, Data, Log, Msg};
+use crate::cfg;
+use crate::messages::{ACTION_INIT, ActionSpec, CommandError, Data, Event as MsgEvent, Log, Msg};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils;
 
 const MODULE: &str = "monitor";
-const DEBOUNCE_DELAY: u64 = 10; // seconds
+
+pub const ACTIONS: &[ActionSpec] = &[ActionSpec::new(
+    ACTION_INIT,
+    "",
+    "Watch the nas folder for changes",
+)];
 
 type DebounceMap = Arc<Mutex<HashMap<(String, EventKind), tokio::task::JoinHandle<()>>>>;
 
@@ -46,78 +44,60 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_init(&mut self, mut shutdown_rx: broadcast::Receiver<()>) {
+    async fn handle_cmd_init(&mut self, shutdown_rx: broadcast::Receiver<()>) {
         if self.inited {
             return;
         }
         self.inited = true;
 
+        let path_to_watch = cfg::nas_dir();
+        let mut rx = match utils::watch::watch(
+            Path::new(&path_to_watch),
+            RecursiveMode::Recursive,
+            shutdown_rx,
+        ) {
+            Ok(rx) => rx,
+            Err(e) => {
+                self.warn(
+                    MODULE,
+                    format!("[{MODULE}] Failed to watch `{path_to_watch}`. Err: {e}"),
+                )
+                .await;
+                return;
+            }
+        };
+
         let msg_tx_clone = self.msg_tx.clone();
         tokio::spawn(async move {
             let debounce_map: DebounceMap = Arc::new(Mutex::new(HashMap::new()));
-            let path_to_watch = Path::new(NAS_FOLDER);
-            let (tx, mut rx) = mpsc::channel(1024);
-
-            // 用 oneshot 通知 blocking thread 結束
-            let (shutdown_blocking_tx, mut shutdown_blocking_rx) = oneshot::channel::<()>();
-
-            let _watcher_handle = task::spawn_blocking(move || {
-                let mut watcher = RecommendedWatcher::new(
-                    move |res| {
-                        if let Ok(event) = res {
-                            let _ = tx.blocking_send(event);
-                        }
-                    },
-                    Config::default(),
-                )
-                .expect("Watcher 初始化失敗");
 
-                watcher
-                    .watch(Path::new(path_to_watch), RecursiveMode::Recursive)
-                    .expect("無法監聽目錄");
+            // ends with the watcher, on shutdown
+            while let Some(event) = rx.recv().await {
+                // read each time, it may be reloaded
+                let debounce_delay = cfg::get().monitor.debounce_delay;
+                for path in &event.paths {
+                    let path_str = path.display().to_string();
+                    let debounce_map = debounce_map.clone();
 
-                // blocking thread 等待 shutdown signal，或每秒檢查一次
-                loop {
-                    if shutdown_blocking_rx.try_recv().is_ok() {
-                        break;
-                    }
-                    thread::sleep(Duration::from_secs(1));
-                }
-            });
-
-            loop {
-                tokio::select! {
-                    Some(event) = rx.recv() => {
-                        for path in &event.paths {
-                            let path_str = path.display().to_string();
-                            let debounce_map = debounce_map.clone();
-
-                            let key = (path_str.clone(), event.kind);
-
-                            // cancel the previous task if it exists
-                            let mut map = debounce_map.lock().await;
-                            if let Some(handle) = map.remove(&key) {
-                                handle.abort(); // Abort the previous task
-                            }
-
-                            let event_clone = event.clone();
-                            let msg_tx_clone_clone = msg_tx_clone.clone();
-
-                            // spawn a new task with a debounce delay
-                            let handle = tokio::spawn(async move {
-                                sleep(Duration::from_secs(DEBOUNCE_DELAY)).await;
-                                handle_event(event_clone, &msg_tx_clone_clone).await;
-                            });
-
-                            // store the new task handle in the map
-                            map.insert(key, handle);
-                        }
-                    }
+                    let key = (path_str.clone(), event.kind);
 
-                    _ = shutdown_rx.recv() => {
-                        let _ = shutdown_blocking_tx.send(());
-                        break;
+                    // cancel the previous task if it exists
+                    let mut map = debounce_map.lock().await;
+                    if let Some(handle) = map.remove(&key) {
+                        handle.abort(); // Abort the previous task
                     }
+
+                    let event_clone = event.clone();
+                    let msg_tx_clone_clone = msg_tx_clone.clone();
+
+                    // spawn a new task with a debounce delay
+                    let handle = tokio::spawn(async move {
+                        sleep(Duration::from_secs(debounce_delay)).await;
+                        handle_event(event_clone, &msg_tx_clone_clone).await;
+                    });
+
+                    // store the new task handle in the map
+                    map.insert(key, handle);
                 }
             }
         });
@@ -136,39 +116,33 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => {
-                        let shutdown_rx = self.shutdown_tx.subscribe();
-                        self.handle_cmd_init(shutdown_rx).await;
-                    }
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => {
+                    let shutdown_rx = self.shutdown_tx.subscribe();
+                    self.handle_cmd_init(shutdown_rx).await;
+                    Ok(())
                 }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
 }
 
 fn monitor_get_file(file_path: &str) -> String {
-    let keyword = format!("{NAS_FOLDER}/");
+    let keyword = format!("{}/", cfg::nas_dir());
     if let Some(pos) = file_path.find(&keyword) {
         let result = &file_path[pos..];
         return result.to_owned();
@@ -194,29 +168,7 @@ async fn handle_event(event: Event, msg_tx: &Sender<Msg>) {
                 };
                 let _ = msg_tx.send(msg).await;
 
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Cmd(Cmd {
-                        cmd: format!(
-                            "p nas {ACTION_FILE_MODIFY} {}",
-                            general_purpose::STANDARD.encode(&filename)
-                        ),
-                    }),
-                };
-                let _ = msg_tx.send(msg).await;
-
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Cmd(Cmd {
-                        cmd: format!(
-                            "p runtipi {ACTION_FILE_MODIFY} {}",
-                            general_purpose::STANDARD.encode(&filename)
-                        ),
-                    }),
-                };
-                let _ = msg_tx.send(msg).await;
+                utils::msg::emit(msg_tx, MODULE, MsgEvent::FileModified { filename }).await;
             }
         }
         notify::event::EventKind::Remove(_) => {
@@ -233,17 +185,7 @@ async fn handle_event(event: Event, msg_tx: &Sender<Msg>) {
                 };
                 let _ = msg_tx.send(msg).await;
 
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Cmd(Cmd {
-                        cmd: format!(
-                            "p nas {ACTION_FILE_REMOVE} {}",
-                            general_purpose::STANDARD.encode(filename)
-                        ),
-                    }),
-                };
-                let _ = msg_tx.send(msg).await;
+                utils::msg::emit(msg_tx, MODULE, MsgEvent::FileRemoved { filename }).await;
             }
         }
         notify::event::EventKind::Access(_) => (),
@@ -1,22 +1,43 @@
 use async_trait::async_trait;
 use log::Level::{Info, Warn};
-use rumqttc::{AsyncClient, Event, Incoming, LastWill, MqttOptions, Publish, QoS};
-use tokio::sync::broadcast;
+use rumqttc::{AsyncClient, Event, Incoming, LastWill, MqttOptions, Outgoing, Publish, QoS};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
 
-use crate::cfg;
+use crate::cfg::{self, Cfg, MqttCfg};
 use crate::messages::{
-    ACTION_APP_UPTIME, ACTION_ARROW, ACTION_CLI, ACTION_GUI, ACTION_INIT, ACTION_ONBOARD,
-    ACTION_PUBLISH, ACTION_SHOW, ACTION_TAILSCALE_IP, ACTION_TEMPERATURE, ACTION_VERSION, Cmd,
-    Data, Msg,
+    self, ACTION_APP_UPTIME, ACTION_ARROW, ACTION_CLI, ACTION_GUI, ACTION_INIT, ACTION_ONBOARD,
+    ACTION_PUBLISH, ACTION_SHOW, ACTION_TAILSCALE_IP, ACTION_TEMPERATURE, ACTION_VERSION,
+    ActionSpec, Command, CommandError, Data, Msg,
 };
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{self, mode::Mode, panel};
 
 const MODULE: &str = "mqtt";
-const BROKER: &str = "broker.emqx.io";
 const MQTT_KEEP_ALIVE: u64 = 300;
 const RESTART_DELAY: u64 = 60;
+const FLUSH_TIMEOUT: u64 = 3;
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_INIT,
+        "<mode> [gui_panel]",
+        "Connect to the broker, mode: gui <gui_panel> or cli",
+    ),
+    ActionSpec::new(
+        "restart",
+        "",
+        "Connect to the broker again, in the mode of init",
+    ),
+    ActionSpec::new(ACTION_SHOW, "", "Show the mqtt state"),
+    ActionSpec::new(
+        ACTION_PUBLISH,
+        "<retain> <key> <payload>",
+        "Publish tln/<name>/<key>",
+    ),
+    ActionSpec::new(ACTION_ARROW, "<arrow>", "Arrow key from the panel, ignored"),
+];
 
 #[derive(Debug)]
 pub struct PluginUnit {
@@ -27,6 +48,11 @@ pub struct PluginUnit {
     started: bool,
     gui_panel: String,
     client: Option<AsyncClient>,
+    task: Option<JoinHandle<()>>,
+    // disconnects the receive task without a restart
+    stop_tx: Option<oneshot::Sender<()>>,
+    // the name and broker of the connection, reconnect when the cfg changes them
+    connected: Option<(String, MqttCfg)>,
 }
 
 impl PluginUnit {
@@ -41,6 +67,9 @@ impl PluginUnit {
             started: false,
             gui_panel: String::new(),
             client: None,
+            task: None,
+            stop_tx: None,
+            connected: None,
         }
     }
 
@@ -55,7 +84,10 @@ impl PluginUnit {
             format!("[{MODULE}] 1/5: Initialization"),
         )
         .await;
-        let mut mqttoptions = MqttOptions::new(cfg::name(), BROKER, 1883);
+        let mqtt = cfg::get().mqtt;
+        self.connected = Some((cfg::name(), mqtt.clone()));
+        let broker = mqtt.broker.clone();
+        let mut mqttoptions = MqttOptions::new(cfg::name(), mqtt.broker, mqtt.port);
         let will = LastWill::new(
             format!("tln/{}/onboard", cfg::name()),
             "0",
@@ -88,10 +120,13 @@ impl PluginUnit {
             format!("[{MODULE}] 3/5: Subscribe"),
         )
         .await;
-        client
-            .subscribe("tln/#", QoS::AtMostOnce)
-            .await
-            .expect("Failed to subscribe");
+        if let Err(e) = client.subscribe("tln/#", QoS::AtMostOnce).await {
+            self.warn(
+                MODULE,
+                format!("[{MODULE}] Failed to subscribe. Err: {e:?}"),
+            )
+            .await;
+        }
 
         // 4. Publish
         panel::output_push(
@@ -103,7 +138,7 @@ impl PluginUnit {
             format!("[{MODULE}] 4/5: Publish"),
         )
         .await;
-        client
+        if let Err(e) = client
             .publish(
                 format!("tln/{}/onboard", cfg::name()),
                 QoS::AtLeastOnce,
@@ -111,14 +146,21 @@ impl PluginUnit {
                 "1",
             )
             .await
-            .expect("Failed to publish");
+        {
+            self.warn(
+                MODULE,
+                format!("[{MODULE}] Failed to publish onboard. Err: {e:?}"),
+            )
+            .await;
+        }
 
         // 5. Receive
         let msg_tx_clone = self.msg_tx.clone();
         let gui_panel_clone = self.gui_panel.clone();
         let mode_clone = self.mode.clone();
         let client_clone = client.clone();
-        tokio::spawn(async move {
+        let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
             panel::output_push(
                 MODULE,
                 &msg_tx_clone,
@@ -133,7 +175,7 @@ impl PluginUnit {
             loop {
                 tokio::select! {
                     event = connection.poll() => {
-                        if process_event(&msg_tx_clone, &mode_clone, &gui_panel_clone, event).await {
+                        if process_event(&msg_tx_clone, &mode_clone, &gui_panel_clone, &broker, event).await {
                             break;
                         }
                     }
@@ -141,6 +183,11 @@ impl PluginUnit {
                         shoutdown_flag = true;
                         break;
                     }
+                    // also when a newer connection replaced this one
+                    _ = &mut stop_rx => {
+                        shoutdown_flag = true;
+                        break;
+                    }
                 }
             }
 
@@ -153,108 +200,114 @@ impl PluginUnit {
                 format!("[{MODULE}] Disconnect"),
             )
             .await;
-            client_clone
-                .disconnect()
-                .await
-                .expect("Failed to disconnect");
-
-            if !shoutdown_flag {
-                // restart in RESTART_DELAY seconds
-                tokio::time::sleep(tokio::time::Duration::from_secs(RESTART_DELAY)).await;
-
-                let action = match mode_clone {
-                    Mode::ModeCli => "cli".to_string(),
-                    Mode::ModeGui => format!("gui {}", gui_panel_clone),
-                };
-
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Cmd(Cmd {
-                        cmd: format!("p mqtt restart {action}"),
-                    }),
-                };
-                let _ = msg_tx_clone.send(msg).await;
+            if let Err(e) = client_clone.disconnect().await {
+                panel::output_push(
+                    MODULE,
+                    &msg_tx_clone,
+                    &mode_clone,
+                    &gui_panel_clone,
+                    Warn,
+                    format!("[{MODULE}] Failed to disconnect. Err: {e:?}"),
+                )
+                .await;
+            }
+
+            if shoutdown_flag {
+                // keep polling so the queued offboard publish and the disconnect reach the broker
+                let _ =
+                    tokio::time::timeout(tokio::time::Duration::from_secs(FLUSH_TIMEOUT), async {
+                        loop {
+                            match connection.poll().await {
+                                Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+                                Ok(_) => (),
+                            }
+                        }
+                    })
+                    .await;
+            } else {
+                // restart in RESTART_DELAY seconds, unless replaced or shut down meanwhile
+                tokio::select! {
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(RESTART_DELAY)) => (),
+                    _ = shutdown_rx.recv() => return,
+                    _ = &mut stop_rx => return,
+                }
+
+                utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, "restart")).await;
             }
         });
 
         self.client = Some(client);
+        self.task = Some(task);
+        self.stop_tx = Some(stop_tx);
 
         // 🧪 補充：錯誤處理與重連
         // - 處理連線失敗、broker 掛掉、封包錯誤等情況
         // - 可設定自動重連機制或 exponential backoff
     }
 
-    async fn handle_cmd_init(
-        &mut self,
-        cmd_parts: &[String],
-        cmd: &Cmd,
-        shutdown_rx: broadcast::Receiver<()>,
-    ) {
-        if let Some(mode) = cmd_parts.get(3) {
-            match mode.as_str() {
-                ACTION_GUI => {
-                    // avoid re-entry
-                    if self.started && self.mode == Mode::ModeGui {
-                        self.log(
-                            MODULE,
-                            Warn,
-                            format!("[{MODULE}] Started and GUI mode already. Ignore."),
-                        )
-                        .await;
-                        return;
-                    }
-
-                    if let Some(gui_panel) = cmd_parts.get(4) {
-                        self.started = true;
-                        self.mode = Mode::ModeGui;
-                        self.gui_panel = gui_panel.to_string();
-
-                        self.log(
-                            MODULE,
-                            Info,
-                            format!("[{MODULE}] init gui mode (panel: `{gui_panel}`)"),
-                        )
-                        .await;
-                    }
+    async fn handle_cmd_init(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        match cmd.get_str(0, "mode")? {
+            ACTION_GUI => {
+                // avoid re-entry
+                if self.started && self.mode == Mode::ModeGui {
+                    self.log(
+                        MODULE,
+                        Warn,
+                        format!("[{MODULE}] Started and GUI mode already. Ignore."),
+                    )
+                    .await;
+                    return Ok(());
                 }
-                ACTION_CLI => {
-                    // avoid re-entry
-                    if self.started && self.mode == Mode::ModeCli {
-                        self.log(
-                            MODULE,
-                            Warn,
-                            format!("[{MODULE}] Started and CLI mode already. Ignore."),
-                        )
-                        .await;
-                        return;
-                    }
-                    self.started = true;
-                    self.mode = Mode::ModeCli;
 
-                    self.log(MODULE, Info, format!("[{MODULE}] init cli mode"))
-                        .await;
-                }
-                _ => {
+                let gui_panel = cmd.get_str(1, "gui_panel")?;
+                self.started = true;
+                self.mode = Mode::ModeGui;
+                self.gui_panel = gui_panel.to_string();
+
+                self.log(
+                    MODULE,
+                    Info,
+                    format!("[{MODULE}] init gui mode (panel: `{gui_panel}`)"),
+                )
+                .await;
+            }
+            ACTION_CLI => {
+                // avoid re-entry
+                if self.started && self.mode == Mode::ModeCli {
                     self.log(
                         MODULE,
                         Warn,
-                        format!("[{MODULE}] Unknown mode ({mode}) for cmd `{}`.", cmd.cmd),
+                        format!("[{MODULE}] Started and CLI mode already. Ignore."),
                     )
                     .await;
-                    return;
+                    return Ok(());
                 }
-            }
+                self.started = true;
+                self.mode = Mode::ModeCli;
 
-            self.start_mqtt(shutdown_rx).await;
-        } else {
-            self.log(
-                MODULE,
-                Warn,
-                format!("[{MODULE}] Missing mode for cmd `{}`.", cmd.cmd),
-            )
-            .await;
+                self.log(MODULE, Info, format!("[{MODULE}] init cli mode"))
+                    .await;
+            }
+            mode => {
+                return Err(CommandError::InvalidArg {
+                    name: "mode".to_string(),
+                    value: mode.to_string(),
+                    expected: format!("{ACTION_GUI}/{ACTION_CLI}"),
+                });
+            }
         }
+
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        self.start_mqtt(shutdown_rx).await;
+
+        Ok(())
+    }
+
+    async fn handle_cmd_restart(&mut self) -> Result<(), CommandError> {
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        self.start_mqtt(shutdown_rx).await;
+
+        Ok(())
     }
 
     async fn publish(&mut self, topic: &str, retain: bool, payload: &str) {
@@ -284,18 +337,20 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.log(MODULE, Info, format!("[{MODULE}] show")).await;
+
+        Ok(())
     }
 
-    async fn handle_cmd_publish(&mut self, cmd_parts: &[String]) {
-        if let (Some(retain), Some(key), Some(payload)) =
-            (cmd_parts.get(3), cmd_parts.get(4), cmd_parts.get(5))
-        {
-            let retain = retain == "true";
-            self.publish(&format!("tln/{}/{key}", cfg::name()), retain, payload)
-                .await;
-        }
+    async fn handle_cmd_publish(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let retain = cmd.get_bool(0, "retain")?;
+        let key = cmd.get_str(1, "key")?;
+        let payload = cmd.get_str(2, "payload")?;
+        self.publish(&format!("tln/{}/{key}", cfg::name()), retain, payload)
+            .await;
+
+        Ok(())
     }
 }
 
@@ -309,43 +364,93 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
-    async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => {
-                        let shutdown_rx = self.shutdown_tx.subscribe();
-                        self.handle_cmd_init(&cmd_parts, cmd, shutdown_rx).await;
-                    }
-                    "restart" => {
-                        let shutdown_rx = self.shutdown_tx.subscribe();
-                        self.start_mqtt(shutdown_rx).await;
-                    }
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_PUBLISH => self.handle_cmd_publish(&cmd_parts).await,
-                    ACTION_ARROW => (),
-                    _ => {
-                        self.log(
-                            MODULE,
-                            Warn,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
+    async fn shutdown(&mut self) {
+        // say offboard now instead of leaving it to the last will
+        if let Some(client) = &self.client {
+            let _ = client
+                .publish(
+                    format!("tln/{}/onboard", cfg::name()),
+                    QoS::AtLeastOnce,
+                    true,
+                    "0",
+                )
+                .await;
+        }
+
+        // the receive task disconnects and flushes, wait for it
+        let _ = self.shutdown_tx.send(());
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+
+    async fn reconfigure(&mut self, cfg: &Cfg) {
+        let Some((name, mqtt)) = &self.connected else {
+            return;
+        };
+        if *name == cfg.name && *mqtt == cfg.mqtt {
+            return;
+        }
+
+        self.info(
+            MODULE,
+            format!(
+                "[{MODULE}] Reconnect to {}:{}",
+                cfg.mqtt.broker, cfg.mqtt.port
+            ),
+        )
+        .await;
+        // the old name goes offboard, the new one onboards below
+        if *name != cfg.name {
+            let offboard = match &self.client {
+                Some(client) => {
+                    client
+                        .publish(format!("tln/{name}/onboard"), QoS::AtLeastOnce, true, "0")
+                        .await
                 }
-            } else {
-                self.log(
+                None => Ok(()),
+            };
+            if let Err(e) = offboard {
+                self.warn(
                     MODULE,
-                    Warn,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
+                    format!("[{MODULE}] Failed to publish offboard of {name}. Err: {e:?}"),
                 )
                 .await;
             }
         }
+        // disconnect cleanly, no last will
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        self.start_mqtt(shutdown_rx).await;
+    }
+
+    async fn handle_cmd(&mut self, msg: &Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init(cmd).await,
+                "restart" => self.handle_cmd_restart().await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_PUBLISH => self.handle_cmd_publish(cmd).await,
+                ACTION_ARROW => Ok(()),
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
+            }
+        }
     }
 }
 
@@ -353,12 +458,23 @@ async fn process_event(
     msg_tx: &Sender<Msg>,
     mode: &Mode,
     gui_panel: &str,
+    broker: &str,
     event: Result<Event, rumqttc::ConnectionError>,
 ) -> bool {
     match event {
         Ok(Event::Incoming(Incoming::Publish(publish))) => {
             process_event_publish(msg_tx, mode, gui_panel, &publish).await;
         }
+        Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+            utils::msg::emit(
+                msg_tx,
+                MODULE,
+                messages::Event::MqttConnected {
+                    broker: broker.to_string(),
+                },
+            )
+            .await;
+        }
         Ok(_) => { /* 其他事件略過 */ }
         Err(e) => {
             panel::output_push(
@@ -388,7 +504,19 @@ async fn process_event_publish(
     if let Some(captures) = re.captures(topic) {
         let name = &captures[1];
         let key = &captures[2];
-        let payload = std::str::from_utf8(&publish.payload).expect("Failed to parse payload");
+        // one bad payload must not stop the receive loop
+        let Ok(payload) = std::str::from_utf8(&publish.payload) else {
+            panel::output_push(
+                MODULE,
+                msg_tx,
+                mode,
+                gui_panel,
+                Warn,
+                format!("[{MODULE}] <- pub::{key} {name}: payload is not UTF-8, ignored"),
+            )
+            .await;
+            return;
+        };
 
         match key {
             ACTION_ONBOARD | ACTION_VERSION | ACTION_TAILSCALE_IP | ACTION_TEMPERATURE
@@ -403,14 +531,12 @@ async fn process_event_publish(
                 )
                 .await;
 
-                let msg = Msg {
-                    ts: utils::time::ts(),
-                    module: MODULE.to_string(),
-                    data: Data::Cmd(Cmd {
-                        cmd: format!("p devices {key} {name} {payload}"),
-                    }),
-                };
-                let _ = msg_tx.send(msg).await;
+                utils::msg::command(
+                    msg_tx,
+                    MODULE,
+                    Command::new("devices", key).arg(name).arg(payload),
+                )
+                .await;
             }
             _ => {
                 panel::output_push(
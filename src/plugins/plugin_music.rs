@@ -1,13 +1,19 @@
 use async_trait::async_trait;
 use tokio::sync::mpsc::Sender;
 
-use crate::consts::MUSIC_FOLDER;
-use crate::messages::{ACTION_INIT, ACTION_SHOW, Data, Msg};
+use crate::cfg;
+use crate::messages::{ACTION_INIT, ACTION_SHOW, ActionSpec, Command, CommandError, Data, Msg};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{self, ffmpeg::Ffmpeg, yt_dlp::YtDlp};
 
 const MODULE: &str = "music";
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_INIT, "", "Check yt_dlp and ffmpeg"),
+    ActionSpec::new(ACTION_SHOW, "", "Show the yt_dlp and ffmpeg versions"),
+    ActionSpec::new("download", "<url>", "Download the audio of a video"),
+];
+
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
@@ -19,7 +25,8 @@ pub struct PluginUnit {
 
 impl PluginUnit {
     pub async fn new(msg_tx: Sender<Msg>) -> Self {
-        let _ = std::fs::create_dir_all(MUSIC_FOLDER);
+        let music_dir = cfg::music_dir();
+        let _ = std::fs::create_dir_all(&music_dir);
 
         utils::msg::log_new(&msg_tx, MODULE).await;
 
@@ -27,14 +34,14 @@ impl PluginUnit {
             name: MODULE.to_owned(),
             msg_tx,
             inited: false,
-            yt_dlp: YtDlp::new(MUSIC_FOLDER.to_string()).await,
+            yt_dlp: YtDlp::new(music_dir).await,
             ffmpeg: Ffmpeg::new().await,
         }
     }
 
-    async fn handle_cmd_init(&mut self) {
+    async fn handle_cmd_init(&mut self) -> Result<(), CommandError> {
         if self.inited {
-            return;
+            return Ok(());
         }
 
         let yt_dlp_installed = match self.yt_dlp.init().await {
@@ -76,9 +83,11 @@ impl PluginUnit {
         };
 
         self.inited = yt_dlp_installed && ffmpeg_installed;
+
+        Ok(())
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(MODULE, format!("[{MODULE}] inited: {:?}", self.inited))
             .await;
         self.info(
@@ -91,29 +100,32 @@ impl PluginUnit {
             format!("[{MODULE}] ffmpeg version: {}", self.ffmpeg.version()),
         )
         .await;
+
+        Ok(())
     }
 
-    async fn my_handle_cmd_downlad(&mut self, cmd_parts: &[String]) {
+    async fn my_handle_cmd_downlad(&mut self, cmd: &Command) -> Result<(), CommandError> {
         if !self.inited {
             self.warn(MODULE, format!("[{MODULE}] Not inited")).await;
-            return;
+            return Ok(());
         }
 
-        if let Some(url) = cmd_parts.get(3) {
-            self.info(MODULE, format!("[{MODULE}] download: {url}"))
-                .await;
+        let url = cmd.get_str(0, "url")?;
+        self.info(MODULE, format!("[{MODULE}] download: {url}"))
+            .await;
 
-            match self.yt_dlp.download(url).await {
-                Ok(_) => {
-                    self.info(MODULE, format!("[{MODULE}] download: {url} ok."))
-                        .await
-                }
-                Err(e) => {
-                    self.info(MODULE, format!("[{MODULE}] download: {url} failed. {e}"))
-                        .await
-                }
+        match self.yt_dlp.download(url).await {
+            Ok(_) => {
+                self.info(MODULE, format!("[{MODULE}] download: {url} ok."))
+                    .await
+            }
+            Err(e) => {
+                self.info(MODULE, format!("[{MODULE}] download: {url} failed. {e}"))
+                    .await
             }
         }
+
+        Ok(())
     }
 }
 
@@ -127,31 +139,24 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => self.handle_cmd_init().await,
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    "download" => self.my_handle_cmd_downlad(&cmd_parts).await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init().await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                "download" => self.my_handle_cmd_downlad(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -11,10 +11,11 @@ use serde_json::json;
 use tokio::sync::mpsc::Sender; // trait for `.encode()`
 
 use crate::cfg;
-use crate::consts::{self, NAS_FOLDER, WEB_PORT};
 use crate::messages::{
-    ACTION_DEVICES, ACTION_FILE_MODIFY, ACTION_FILE_REMOVE, ACTION_INIT, ACTION_NAS_STATE,
-    ACTION_ONBOARD, ACTION_SELF_NAS_STATE, ACTION_SHOW, ACTION_TAILSCALE_IP, Cmd, Data, Msg,
+    ACTION_DEVICES, ACTION_INIT, ACTION_NAS_STATE, ACTION_ONBOARD, ACTION_SELF_NAS_STATE,
+    ACTION_SHOW, ACTION_STATE, ActionSpec, Command, CommandError, Data, EVENT_DEVICE_ONBOARD,
+    EVENT_DEVICE_TAILSCALE_IP, EVENT_FILE_MODIFIED, EVENT_FILE_REMOVED, Event, Msg, QueryResult,
+    Reply,
 };
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{
@@ -25,12 +26,35 @@ use crate::utils::{
 const MODULE: &str = "nas";
 const WAITING_FOR_NAS_SERVER_IP_DELAY: u64 = 3;
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_SHOW, "", "Show the nas server, state and devices"),
+    ActionSpec::new(
+        ACTION_INIT,
+        "[nas_server]",
+        "Start syncing with the nas server",
+    ),
+    ActionSpec::new(
+        ACTION_DEVICES,
+        "<action> <name> <onboard>",
+        "Device update, e.g. `onboard <name> <onboard>`",
+    ),
+    ActionSpec::new(
+        ACTION_NAS_STATE,
+        "<name> <nas_state>",
+        "Set a client's state: Synced/Syncing",
+    ),
+    ActionSpec::new(
+        ACTION_SELF_NAS_STATE,
+        "<nas_state>",
+        "Set this client's state: Synced/Syncing",
+    ),
+];
+
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
     msg_tx: Sender<Msg>,
     inited: bool,
-    gui_panel: String,
     nas_server: String,
     nas_state: NasState,     // For client
     nas_infos: Vec<NasInfo>, // For server
@@ -44,7 +68,6 @@ impl PluginUnit {
             name: MODULE.to_owned(),
             msg_tx,
             inited: false,
-            gui_panel: "infos".to_string(),
             nas_server: String::new(),
             nas_state: NasState::Unsync,
             nas_infos: vec![],
@@ -53,12 +76,12 @@ impl PluginUnit {
 
     async fn update_infos_client_nas_state(&mut self) {
         // update infos
-        self.cmd(
+        self.emit(
             MODULE,
-            format!(
-                "p {} nas {ACTION_NAS_STATE} {:?}",
-                self.gui_panel, self.nas_state
-            ),
+            Event::NasState {
+                name: None,
+                nas_state: self.nas_state.clone(),
+            },
         )
         .await;
     }
@@ -96,12 +119,12 @@ impl PluginUnit {
 
                 // update infos
                 let nas_state_clone = nas_info.nas_state.clone();
-                self.cmd(
+                self.emit(
                     MODULE,
-                    format!(
-                        "p {} nas {ACTION_NAS_STATE} {name} {nas_state_clone:?}",
-                        self.gui_panel
-                    ),
+                    Event::NasState {
+                        name: Some(name.to_string()),
+                        nas_state: nas_state_clone,
+                    },
                 )
                 .await;
             }
@@ -120,20 +143,26 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_self_nas_state(&mut self, cmd_parts: &[String]) {
-        if let Some(nas_state) = cmd_parts.get(3) {
-            match nas_state.as_str() {
-                "Synced" => {
-                    self.nas_state = NasState::Synced;
-                    self.update_infos_client_nas_state().await;
-                }
-                "Syncing" => {
-                    self.nas_state = NasState::Syncing;
-                    self.update_infos_client_nas_state().await;
-                }
-                _ => todo!(),
+    async fn handle_cmd_self_nas_state(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        match cmd.get_str(0, "nas_state")? {
+            "Synced" => {
+                self.nas_state = NasState::Synced;
+                self.update_infos_client_nas_state().await;
+            }
+            "Syncing" => {
+                self.nas_state = NasState::Syncing;
+                self.update_infos_client_nas_state().await;
+            }
+            nas_state => {
+                return Err(CommandError::InvalidArg {
+                    name: "nas_state".to_string(),
+                    value: nas_state.to_string(),
+                    expected: "Synced/Syncing".to_string(),
+                });
             }
         }
+
+        Ok(())
     }
 
     async fn handle_nas_event_client_in_state_unsync_onboard(&mut self) {
@@ -150,10 +179,13 @@ impl PluginUnit {
                 .await;
 
                 // re-onboard
-                utils::msg::cmd(
+                utils::msg::command(
                     &msg_tx_clone,
                     MODULE,
-                    format!("p nas {ACTION_DEVICES} onboard {nas_server_clone} '1'"),
+                    Command::new(MODULE, ACTION_DEVICES)
+                        .arg(ACTION_ONBOARD)
+                        .arg(&nas_server_clone)
+                        .arg(1),
                 )
                 .await;
             });
@@ -167,7 +199,7 @@ impl PluginUnit {
         tokio::spawn(async move {
             loop {
                 // get file_list
-                let file_list = FileList::new(consts::NAS_FOLDER).await;
+                let file_list = FileList::new(&cfg::nas_dir()).await;
 
                 // send to server
                 utils::msg::log_info(
@@ -177,9 +209,10 @@ impl PluginUnit {
                 )
                 .await;
 
+                let web_port = cfg::web_port();
                 let client = reqwest::Client::new();
                 let json: serde_json::Value = client
-                    .post(format!("http://{}:{WEB_PORT}/check_hash", &nas_server_ip))
+                    .post(format!("http://{}:{web_port}/check_hash", &nas_server_ip))
                     .json(&json!({
                         "data": {
                             "name": cfg::name(),
@@ -204,10 +237,10 @@ impl PluginUnit {
                         format!("[{MODULE}] {}: Hash matched. Synced.", &nas_server_clone),
                     )
                     .await;
-                    utils::msg::cmd(
+                    utils::msg::command(
                         &msg_tx_clone,
                         MODULE,
-                        format!("p nas {ACTION_SELF_NAS_STATE} Synced"),
+                        Command::new(MODULE, ACTION_SELF_NAS_STATE).arg("Synced"),
                     )
                     .await;
                     break;
@@ -221,10 +254,10 @@ impl PluginUnit {
                         ),
                     )
                     .await;
-                    utils::msg::cmd(
+                    utils::msg::command(
                         &msg_tx_clone,
                         MODULE,
-                        format!("p nas {ACTION_SELF_NAS_STATE} Syncing"),
+                        Command::new(MODULE, ACTION_SELF_NAS_STATE).arg("Syncing"),
                     )
                     .await;
 
@@ -237,9 +270,10 @@ impl PluginUnit {
                     for action in &actions {
                         match action {
                             SyncAction::GetFile { filename, mtime: _ } => {
+                                let web_port = cfg::web_port();
                                 let client = reqwest::Client::new();
                                 let resp: serde_json::Value = client
-                                    .post(format!("http://{}:{WEB_PORT}/download", &nas_server_ip))
+                                    .post(format!("http://{}:{web_port}/download", &nas_server_ip))
                                     .json(&json!({
                                         "data": {
                                             "filename": filename,
@@ -325,94 +359,91 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_devices(&mut self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(3) {
-            let ts = utils::time::ts();
-            match action.as_str() {
-                ACTION_ONBOARD => {
-                    if let (Some(name), Some(onboard)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        let onboard_str = onboard.clone();
-                        let onboard = onboard == "1";
-                        if let Some(nas_info) = self
-                            .nas_infos
-                            .iter_mut()
-                            .find(|nas_info| nas_info.name == *name)
-                        {
-                            nas_info.ts = ts;
-                            nas_info.onboard = onboard;
-                        } else {
-                            let nas_info_add = NasInfo {
-                                ts,
-                                name: name.to_string(),
-                                onboard,
-                                nas_state: NasState::Unsync,
-                                tailscale_ip: None,
-                            };
-                            self.nas_infos.push(nas_info_add.clone());
-                        }
+    async fn handle_device_onboard(&mut self, name: &str, onboard: bool) {
+        let ts = utils::time::ts();
+        if let Some(nas_info) = self
+            .nas_infos
+            .iter_mut()
+            .find(|nas_info| nas_info.name == *name)
+        {
+            nas_info.ts = ts;
+            nas_info.onboard = onboard;
+        } else {
+            let nas_info_add = NasInfo {
+                ts,
+                name: name.to_string(),
+                onboard,
+                nas_state: NasState::Unsync,
+                tailscale_ip: None,
+            };
+            self.nas_infos.push(nas_info_add.clone());
+        }
 
-                        // update infos
-                        self.cmd(
-                            MODULE,
-                            format!("p {} nas onboard {name} {onboard_str}", self.gui_panel,),
-                        )
-                        .await;
-                        self.update_infos_client_nas_state().await;
-
-                        // handle_nas_event
-                        self.handle_nas_event(
-                            name,
-                            if onboard {
-                                &NasEvent::Onboard
-                            } else {
-                                &NasEvent::Offboard
-                            },
-                        )
-                        .await;
-                    }
-                }
-                ACTION_TAILSCALE_IP => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(tailscale_ip)) = (cmd_parts.get(4), cmd_parts.get(5)) {
-                        if let Some(nas_info) = self
-                            .nas_infos
-                            .iter_mut()
-                            .find(|nas_info| nas_info.name == *name)
-                        {
-                            nas_info.ts = ts;
-                            nas_info.tailscale_ip = Some(tailscale_ip.to_string());
-                        }
-                    }
-                }
-                _ => (),
+        // update infos
+        self.update_infos_client_nas_state().await;
+
+        // handle_nas_event
+        self.handle_nas_event(
+            &name.to_string(),
+            if onboard {
+                &NasEvent::Onboard
+            } else {
+                &NasEvent::Offboard
+            },
+        )
+        .await;
+    }
+
+    async fn handle_device_tailscale_ip(&mut self, name: &str, tailscale_ip: &str) {
+        if let Some(nas_info) = self
+            .nas_infos
+            .iter_mut()
+            .find(|nas_info| nas_info.name == *name)
+        {
+            nas_info.ts = utils::time::ts();
+            nas_info.tailscale_ip = Some(tailscale_ip.to_string());
+        }
+    }
+
+    // re-onboard while waiting for the nas server ip
+    async fn handle_cmd_devices(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        match cmd.get_str(0, "action")? {
+            ACTION_ONBOARD => {
+                let name = cmd.get_str(1, "name")?;
+                let onboard = cmd.get_bool(2, "onboard")?;
+                self.handle_device_onboard(name, onboard).await;
             }
+            action => return Err(CommandError::UnknownAction(action.to_string())),
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_init(&mut self, cmd_parts: &[String]) {
+    async fn handle_cmd_init(&mut self, cmd: &Command) -> Result<(), CommandError> {
         if self.inited {
-            return;
+            return Ok(());
         }
         self.inited = true;
 
-        let _ = fs::create_dir_all(NAS_FOLDER);
+        let _ = fs::create_dir_all(cfg::nas_dir());
 
-        if let Some(nas_server) = cmd_parts.get(3) {
+        if let Some(nas_server) = cmd.args.first() {
             self.nas_server = nas_server.to_string();
 
             // update infos
-            let msg = Msg {
-                ts: utils::time::ts(),
-                module: MODULE.to_string(),
-                data: Data::Cmd(Cmd {
-                    cmd: format!("p {} nas nas_server {nas_server}", self.gui_panel,),
-                }),
-            };
-            let _ = self.msg_tx.send(msg).await;
+            self.emit(
+                MODULE,
+                Event::NasServer {
+                    nas_server: nas_server.to_string(),
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(MODULE, format!("Nas Server: {}", self.nas_server))
             .await;
         self.info(MODULE, format!("Nas State: {:?}", self.nas_state))
@@ -434,40 +465,51 @@ impl PluginUnit {
             )
             .await;
         }
-    }
 
-    async fn handle_cmd_nas_state(&mut self, cmd_parts: &[String]) {
-        #[allow(clippy::collapsible_if)]
-        if let (Some(name), Some(nas_state)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-            if let Some(nas_info) = self
-                .nas_infos
-                .iter_mut()
-                .find(|nas_info| nas_info.name == *name)
-            {
-                match nas_state.as_str() {
-                    "Synced" => nas_info.nas_state = NasState::Synced,
-                    "Syncing" => nas_info.nas_state = NasState::Syncing,
-                    _ => todo!(),
-                }
+        Ok(())
+    }
 
-                // update infos
-                let nas_info_nas_state = nas_info.nas_state.clone();
-                self.cmd(
-                    MODULE,
-                    format!(
-                        "p {} nas {ACTION_NAS_STATE} {name} {nas_info_nas_state:?}",
-                        self.gui_panel
-                    ),
-                )
-                .await;
+    async fn handle_cmd_nas_state(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let nas_state = match cmd.get_str(1, "nas_state")? {
+            "Synced" => NasState::Synced,
+            "Syncing" => NasState::Syncing,
+            nas_state => {
+                return Err(CommandError::InvalidArg {
+                    name: "nas_state".to_string(),
+                    value: nas_state.to_string(),
+                    expected: "Synced/Syncing".to_string(),
+                });
             }
+        };
+
+        if let Some(nas_info) = self
+            .nas_infos
+            .iter_mut()
+            .find(|nas_info| nas_info.name == *name)
+        {
+            nas_info.nas_state = nas_state;
+
+            // update infos
+            let nas_info_nas_state = nas_info.nas_state.clone();
+            self.emit(
+                MODULE,
+                Event::NasState {
+                    name: Some(name.to_string()),
+                    nas_state: nas_info_nas_state,
+                },
+            )
+            .await;
         }
+
+        Ok(())
     }
 
     async fn remove_file(&self, remote_ip: &str, remote_name: &str, filename: &str) {
+        let web_port = cfg::web_port();
         let client = reqwest::Client::new();
         let _ = client
-            .post(format!("http://{remote_ip}:{WEB_PORT}/remove"))
+            .post(format!("http://{remote_ip}:{web_port}/remove"))
             .json(&json!({
                 "data": {
                     "filename": filename,
@@ -483,68 +525,54 @@ impl PluginUnit {
         .await;
     }
 
-    async fn handle_cmd_file_modify(&mut self, cmd_parts: &[String]) {
-        if let Some(filename) = cmd_parts.get(3) {
-            let filename_bytes = general_purpose::STANDARD
-                .decode(filename)
-                .expect("Failed to decode");
-            let filename = String::from_utf8(filename_bytes).expect("Invalid UTF-8");
-
-            // server
-            #[allow(clippy::collapsible_else_if)]
-            if self.nas_server == cfg::name() {
-                // send to all clients except me
-                for nas_info in &self.nas_infos {
-                    if nas_info.name != self.nas_server && nas_info.tailscale_ip.is_some() {
-                        put_file(
-                            &self.msg_tx,
-                            &nas_info.tailscale_ip.clone().unwrap(),
-                            &nas_info.name,
-                            &filename,
-                        )
-                        .await;
-                    }
+    async fn handle_event_file_modified(&mut self, filename: &str) {
+        // server
+        #[allow(clippy::collapsible_else_if)]
+        if self.nas_server == cfg::name() {
+            // send to all clients except me
+            for nas_info in &self.nas_infos {
+                if nas_info.name != self.nas_server && nas_info.tailscale_ip.is_some() {
+                    put_file(
+                        &self.msg_tx,
+                        &nas_info.tailscale_ip.clone().unwrap(),
+                        &nas_info.name,
+                        filename,
+                    )
+                    .await;
                 }
             }
-            // client
-            else {
-                if self.nas_state == NasState::Synced {
-                    let nas_server_ip = self.get_nas_server_ip().await.unwrap(); // must NOT be None
-                    put_file(&self.msg_tx, &nas_server_ip, &self.nas_server, &filename).await;
-                }
+        }
+        // client
+        else {
+            if self.nas_state == NasState::Synced {
+                let nas_server_ip = self.get_nas_server_ip().await.unwrap(); // must NOT be None
+                put_file(&self.msg_tx, &nas_server_ip, &self.nas_server, filename).await;
             }
         }
     }
 
-    async fn handle_cmd_file_remove(&mut self, cmd_parts: &[String]) {
-        if let Some(filename) = cmd_parts.get(3) {
-            let filename_bytes = general_purpose::STANDARD
-                .decode(filename)
-                .expect("Failed to decode");
-            let filename = String::from_utf8(filename_bytes).expect("Invalid UTF-8");
-
-            // server
-            #[allow(clippy::collapsible_else_if)]
-            if self.nas_server == cfg::name() {
-                // send to all clients except me
-                for nas_info in &self.nas_infos {
-                    if nas_info.name != self.nas_server && nas_info.tailscale_ip.is_some() {
-                        self.remove_file(
-                            &nas_info.tailscale_ip.clone().unwrap(),
-                            &nas_info.name,
-                            &filename,
-                        )
-                        .await;
-                    }
+    async fn handle_event_file_removed(&mut self, filename: &str) {
+        // server
+        #[allow(clippy::collapsible_else_if)]
+        if self.nas_server == cfg::name() {
+            // send to all clients except me
+            for nas_info in &self.nas_infos {
+                if nas_info.name != self.nas_server && nas_info.tailscale_ip.is_some() {
+                    self.remove_file(
+                        &nas_info.tailscale_ip.clone().unwrap(),
+                        &nas_info.name,
+                        filename,
+                    )
+                    .await;
                 }
             }
-            // client
-            else {
-                if self.nas_state == NasState::Synced {
-                    let nas_server_ip = self.get_nas_server_ip().await.unwrap(); // must NOT be None
-                    self.remove_file(&nas_server_ip, &self.nas_server, &filename)
-                        .await;
-                }
+        }
+        // client
+        else {
+            if self.nas_state == NasState::Synced {
+                let nas_server_ip = self.get_nas_server_ip().await.unwrap(); // must NOT be None
+                self.remove_file(&nas_server_ip, &self.nas_server, filename)
+                    .await;
             }
         }
     }
@@ -560,35 +588,60 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
+    fn subscriptions(&self) -> &[&str] {
+        &[
+            EVENT_DEVICE_ONBOARD,
+            EVENT_DEVICE_TAILSCALE_IP,
+            EVENT_FILE_MODIFIED,
+            EVENT_FILE_REMOVED,
+        ]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::DeviceOnboard { name, onboard } => {
+                self.handle_device_onboard(name, *onboard).await
+            }
+            Event::DeviceTailscaleIp { name, tailscale_ip } => {
+                self.handle_device_tailscale_ip(name, tailscale_ip).await
+            }
+            Event::FileModified { filename } => self.handle_event_file_modified(filename).await,
+            Event::FileRemoved { filename } => self.handle_event_file_removed(filename).await,
+            _ => (),
+        }
+    }
+
+    async fn query(&mut self, cmd: &Command) -> QueryResult {
+        match cmd.action.as_str() {
+            ACTION_STATE => Ok(Reply::Nas {
+                nas_server: self.nas_server.clone(),
+                nas_state: self.nas_state.clone(),
+                nas_infos: self.nas_infos.clone(),
+            }),
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        }
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_INIT => self.handle_cmd_init(&cmd_parts).await,
-                    ACTION_DEVICES => self.handle_cmd_devices(&cmd_parts).await,
-                    ACTION_NAS_STATE => self.handle_cmd_nas_state(&cmd_parts).await,
-                    ACTION_FILE_MODIFY => self.handle_cmd_file_modify(&cmd_parts).await,
-                    ACTION_FILE_REMOVE => self.handle_cmd_file_remove(&cmd_parts).await,
-                    ACTION_SELF_NAS_STATE => self.handle_cmd_self_nas_state(&cmd_parts).await,
-                    _ => {
-                        self.info(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.info(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_INIT => self.handle_cmd_init(cmd).await,
+                ACTION_DEVICES => self.handle_cmd_devices(cmd).await,
+                ACTION_NAS_STATE => self.handle_cmd_nas_state(cmd).await,
+                ACTION_SELF_NAS_STATE => self.handle_cmd_self_nas_state(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -609,10 +662,11 @@ async fn put_file(msg_tx: &Sender<Msg>, remote_ip: &str, remote_name: &str, file
         let bytes = fs::read(&file_path).unwrap();
         let hash_str = nas_info::hash_str(&String::from_utf8_lossy(&bytes));
 
+        let web_port = cfg::web_port();
         let client = reqwest::Client::new();
         let json: serde_json::Value = client
             // let json = client
-            .post(format!("http://{remote_ip}:{WEB_PORT}/verify_hash"))
+            .post(format!("http://{remote_ip}:{web_port}/verify_hash"))
             .json(&json!({
                 "data": {
                     "filename": filename,
@@ -643,9 +697,10 @@ async fn put_file(msg_tx: &Sender<Msg>, remote_ip: &str, remote_name: &str, file
                 .unwrap_or_else(|_| Utc::now().to_rfc3339());
             let encoded = general_purpose::STANDARD.encode(&bytes);
 
+            let web_port = cfg::web_port();
             let client = reqwest::Client::new();
             let _ = client
-                .post(format!("http://{remote_ip}:{WEB_PORT}/upload"))
+                .post(format!("http://{remote_ip}:{web_port}/upload"))
                 .json(&json!({
                     "data": {
                         "filename": filename,
@@ -1,7 +1,11 @@
 use async_trait::async_trait;
 use ratatui::{
     DefaultTerminal, Frame,
-    crossterm::{cursor::SetCursorStyle, execute},
+    crossterm::{
+        cursor::SetCursorStyle,
+        event::{DisableBracketedPaste, EnableBracketedPaste},
+        execute,
+    },
     layout::{Position, Rect},
     style::{Color, Style},
     text::{Line, Span, Text},
@@ -10,8 +14,12 @@ use ratatui::{
 
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
+use unicode_width::UnicodeWidthStr;
 
-use crate::messages::{ACTION_ARROW, ACTION_CREATE, ACTION_INIT, ACTION_SHOW, Data, Msg};
+use crate::messages::{
+    ACTION_ARROW, ACTION_CREATE, ACTION_INIT, ACTION_SHOW, ActionSpec, Command, CommandError, Data,
+    Msg,
+};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils;
 
@@ -19,6 +27,44 @@ const MODULE: &str = "panels";
 const MAX_OUTPUT_LEN: usize = 300;
 const CURSOR_PANEL_TITLE: &str = "command";
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_INIT, "", "Take over the terminal"),
+    ActionSpec::new(ACTION_SHOW, "", "List the panels"),
+    ActionSpec::new("tab", "", "Activate the next panel"),
+    ActionSpec::new("size", "<action>", "Resize the active panel: +x/-x/+y/-y"),
+    ActionSpec::new(
+        "location",
+        "<direction>",
+        "Move the active panel: up/down/left/right",
+    ),
+    ActionSpec::new(
+        ACTION_ARROW,
+        "<arrow>",
+        "Forward an arrow key to the active panel's plugin",
+    ),
+    ActionSpec::new(
+        "sub_title",
+        "<panel_title> <sub_title>",
+        "Set a panel's sub title",
+    ),
+    ActionSpec::new("output_clear", "", "Clear the active panel"),
+    ActionSpec::new(
+        "output_update",
+        "<panel_title> <output> [cursor]",
+        "Replace a panel's output, cursor: the columns before it on the command panel",
+    ),
+    ActionSpec::new(
+        "output_push",
+        "<panel_title> <output>",
+        "Append to a panel's output",
+    ),
+    ActionSpec::new(
+        ACTION_CREATE,
+        "<title> <plugin_name> <x> <y> <width> <height>",
+        "Create a panel for a plugin",
+    ),
+];
+
 #[derive(Debug)]
 struct Panel {
     title: String,
@@ -29,6 +75,8 @@ struct Panel {
     width: u16,
     height: u16,
     output: Vec<String>,
+    // columns before the cursor, at the end of the line when None
+    cursor: Option<u16>,
 }
 
 #[derive(Debug)]
@@ -72,136 +120,177 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_init(&mut self) {
+    async fn handle_cmd_init(&mut self) -> Result<(), CommandError> {
         if self.inited {
-            return;
+            return Ok(());
         }
         self.inited = true;
         self.terminal = Some(ratatui::init());
 
         let mut stdout = std::io::stdout();
-        execute!(stdout, SetCursorStyle::BlinkingBlock).unwrap();
+        // a paste comes as one event, not as keys with enter among them
+        execute!(stdout, SetCursorStyle::BlinkingBlock, EnableBracketedPaste).unwrap();
 
         let mut shutdown_rx = self.shutdown_tx.subscribe();
         tokio::spawn(async move {
             let _ = shutdown_rx.recv().await;
 
             let mut stdout = std::io::stdout();
-            execute!(stdout, SetCursorStyle::DefaultUserShape).unwrap();
+            execute!(
+                stdout,
+                SetCursorStyle::DefaultUserShape,
+                DisableBracketedPaste
+            )
+            .unwrap();
 
             ratatui::restore();
         });
 
         self.info(MODULE, format!("[{MODULE}] init")).await;
+
+        Ok(())
     }
 
-    fn handle_cmd_tab(&mut self) {
+    // redraw after `f` updates the panels, only when the terminal is ready
+    fn redraw<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
         if let Some(mut terminal) = self.terminal.take() {
-            self.active_panel = (self.active_panel + 1) % self.panels.len();
-
+            f(self);
             let _ = terminal.draw(|frame| self.draw(frame));
             self.terminal = Some(terminal);
         }
     }
 
-    fn handle_cmd_size(&mut self, cmd_parts: &[String]) {
-        #[allow(clippy::collapsible_if)]
-        if let Some(mut terminal) = self.terminal.take() {
-            if let Some(action) = cmd_parts.get(3) {
-                for (idx, panel) in self.panels.iter_mut().enumerate() {
-                    if idx == self.active_panel {
-                        match action.as_str() {
-                            "+x" => {
-                                panel.width += 1;
-                            }
-                            "-x" => {
-                                if panel.width > 2 {
-                                    panel.width -= 1;
-                                }
-                            }
-                            "+y" => {
-                                panel.height += 1;
-                            }
-                            "-y" => {
-                                if panel.height > 2 {
-                                    panel.height -= 1;
-                                }
-                            }
-                            _ => (),
-                        }
-                        break;
-                    }
+    fn handle_cmd_tab(&mut self) -> Result<(), CommandError> {
+        self.redraw(|this| this.active_panel = (this.active_panel + 1) % this.panels.len());
+
+        Ok(())
+    }
+
+    fn handle_cmd_size(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let action = cmd.get_str(0, "action")?;
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.get_mut(this.active_panel) {
+                match action {
+                    "+x" => panel.width += 1,
+                    "-x" if panel.width > 2 => panel.width -= 1,
+                    "+y" => panel.height += 1,
+                    "-y" if panel.height > 2 => panel.height -= 1,
+                    _ => (),
                 }
-                let _ = terminal.draw(|frame| self.draw(frame));
-                self.terminal = Some(terminal);
             }
-        }
+        });
+
+        Ok(())
     }
 
-    fn handle_cmd_location(&mut self, cmd_parts: &[String]) {
-        #[allow(clippy::collapsible_if)]
-        if let Some(mut terminal) = self.terminal.take() {
-            if let Some(direction) = cmd_parts.get(3) {
-                for (idx, panel) in self.panels.iter_mut().enumerate() {
-                    if idx == self.active_panel {
-                        match direction.as_str() {
-                            "up" => {
-                                if panel.y > 0 {
-                                    panel.y -= 1;
-                                }
-                            }
-                            "down" => {
-                                panel.y += 1;
-                            }
-                            "left" => {
-                                if panel.x > 0 {
-                                    panel.x -= 1;
-                                }
-                            }
-                            "right" => {
-                                panel.x += 1;
-                            }
-                            _ => (),
-                        }
-                        break;
-                    }
+    fn handle_cmd_location(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let direction = cmd.get_str(0, "direction")?;
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.get_mut(this.active_panel) {
+                match direction {
+                    "up" if panel.y > 0 => panel.y -= 1,
+                    "down" => panel.y += 1,
+                    "left" if panel.x > 0 => panel.x -= 1,
+                    "right" => panel.x += 1,
+                    _ => (),
                 }
-                let _ = terminal.draw(|frame| self.draw(frame));
-                self.terminal = Some(terminal);
             }
+        });
+
+        Ok(())
+    }
+
+    async fn handle_cmd_arrow(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let arrow = cmd.get_str(0, "arrow")?;
+        if let Some(panel) = self.panels.get(self.active_panel) {
+            let panel_plugin_name = panel.plugin_name.clone();
+            self.command(
+                MODULE,
+                Command::new(&panel_plugin_name, ACTION_ARROW).arg(arrow),
+            )
+            .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_arrow(&mut self, cmd_parts: &[String]) {
-        if let Some(arrow) = cmd_parts.get(3) {
-            for (idx, panel) in self.panels.iter_mut().enumerate() {
-                if idx == self.active_panel {
-                    let panel_plugin_name = panel.plugin_name.clone();
-                    self.cmd(
-                        MODULE,
-                        format!("p {panel_plugin_name} {ACTION_ARROW} {arrow}"),
-                    )
-                    .await;
-                    break;
-                }
+    fn handle_cmd_sub_title(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let panel_title = cmd.get_str(0, "panel_title")?;
+        let sub_title = cmd.get_str(1, "sub_title")?;
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.iter_mut().find(|p| p.title == *panel_title) {
+                panel.sub_title = sub_title.to_string();
             }
-        }
+        });
+
+        Ok(())
     }
 
-    async fn handle_cmd_sub_title(&mut self, cmd_parts: &[String]) {
-        #[allow(clippy::collapsible_if)]
-        if let Some(mut terminal) = self.terminal.take() {
-            if let (Some(panel_title), Some(sub_title)) = (cmd_parts.get(3), cmd_parts.get(4)) {
-                if let Some(panel) = self.panels.iter_mut().find(|p| p.title == *panel_title) {
-                    panel.sub_title = sub_title.to_string();
+    fn handle_cmd_output_clear(&mut self) -> Result<(), CommandError> {
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.get_mut(this.active_panel) {
+                panel.output.clear();
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handle_cmd_output_update(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let panel_title = cmd.get_str(0, "panel_title")?;
+        let output = cmd.get_str(1, "output")?;
+        let cursor = match cmd.args.get(2) {
+            Some(_) => Some(cmd.get::<u16>(2, "cursor")?),
+            None => None,
+        };
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.iter_mut().find(|p| p.title == *panel_title) {
+                panel.output.clear();
+                panel.output.push(output.to_string());
+                panel.cursor = cursor;
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handle_cmd_output_push(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let panel_title = cmd.get_str(0, "panel_title")?;
+        let output = cmd.get_str(1, "output")?;
+        self.redraw(|this| {
+            if let Some(panel) = this.panels.iter_mut().find(|p| p.title == *panel_title) {
+                panel.output.push(output.to_string());
+                let panel_output_len = panel.output.len();
+                if panel_output_len > MAX_OUTPUT_LEN {
+                    panel.output.drain(..panel_output_len - MAX_OUTPUT_LEN);
                 }
             }
-            let _ = terminal.draw(|frame| self.draw(frame));
-            self.terminal = Some(terminal);
-        }
+        });
+
+        Ok(())
+    }
+
+    fn handle_cmd_create(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let panel = Panel {
+            title: cmd.get_str(0, "title")?.to_string(),
+            sub_title: String::new(),
+            plugin_name: cmd.get_str(1, "plugin_name")?.to_string(),
+            x: cmd.get(2, "x")?,
+            y: cmd.get(3, "y")?,
+            width: cmd.get(4, "width")?,
+            height: cmd.get(5, "height")?,
+            output: vec![],
+            cursor: None,
+        };
+        self.redraw(|this| this.panels.push(panel));
+
+        Ok(())
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(MODULE, format!("[{MODULE}] show")).await;
         self.info(MODULE, format!("[{MODULE}] inited: {}", self.inited))
             .await;
@@ -220,6 +309,8 @@ impl PluginUnit {
             )
             .await;
         }
+
+        Ok(())
     }
 }
 
@@ -233,135 +324,32 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => self.handle_cmd_init().await,
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    "tab" => self.handle_cmd_tab(),
-                    "size" => self.handle_cmd_size(&cmd_parts),
-                    "location" => self.handle_cmd_location(&cmd_parts),
-                    ACTION_ARROW => self.handle_cmd_arrow(&cmd_parts).await,
-                    "sub_title" => self.handle_cmd_sub_title(&cmd_parts).await,
-                    "output_clear" => {
-                        if let Some(mut terminal) = self.terminal.take() {
-                            for (idx, panel) in self.panels.iter_mut().enumerate() {
-                                if idx == self.active_panel {
-                                    panel.output.clear();
-                                    break;
-                                }
-                            }
-                            let _ = terminal.draw(|frame| self.draw(frame));
-                            self.terminal = Some(terminal);
-                        }
-                    }
-                    "output_update" =>
-                    {
-                        #[allow(clippy::collapsible_if)]
-                        if let Some(mut terminal) = self.terminal.take() {
-                            if let (Some(panel_title), Some(output)) =
-                                (cmd_parts.get(3), cmd_parts.get(4))
-                            {
-                                if let Some(panel) =
-                                    self.panels.iter_mut().find(|p| p.title == *panel_title)
-                                {
-                                    panel.output.clear();
-                                    panel.output.push(output.to_string());
-                                }
-                            }
-                            let _ = terminal.draw(|frame| self.draw(frame));
-                            self.terminal = Some(terminal);
-                        }
-                    }
-                    "output_push" =>
-                    {
-                        #[allow(clippy::collapsible_if)]
-                        if let Some(mut terminal) = self.terminal.take() {
-                            if let (Some(panel_title), Some(output)) =
-                                (cmd_parts.get(3), cmd_parts.get(4))
-                            {
-                                if let Some(panel) =
-                                    self.panels.iter_mut().find(|p| p.title == *panel_title)
-                                {
-                                    panel.output.push(output.to_string());
-                                    let panel_output_len = panel.output.len();
-                                    if panel_output_len > MAX_OUTPUT_LEN {
-                                        panel.output.drain(..panel_output_len - MAX_OUTPUT_LEN);
-                                    }
-                                }
-                            }
-                            let _ = terminal.draw(|frame| self.draw(frame));
-                            self.terminal = Some(terminal);
-                        }
-                    }
-                    ACTION_CREATE => {
-                        if let Some(mut terminal) = self.terminal.take() {
-                            if let (
-                                Some(title),
-                                Some(plugin_name),
-                                Some(x),
-                                Some(y),
-                                Some(width),
-                                Some(height),
-                            ) = (
-                                cmd_parts.get(3),
-                                cmd_parts.get(4),
-                                cmd_parts.get(5),
-                                cmd_parts.get(6),
-                                cmd_parts.get(7),
-                                cmd_parts.get(8),
-                            ) {
-                                let panel = Panel {
-                                    title: title.to_string(),
-                                    sub_title: String::new(),
-                                    plugin_name: plugin_name.to_string(),
-                                    x: x.parse::<u16>()
-                                        .unwrap_or_else(|_| panic!("Failed to parse x (`{x}`)")),
-                                    y: y.parse::<u16>()
-                                        .unwrap_or_else(|_| panic!("Failed to parse y (`{y}`)")),
-                                    width: width.parse::<u16>().unwrap_or_else(|_| {
-                                        panic!("Failed to parse width (`{width}`)")
-                                    }),
-                                    height: height.parse::<u16>().unwrap_or_else(|_| {
-                                        panic!("Failed to parse height (`{height}`)")
-                                    }),
-                                    output: vec![],
-                                };
-                                self.panels.push(panel);
-
-                                let _ = terminal.draw(|frame| self.draw(frame));
-                                self.terminal = Some(terminal);
-                            } else {
-                                self.warn(
-                                    MODULE,
-                                    format!(
-                                        "[{MODULE}] Missing title/plugin_name/x/y/width/height for cmd `{}`.",
-                                        cmd.cmd
-                                    ),
-                                )
-                                .await;
-                            }
-                        }
-                    }
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init().await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                "tab" => self.handle_cmd_tab(),
+                "size" => self.handle_cmd_size(cmd),
+                "location" => self.handle_cmd_location(cmd),
+                ACTION_ARROW => self.handle_cmd_arrow(cmd).await,
+                "sub_title" => self.handle_cmd_sub_title(cmd),
+                "output_clear" => self.handle_cmd_output_clear(),
+                "output_update" => self.handle_cmd_output_update(cmd),
+                "output_push" => self.handle_cmd_output_push(cmd),
+                ACTION_CREATE => self.handle_cmd_create(cmd),
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -427,19 +415,25 @@ fn draw_panel(panel: &mut Panel, frame: &mut Frame, active: bool) {
         })
         .collect();
 
+    // the command line scrolls sideways to keep the cursor inside the panel
+    let cursor = (panel.title == CURSOR_PANEL_TITLE && !panel.output.is_empty()).then(|| {
+        panel
+            .cursor
+            .unwrap_or_else(|| panel.output[0].width() as u16)
+    });
+    let last_column = panel_area.width.saturating_sub(3);
+    let h_offset = cursor.map_or(0, |cursor| cursor.saturating_sub(last_column));
+
     // let text = Paragraph::new(Text::from(panel.output.join("\n")))
     let text = Paragraph::new(Text::from(lines))
         .style(Style::default().fg(if active { Color::Cyan } else { Color::White }))
-        .scroll((scroll_offset, 0));
+        .scroll((scroll_offset, h_offset));
 
     frame.render_widget(text, panel_block.inner(panel_area));
 
     // cursor is only for panel command
-    if panel.title == CURSOR_PANEL_TITLE && !panel.output.is_empty() {
-        frame.set_cursor_position(Position::new(
-            panel_x + panel.output[0].len() as u16 + 1,
-            panel_y + 1,
-        ));
+    if let Some(cursor) = cursor {
+        frame.set_cursor_position(Position::new(panel_x + cursor - h_offset + 1, panel_y + 1));
     }
 }
 
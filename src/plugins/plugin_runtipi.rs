@@ -1,15 +1,21 @@
 use async_trait::async_trait;
-use base64::Engine as _;
-use base64::engine::general_purpose;
 use tokio::sync::mpsc::Sender;
 
 use crate::cfg;
-use crate::messages::{ACTION_ARROW, ACTION_FILE_MODIFY, ACTION_INIT, ACTION_SHOW, Data, Msg};
+use crate::messages::{
+    ACTION_ARROW, ACTION_INIT, ACTION_SHOW, ActionSpec, Command, CommandError, Data,
+    EVENT_FILE_MODIFIED, Event, Msg,
+};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils;
 
 const MODULE: &str = "runtipi";
-const RUNTIPI_MUSIC_FOLDER: &str = "~/runtipi/media/data/music/";
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_INIT, "<runtipi_server>", "Set the runtipi server"),
+    ActionSpec::new(ACTION_SHOW, "", "Show the runtipi server"),
+    ActionSpec::new(ACTION_ARROW, "<arrow>", "Arrow key from the panel, ignored"),
+];
 
 #[derive(Debug)]
 pub struct PluginUnit {
@@ -29,18 +35,18 @@ impl PluginUnit {
         }
     }
 
-    async fn handle_cmd_init(&mut self, cmd_parts: &[String]) {
-        if let Some(runtipi_server) = cmd_parts.get(3) {
-            self.runtipi_server = runtipi_server.to_string();
-        }
+    async fn handle_cmd_init(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        self.runtipi_server = cmd.get_str(0, "runtipi_server")?.to_string();
+        Ok(())
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(MODULE, format!("Runtipi Server: {}", self.runtipi_server))
             .await;
+        Ok(())
     }
 
-    async fn handle_cmd_file_modify(&mut self, cmd_parts: &[String]) {
+    async fn handle_event_file_modified(&mut self, filename: &str) {
         // only for runtipi server
         if self.runtipi_server != cfg::name() {
             self.warn(
@@ -51,25 +57,27 @@ impl PluginUnit {
             return;
         }
 
-        if let Some(filename) = cmd_parts.get(3) {
-            let filename_bytes = general_purpose::STANDARD
-                .decode(filename)
-                .expect("Failed to decode");
-            let filename = String::from_utf8(filename_bytes).expect("Invalid UTF-8");
-
-            // if filename starts with "./nas/music/"
-            if filename.starts_with("./nas/music/") {
-                // cp file to RUNTIPI_MUSIC_FOLDER use system command
-                let cmd = format!("cp -f {} {}", filename, RUNTIPI_MUSIC_FOLDER);
-                self.info(MODULE, format!("[{MODULE}] Running command: {cmd}"))
+        // if filename is in the music folder, e.g. "./nas/music/"
+        if filename.starts_with(&format!("{}/", cfg::music_dir())) {
+            // cp file to runtipi.music_dir use system command
+            let cmd = format!(
+                "cp -f {} {}",
+                shell_words::quote(filename),
+                cfg::get().runtipi.music_dir
+            );
+            self.info(MODULE, format!("[{MODULE}] Running command: {cmd}"))
+                .await;
+            if let Err(e) = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(cmd)
+                .output()
+                .await
+            {
+                self.warn(MODULE, format!("[{MODULE}] Failed to copy file: {e}"))
+                    .await;
+            } else {
+                self.info(MODULE, format!("[{MODULE}] File copied successfully."))
                     .await;
-                if let Err(e) = std::process::Command::new("sh").arg("-c").arg(cmd).output() {
-                    self.warn(MODULE, format!("[{MODULE}] Failed to copy file: {e}"))
-                        .await;
-                } else {
-                    self.info(MODULE, format!("[{MODULE}] File copied successfully."))
-                        .await;
-                }
             }
         }
     }
@@ -85,32 +93,34 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
+    fn subscriptions(&self) -> &[&str] {
+        &[EVENT_FILE_MODIFIED]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        if let Event::FileModified { filename } = event {
+            self.handle_event_file_modified(filename).await;
+        }
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => self.handle_cmd_init(&cmd_parts).await,
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_FILE_MODIFY => self.handle_cmd_file_modify(&cmd_parts).await,
-                    ACTION_ARROW => (),
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init(cmd).await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_ARROW => Ok(()),
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
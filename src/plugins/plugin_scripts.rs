@@ -1,30 +1,319 @@
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::path::Path;
 
 use async_trait::async_trait;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
 
-use crate::messages::{ACTION_INIT, ACTION_SHOW, Data, Msg};
-use crate::plugins::plugins_main;
-use crate::utils;
+use crate::cfg;
+use crate::messages::{
+    ACTION_CANCEL, ACTION_CHECK, ACTION_INIT, ACTION_JOBS, ACTION_RUN, ACTION_SHOW, ActionSpec,
+    Command, CommandError, Data, Event, Msg, topic_matches,
+};
+use crate::plugins::plugins_main::{self, Plugin};
+use crate::utils::{
+    self,
+    script::{Line, Step},
+};
 
 const MODULE: &str = "scripts";
+// topics kept for a job between two waits
+const TOPIC_SIZE: usize = 1024;
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_INIT,
+        "<scripts_filename>",
+        "Run the init script as a job, see run",
+    ),
+    ActionSpec::new(ACTION_SHOW, "", "Print the script file in use"),
+    ActionSpec::new(
+        ACTION_RUN,
+        "<scripts_filename>",
+        "Run a script as a job, with set, include, if host, sleep and wait",
+    ),
+    ActionSpec::new(ACTION_JOBS, "", "List the running jobs and their steps"),
+    ActionSpec::new(ACTION_CANCEL, "<id>", "Cancel a running job"),
+    ActionSpec::new(
+        ACTION_CHECK,
+        "<scripts_filename>",
+        "Print the steps of a script and its errors, without running it",
+    ),
+];
+
+// a script running in the background
+#[derive(Debug)]
+struct Job {
+    id: u32,
+    file: String,
+    // e.g. `./gui.scripts:12 wait mqtt.connected 30`
+    step_rx: watch::Receiver<String>,
+    task: JoinHandle<()>,
+}
 
 #[derive(Debug)]
 pub struct PluginUnit {
     name: String,
     msg_tx: Sender<Msg>,
+    shutdown_tx: broadcast::Sender<()>,
     scripts_filename: Option<String>,
+    jobs: Vec<Job>,
+    last_id: u32,
+    // the topic of every event, for the waiting jobs
+    topic_tx: broadcast::Sender<String>,
 }
 
 impl PluginUnit {
-    pub async fn new(msg_tx: Sender<Msg>) -> Self {
+    pub async fn new(msg_tx: Sender<Msg>, shutdown_tx: broadcast::Sender<()>) -> Self {
         utils::msg::log_new(&msg_tx, MODULE).await;
 
+        let (topic_tx, _) = broadcast::channel(TOPIC_SIZE);
         Self {
             name: MODULE.to_owned(),
             msg_tx,
+            shutdown_tx,
             scripts_filename: None,
+            jobs: vec![],
+            last_id: 0,
+            topic_tx,
+        }
+    }
+
+    async fn handle_cmd_init(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let scripts_filename = cmd.get_str(0, "scripts_filename")?;
+
+        if Path::new(scripts_filename).is_file() {
+            if self.start_job(scripts_filename).await {
+                self.info(
+                    MODULE,
+                    format!("[{MODULE}] init script (`{scripts_filename}`)"),
+                )
+                .await;
+            }
+        } else {
+            self.warn(
+                MODULE,
+                format!("[{MODULE}] init script (`{scripts_filename}`) not found!"),
+            )
+            .await;
+        }
+        self.scripts_filename = Some(scripts_filename.to_string());
+
+        Ok(())
+    }
+
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
+        if let Some(scripts_filename) = &self.scripts_filename {
+            if let Ok(file) = File::open(scripts_filename) {
+                let reader = io::BufReader::new(file);
+
+                for line in reader.lines().map_while(Result::ok) {
+                    self.info(MODULE, format!("[{MODULE}] {line}")).await;
+                }
+            } else {
+                self.warn(
+                    MODULE,
+                    format!("[{MODULE}] init script (`{scripts_filename}`) not found!"),
+                )
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_run(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let scripts_filename = cmd.get_str(0, "scripts_filename")?;
+        self.start_job(scripts_filename).await;
+
+        Ok(())
+    }
+
+    async fn handle_cmd_jobs(&mut self) -> Result<(), CommandError> {
+        self.jobs.retain(|job| !job.task.is_finished());
+
+        self.info(MODULE, format!("{:<4} {:<20} {}", "Id", "File", "Step"))
+            .await;
+        for job in &self.jobs {
+            let step = job.step_rx.borrow().clone();
+            self.info(MODULE, format!("{:<4} {:<20} {step}", job.id, job.file))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_cancel(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let id = cmd.get::<u32>(0, "id")?;
+        self.jobs.retain(|job| !job.task.is_finished());
+
+        match self.jobs.iter().position(|job| job.id == id) {
+            Some(idx) => {
+                let job = self.jobs.remove(idx);
+                job.task.abort();
+                self.info(
+                    MODULE,
+                    format!("[{MODULE}] Job {id} cancelled (`{}`)", job.file),
+                )
+                .await;
+            }
+            None => {
+                self.warn(MODULE, format!("[{MODULE}] No running job {id}"))
+                    .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_check(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let scripts_filename = cmd.get_str(0, "scripts_filename")?;
+
+        match utils::script::check(scripts_filename, &cfg::name()) {
+            Ok(lines) => {
+                for line in &lines {
+                    self.info(
+                        MODULE,
+                        format!("[{MODULE}] {}:{} {}", line.file, line.no, line.step),
+                    )
+                    .await;
+                }
+                self.info(
+                    MODULE,
+                    format!(
+                        "[{MODULE}] `{scripts_filename}`: {} steps, no errors",
+                        lines.len()
+                    ),
+                )
+                .await;
+            }
+            Err(errors) => {
+                for e in errors {
+                    self.warn(MODULE, format!("[{MODULE}] {e}")).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // nothing runs when any line of the script is wrong
+    async fn start_job(&mut self, scripts_filename: &str) -> bool {
+        let lines = match utils::script::load(scripts_filename, &cfg::name()) {
+            Ok(lines) => lines,
+            Err(errors) => {
+                for e in errors {
+                    self.warn(MODULE, format!("[{MODULE}] {e}")).await;
+                }
+                return false;
+            }
+        };
+
+        self.jobs.retain(|job| !job.task.is_finished());
+        self.last_id += 1;
+        let id = self.last_id;
+        let (step_tx, step_rx) = watch::channel(String::new());
+        let task = tokio::spawn(run_job(
+            id,
+            scripts_filename.to_string(),
+            lines,
+            self.msg_tx.clone(),
+            self.topic_tx.subscribe(),
+            self.shutdown_tx.subscribe(),
+            step_tx,
+        ));
+        self.jobs.push(Job {
+            id,
+            file: scripts_filename.to_string(),
+            step_rx,
+            task,
+        });
+
+        self.info(
+            MODULE,
+            format!("[{MODULE}] Job {id} started (`{scripts_filename}`)"),
+        )
+        .await;
+
+        true
+    }
+}
+
+// one step after another: commands are sent without waiting for them, use
+// `wait` for what they bring about
+async fn run_job(
+    id: u32,
+    file: String,
+    lines: Vec<Line>,
+    msg_tx: Sender<Msg>,
+    mut topic_rx: broadcast::Receiver<String>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    step_tx: watch::Sender<String>,
+) {
+    let steps = async {
+        for line in lines {
+            step_tx.send_replace(format!("{}:{} {}", line.file, line.no, line.step));
+
+            match line.step {
+                Step::Cmd(cmd) => utils::msg::cmd(&msg_tx, MODULE, cmd).await,
+                Step::Sleep(duration) => sleep(duration).await,
+                Step::Wait {
+                    topic,
+                    timeout: None,
+                } => wait_for(&mut topic_rx, &topic).await,
+                Step::Wait {
+                    topic,
+                    timeout: Some(duration),
+                } => {
+                    if timeout(duration, wait_for(&mut topic_rx, &topic))
+                        .await
+                        .is_err()
+                    {
+                        return Err(format!(
+                            "{}:{} timed out waiting for `{topic}`",
+                            line.file, line.no
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    };
+
+    let result = tokio::select! {
+        result = steps => result,
+        _ = shutdown_rx.recv() => return,
+    };
+    match result {
+        Ok(()) => {
+            utils::msg::log_info(
+                &msg_tx,
+                MODULE,
+                format!("[{MODULE}] Job {id} done (`{file}`)"),
+            )
+            .await;
+        }
+        Err(e) => {
+            utils::msg::log_warn(&msg_tx, MODULE, format!("[{MODULE}] Job {id} stopped. {e}"))
+                .await;
+        }
+    }
+}
+
+// the next event matching `pattern`, including those since the last wait.
+// nothing from before the job started, `topic_rx` subscribed then
+async fn wait_for(topic_rx: &mut broadcast::Receiver<String>, pattern: &str) {
+    loop {
+        match topic_rx.recv().await {
+            Ok(topic) if topic_matches(pattern, &topic) => return,
+            Ok(_) | Err(RecvError::Lagged(_)) => (),
+            // gone with the plugin, wait to be cancelled
+            Err(RecvError::Closed) => std::future::pending().await,
         }
     }
 }
@@ -39,73 +328,32 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    // for `wait`
+    fn subscriptions(&self) -> &[&str] {
+        &["*"]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        let _ = self.topic_tx.send(event.topic().to_string());
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => {
-                        if let Some(scripts_filename) = cmd_parts.get(3) {
-                            if let Ok(file) = File::open(scripts_filename) {
-                                let reader = io::BufReader::new(file);
-
-                                for line in reader.lines().map_while(Result::ok) {
-                                    self.cmd(MODULE, line).await;
-                                }
-
-                                self.info(
-                                    MODULE,
-                                    format!("[{MODULE}] init script (`{scripts_filename}`)"),
-                                )
-                                .await;
-                            } else {
-                                self.warn(
-                                    MODULE,
-                                    format!(
-                                        "[{MODULE}] init script (`{scripts_filename}`) not found!"
-                                    ),
-                                )
-                                .await;
-                            }
-                            self.scripts_filename = Some(scripts_filename.to_string());
-                        }
-                    }
-                    ACTION_SHOW => {
-                        if let Some(scripts_filename) = &self.scripts_filename {
-                            if let Ok(file) = File::open(scripts_filename) {
-                                let reader = io::BufReader::new(file);
-
-                                for line in reader.lines().map_while(Result::ok) {
-                                    self.info(MODULE, format!("[{MODULE}] {line}")).await;
-                                }
-                            } else {
-                                self.warn(
-                                    MODULE,
-                                    format!(
-                                        "[{MODULE}] init script (`{scripts_filename}`) not found!"
-                                    ),
-                                )
-                                .await;
-                            }
-                        }
-                    }
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init(cmd).await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_RUN => self.handle_cmd_run(cmd).await,
+                ACTION_JOBS => self.handle_cmd_jobs().await,
+                ACTION_CANCEL => self.handle_cmd_cancel(cmd).await,
+                ACTION_CHECK => self.handle_cmd_check(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -1,22 +1,28 @@
 use async_trait::async_trait;
-use log::Level::Info;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
 use tokio::{
     select,
     time::{Duration, sleep},
 };
 
+use crate::cfg::{self, Cfg};
 use crate::messages::{
     ACTION_APP_UPTIME, ACTION_ONBOARD, ACTION_PUBLISH, ACTION_SHOW, ACTION_TAILSCALE_IP,
-    ACTION_TEMPERATURE, ACTION_VERSION, Cmd, Data, Msg,
+    ACTION_TEMPERATURE, ACTION_VERSION, ActionSpec, Command, CommandError, Data,
+    EVENT_DEVICE_ONBOARD, Event, Msg,
 };
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{self, dev_info};
 
 const MODULE: &str = "system";
 const VERSION: &str = "3.1.0";
-const PUBLISH_INTERVAL: u64 = 300;
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_SHOW, "", "Show version, tailscale ip and uptime"),
+    ActionSpec::new(ACTION_PUBLISH, "", "Publish the system info over mqtt"),
+];
 
 #[derive(Debug)]
 struct SystemInfo {
@@ -29,48 +35,34 @@ struct SystemInfo {
 pub struct PluginUnit {
     name: String,
     msg_tx: Sender<Msg>,
+    shutdown_tx: broadcast::Sender<()>,
     system_info: SystemInfo,
+    publish_interval: u64,
+    publish_task: JoinHandle<()>,
 }
 
 impl PluginUnit {
     pub async fn new(msg_tx: Sender<Msg>, shutdown_tx: broadcast::Sender<()>) -> Self {
         utils::msg::log_new(&msg_tx, MODULE).await;
 
-        let msg_tx_clone = msg_tx.clone();
-        let mut shutdown_rx = shutdown_tx.subscribe();
-        tokio::spawn(async move {
-            loop {
-                select! {
-                    _ = sleep(Duration::from_secs(PUBLISH_INTERVAL)) => {
-                        let msg = Msg {
-                            ts: utils::time::ts(),
-                            module: MODULE.to_string(),
-                            data: Data::Cmd(Cmd {
-                                cmd: format!("p system {ACTION_PUBLISH}"),
-                            })
-                        };
-                        let _ = &msg_tx_clone.send(msg).await;
-                    }
-                    _ = shutdown_rx.recv() => {
-                        println!("Shutdown signal received. Exiting task.");
-                        break;
-                    }
-                }
-            }
-        });
+        let publish_interval = cfg::get().system.publish_interval;
+        let publish_task = start_publishing(&msg_tx, &shutdown_tx, publish_interval);
 
         Self {
             name: MODULE.to_owned(),
             msg_tx,
+            shutdown_tx,
             system_info: SystemInfo {
                 version: VERSION.to_string(),
                 tailscale_ip: utils::system::get_tailscale_ip(),
                 ts_start: utils::time::uptime(),
             },
+            publish_interval,
+            publish_task,
         }
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(
             MODULE,
             format!("[{MODULE}] Version: v{}", self.system_info.version),
@@ -106,61 +98,76 @@ impl PluginUnit {
             ),
         )
         .await;
+
+        Ok(())
     }
 
-    async fn handle_cmd_publish(&mut self) {
+    async fn handle_cmd_publish(&mut self) -> Result<(), CommandError> {
         self.update_system().await;
+        Ok(())
     }
 
     async fn update_system(&mut self) {
         // onboard
-        self.cmd(
-            MODULE,
-            format!("p mqtt {ACTION_PUBLISH} false {ACTION_ONBOARD} '1'"),
-        )
-        .await;
+        self.publish(ACTION_ONBOARD, "1".to_string()).await;
 
         // version
-        self.cmd(
-            MODULE,
-            format!(
-                "p mqtt {ACTION_PUBLISH} false {ACTION_VERSION} '{}'",
-                self.system_info.version
-            ),
-        )
-        .await;
+        self.publish(ACTION_VERSION, self.system_info.version.clone())
+            .await;
 
         // tailscale_ip
-        self.cmd(
-            MODULE,
-            format!(
-                "p mqtt {ACTION_PUBLISH} false {ACTION_TAILSCALE_IP} '{}'",
-                self.system_info
-                    .tailscale_ip
-                    .clone()
-                    .unwrap_or("n/a".to_string())
-            ),
+        self.publish(
+            ACTION_TAILSCALE_IP,
+            self.system_info
+                .tailscale_ip
+                .clone()
+                .unwrap_or("n/a".to_string()),
         )
         .await;
 
         // temperature
         let temperature = get_temperature();
-        self.cmd(
-            MODULE,
-            format!("p mqtt {ACTION_PUBLISH} false {ACTION_TEMPERATURE} '{temperature}'",),
-        )
-        .await;
+        self.publish(ACTION_TEMPERATURE, temperature.to_string())
+            .await;
 
         // app uptime
         let uptime = utils::time::uptime() - self.system_info.ts_start;
-        self.cmd(
+        self.publish(ACTION_APP_UPTIME, uptime.to_string()).await;
+    }
+
+    async fn publish(&self, key: &str, payload: String) {
+        self.command(
             MODULE,
-            format!("p mqtt {ACTION_PUBLISH} false {ACTION_APP_UPTIME} '{uptime}'",),
+            Command::new("mqtt", ACTION_PUBLISH)
+                .arg(false)
+                .arg(key)
+                .arg(payload),
         )
         .await;
     }
 }
 
+fn start_publishing(
+    msg_tx: &Sender<Msg>,
+    shutdown_tx: &broadcast::Sender<()>,
+    publish_interval: u64,
+) -> JoinHandle<()> {
+    let msg_tx_clone = msg_tx.clone();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = sleep(Duration::from_secs(publish_interval)) => {
+                    utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, ACTION_PUBLISH)).await;
+                }
+                _ = shutdown_rx.recv() => {
+                    break;
+                }
+            }
+        }
+    })
+}
+
 #[async_trait]
 impl plugins_main::Plugin for PluginUnit {
     fn name(&self) -> &str {
@@ -171,32 +178,46 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn subscriptions(&self) -> &[&str] {
+        &[EVENT_DEVICE_ONBOARD]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        // someone onboard, publish immediately
+        if let Event::DeviceOnboard { onboard: true, .. } = event {
+            self.update_system().await;
+        }
+    }
+
+    async fn reconfigure(&mut self, cfg: &Cfg) {
+        if cfg.system.publish_interval == self.publish_interval {
+            return;
+        }
+
+        self.publish_interval = cfg.system.publish_interval;
+        self.publish_task.abort();
+        self.publish_task =
+            start_publishing(&self.msg_tx, &self.shutdown_tx, self.publish_interval);
+        self.info(
+            MODULE,
+            format!("[{MODULE}] Publish every {} secs", self.publish_interval),
+        )
+        .await;
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    ACTION_PUBLISH => self.handle_cmd_publish().await,
-                    _ => {
-                        self.log(
-                            MODULE,
-                            Info,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.log(
-                    MODULE,
-                    Info,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_PUBLISH => self.handle_cmd_publish().await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
@@ -9,7 +9,10 @@ use tokio::{
 use unicode_width::UnicodeWidthChar;
 use uuid::Uuid;
 
-use crate::messages::{ACTION_ADD, ACTION_INIT, ACTION_SHOW, Cmd, Data, Msg};
+use crate::messages::{
+    ACTION_ADD, ACTION_CHECK, ACTION_DONE, ACTION_INIT, ACTION_LIST, ACTION_SHOW, ACTION_UNDONE,
+    ActionSpec, Command, CommandError, Data, Event, Msg, QueryResult, Reply,
+};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{self, task::Task};
 
@@ -17,9 +20,24 @@ const MODULE: &str = "todos";
 const ONCE: &str = "once";
 const DAILY: &str = "daily";
 const WEEKDAYS: &str = "weekdays";
+// the time of a once task, e.g. 2026/10/16-08:00
+const ONCE_TIME_FORMAT: &str = "%Y/%m/%d-%H:%M";
 const THREE_DAYS: u64 = 3;
 const CHECK_INTERVAL: u64 = 60;
 
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_INIT, "", "Start the reminder checks"),
+    ActionSpec::new(
+        ACTION_ADD,
+        "task <task_name> <frequency> <task_time> [reminder <task_reminder>]",
+        "Add a task, frequency: once/daily/weekdays",
+    ),
+    ActionSpec::new(ACTION_SHOW, "", "List the tasks"),
+    ActionSpec::new(ACTION_DONE, "<index>", "Mark a task done"),
+    ActionSpec::new(ACTION_UNDONE, "<index>", "Mark a task not done"),
+    ActionSpec::new(ACTION_CHECK, "", "Check reminders and due tasks now"),
+];
+
 #[derive(Debug)]
 struct TodoTask {
     id: Uuid,
@@ -47,27 +65,27 @@ impl TodoTask {
 
         match self.frequency.as_str() {
             ONCE => {
-                let scheduled_datetime =
-                    NaiveDateTime::parse_from_str(&self.time, "%Y/%m/%d-%H:%M").unwrap();
-                let scheduled_timestamp = Local
-                    .from_local_datetime(&scheduled_datetime)
-                    .unwrap()
-                    .timestamp() as u64;
-
-                tasks.push(Task {
-                    id: Uuid::new_v4(),
-                    parent: self.id,
-                    name: self.name.clone(),
-                    time: scheduled_timestamp,
-                    reminder: self.reminder,
-                    done: false,
-                    reminded: false,
-                    dued: false,
-                });
+                // checked by add; none in a gap of daylight saving
+                if let Some(scheduled_datetime) =
+                    NaiveDateTime::parse_from_str(&self.time, ONCE_TIME_FORMAT)
+                        .ok()
+                        .and_then(|datetime| Local.from_local_datetime(&datetime).earliest())
+                {
+                    tasks.push(Task {
+                        id: Uuid::new_v4(),
+                        parent: self.id,
+                        name: self.name.clone(),
+                        time: scheduled_datetime.timestamp() as u64,
+                        reminder: self.reminder,
+                        done: false,
+                        reminded: false,
+                        dued: false,
+                    });
+                }
             }
             DAILY => {
                 if let Ok(parsed_time) = chrono::NaiveTime::parse_from_str(&self.time, "%H:%M") {
-                    let now = Local::now();
+                    let now = utils::time::now();
                     for day_offset in 0..THREE_DAYS {
                         if let Some(scheduled_datetime) = Local
                             .with_ymd_and_hms(
@@ -163,29 +181,14 @@ impl PluginUnit {
         let msg_tx_clone = self.msg_tx.clone();
         tokio::spawn(async move {
             // do it first time
-            let msg = Msg {
-                ts: utils::time::ts(),
-                module: MODULE.to_string(),
-                data: Data::Cmd(Cmd {
-                    cmd: format!("p {MODULE} check"),
-                }),
-            };
-            let _ = &msg_tx_clone.send(msg).await;
+            utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, ACTION_CHECK)).await;
 
             loop {
                 select! {
                     _ = sleep(Duration::from_secs(CHECK_INTERVAL)) => {
-                        let msg = Msg {
-                            ts: utils::time::ts(),
-                            module: MODULE.to_string(),
-                            data: Data::Cmd(Cmd {
-                                cmd: format!("p {MODULE} check", ),
-                            })
-                        };
-                        let _ = &msg_tx_clone.send(msg).await;
+                        utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, ACTION_CHECK)).await;
                     }
                     _ = shutdown_rx.recv() => {
-                        println!("Shutdown signal received. Exiting task.");
                         break;
                     }
                 }
@@ -196,101 +199,54 @@ impl PluginUnit {
             .await;
     }
 
-    async fn handle_cmd_init(&mut self, shutdown_rx: broadcast::Receiver<()>) {
+    async fn handle_cmd_init(&mut self) -> Result<(), CommandError> {
+        let shutdown_rx = self.shutdown_tx.subscribe();
         self.start_todo(shutdown_rx).await;
         self.info(MODULE, format!("[{MODULE}] init")).await;
+
+        Ok(())
     }
 
-    async fn update_infos(&self, task: &Task) {
-        // update infos
-        self.cmd(
-            MODULE,
-            format!(
-                "p infos {MODULE} update {} {} {} {} {} {} {} {}",
-                task.id,
-                task.parent,
-                task.name,
-                task.time,
-                task.done,
-                task.dued,
-                task.reminder,
-                task.reminded
-            ),
-        )
-        .await;
+    async fn emit_updated(&self, task: &Task) {
+        self.emit(MODULE, Event::TodoUpdated { task: task.clone() })
+            .await;
     }
 
-    async fn handle_cmd_add(&mut self, cmd_parts: &[String]) {
+    async fn handle_cmd_add(&mut self, cmd: &Command) -> Result<(), CommandError> {
         let mut todo_task = TodoTask::default();
 
         // task
-        if let Some(item) = cmd_parts.get(3) {
-            if item != "task" {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] No `task` (got `{item}`) for add."),
-                )
-                .await;
-                return;
-            }
-            if let Some(name) = cmd_parts.get(4) {
-                todo_task.name = name.clone();
-            } else {
-                self.warn(MODULE, format!("[{MODULE}] No `task name` for add."))
-                    .await;
-            }
-        } else {
-            self.warn(MODULE, format!("[{MODULE}] No `task` for add."))
-                .await;
-            return;
-        }
+        expect_keyword(cmd, 0, "task")?;
+        todo_task.name = cmd.get_str(1, "task_name")?.to_string();
 
         // freq
-        if let Some(item) = cmd_parts.get(5) {
-            match item.as_str() {
-                ONCE => todo_task.frequency = ONCE.into(),
-                DAILY => todo_task.frequency = DAILY.into(),
-                WEEKDAYS => todo_task.frequency = WEEKDAYS.into(),
-                _ => {
-                    self.warn(
-                        MODULE,
-                        format!("[{MODULE}] Invalid frequency (got `{item}`) for add."),
-                    )
-                    .await;
-                    return;
-                }
-            }
-            if let Some(time) = cmd_parts.get(6) {
-                todo_task.time = time.clone();
-            } else {
-                self.warn(MODULE, format!("[{MODULE}] No `task time` for add."))
-                    .await;
+        todo_task.frequency = match cmd.get_str(2, "frequency")? {
+            ONCE => ONCE.into(),
+            DAILY => DAILY.into(),
+            WEEKDAYS => WEEKDAYS.into(),
+            item => {
+                return Err(CommandError::InvalidArg {
+                    name: "frequency".to_string(),
+                    value: item.to_string(),
+                    expected: format!("{ONCE}/{DAILY}/{WEEKDAYS}"),
+                });
             }
-        } else {
-            self.warn(
-                MODULE,
-                format!("[{MODULE}] No `{ONCE}/{DAILY}/{WEEKDAYS}` for add."),
-            )
-            .await;
-            return;
+        };
+        todo_task.time = cmd.get_str(3, "task_time")?.to_string();
+        if todo_task.frequency == ONCE
+            && NaiveDateTime::parse_from_str(&todo_task.time, ONCE_TIME_FORMAT).is_err()
+        {
+            return Err(CommandError::InvalidArg {
+                name: "task_time".to_string(),
+                value: todo_task.time,
+                expected: "yyyy/mm/dd-hh:mm".to_string(),
+            });
         }
 
         // reminder
-        if let Some(item) = cmd_parts.get(7) {
-            if item != "reminder" {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] No `reminder` (got `{item}`) for add."),
-                )
-                .await;
-                return;
-            }
-            if let Some(reminder) = cmd_parts.get(8) {
-                todo_task.reminder = reminder.parse().unwrap_or(0);
-            } else {
-                self.warn(MODULE, format!("[{MODULE}] No `task reminder` for add."))
-                    .await;
-            }
+        if cmd.args.get(4).is_some() {
+            expect_keyword(cmd, 4, "reminder")?;
+            todo_task.reminder = cmd.get(5, "task_reminder")?;
         } else {
             self.warn(MODULE, format!("[{MODULE}] No `task reminder` for add."))
                 .await;
@@ -309,15 +265,17 @@ impl PluginUnit {
 
         let tasks = todo_task.get_tasks().await;
         for task in &tasks {
-            self.update_infos(task).await;
+            self.emit_updated(task).await;
         }
 
         self.todos.push(todo_task);
         self.tasks.extend(tasks);
         self.tasks.sort_by_key(|e| e.time);
+
+        Ok(())
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(
             MODULE,
             format!(
@@ -328,7 +286,7 @@ impl PluginUnit {
         .await;
         for (idx, todo) in self.todos.iter().enumerate() {
             let name_width: usize = todo.name.chars().map(|c| c.width().unwrap_or(0)).sum();
-            let name_space = " ".repeat(12 - name_width);
+            let name_space = " ".repeat(12_usize.saturating_sub(name_width));
 
             self.info(
                 MODULE,
@@ -352,7 +310,7 @@ impl PluginUnit {
         .await;
         for (idx, task) in self.tasks.iter().enumerate() {
             let name_width: usize = task.name.chars().map(|c| c.width().unwrap_or(0)).sum();
-            let name_space = " ".repeat(12 - name_width);
+            let name_space = " ".repeat(12_usize.saturating_sub(name_width));
             let done_str = if task.done { "✓" } else { "✗" };
             let reminder_str = if task.reminded { "✓" } else { "✗" };
             let dued_str = if task.dued { "✓" } else { "✗" };
@@ -369,43 +327,32 @@ impl PluginUnit {
             )
             .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_done(&mut self, cmd_parts: &[String], done: bool) {
-        let mut updated = Vec::new();
+    async fn handle_cmd_done(&mut self, cmd: &Command, done: bool) -> Result<(), CommandError> {
+        let index = cmd.get::<usize>(0, "index")?;
 
-        if let Some(index) = cmd_parts.get(3) {
-            if let Ok(index) = index.parse::<usize>() {
-                let mut name = String::new();
-                if let Some(task) = self.tasks.get_mut(index) {
-                    task.done = done;
-                    name = task.name.clone();
-                    updated.push(task.id);
-                } else {
-                    self.warn(MODULE, format!("[{MODULE}] Task not found: {index}"))
-                        .await;
-                }
-                if !name.is_empty() {
-                    self.info(MODULE, format!("[{MODULE}] Marked task as done: {}", name))
-                        .await;
-                }
-            } else {
-                self.warn(MODULE, format!("[{MODULE}] Invalid task index: {index}"))
-                    .await;
-            }
-        } else {
-            self.warn(MODULE, format!("[{MODULE}] No task index provided."))
+        let Some(task) = self.tasks.get_mut(index) else {
+            self.warn(MODULE, format!("[{MODULE}] Task not found: {index}"))
                 .await;
-        }
+            return Ok(());
+        };
+        task.done = done;
+        let (id, name) = (task.id, task.name.clone());
 
-        for updated_info in updated {
-            if let Some(task) = self.tasks.iter().find(|t| t.id == updated_info) {
-                self.update_infos(task).await;
-            }
+        let state = if done { "done" } else { "not done" };
+        self.info(MODULE, format!("[{MODULE}] Marked task as {state}: {name}"))
+            .await;
+        if let Some(task) = self.tasks.iter().find(|t| t.id == id) {
+            self.emit_updated(task).await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_check(&mut self) {
+    async fn handle_cmd_check(&mut self) -> Result<(), CommandError> {
         let mut infos = Vec::new();
         let mut updated = Vec::new();
 
@@ -441,13 +388,15 @@ impl PluginUnit {
 
         for updated_info in updated {
             if let Some(task) = self.tasks.iter().find(|t| t.id == updated_info) {
-                self.update_infos(task).await;
+                self.emit_updated(task).await;
             }
         }
 
         for info in infos {
             self.info(MODULE, info).await;
         }
+
+        Ok(())
     }
 }
 
@@ -461,38 +410,46 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
+    async fn query(&mut self, cmd: &Command) -> QueryResult {
+        match cmd.action.as_str() {
+            ACTION_LIST => Ok(Reply::Tasks(self.tasks.clone())),
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        }
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => {
-                        let shutdown_rx = self.shutdown_tx.subscribe();
-                        self.handle_cmd_init(shutdown_rx).await;
-                    }
-                    ACTION_ADD => self.handle_cmd_add(&cmd_parts).await,
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    "done" => self.handle_cmd_done(&cmd_parts, true).await,
-                    "undone" => self.handle_cmd_done(&cmd_parts, false).await,
-                    "check" => self.handle_cmd_check().await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init().await,
+                ACTION_ADD => self.handle_cmd_add(cmd).await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                ACTION_DONE => self.handle_cmd_done(cmd, true).await,
+                ACTION_UNDONE => self.handle_cmd_done(cmd, false).await,
+                ACTION_CHECK => self.handle_cmd_check().await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
 }
+
+fn expect_keyword(cmd: &Command, idx: usize, keyword: &str) -> Result<(), CommandError> {
+    match cmd.get_str(idx, keyword)? {
+        item if item == keyword => Ok(()),
+        item => Err(CommandError::InvalidArg {
+            name: keyword.to_string(),
+            value: item.to_string(),
+            expected: format!("`{keyword}`"),
+        }),
+    }
+}
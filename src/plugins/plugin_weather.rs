@@ -1,16 +1,31 @@
 use async_trait::async_trait;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
 
-use crate::messages::{ACTION_ADD, ACTION_INIT, ACTION_SHOW, Cmd, Data, Msg};
+use crate::cfg::{self, Cfg, CityCfg, WeatherCfg};
+use crate::messages::{
+    ACTION_ADD, ACTION_INIT, ACTION_LIST, ACTION_SHOW, ActionSpec, Command, CommandError, Data,
+    EVENT_WEATHER_UPDATED, Event, Msg, QueryResult, Reply,
+};
 use crate::plugins::plugins_main::{self, Plugin};
 use crate::utils::{
     self,
-    weather::{self, City, Weather, WeatherDaily},
+    weather::{self, City},
 };
 
 const MODULE: &str = "weather";
-const WEATHER_POLLING: u64 = 15 * 60; // 15 mins
+
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(
+        ACTION_INIT,
+        "",
+        "Add the cities in cfg, fetch every weather.polling secs",
+    ),
+    ActionSpec::new(ACTION_SHOW, "", "List the cities and temperatures"),
+    ActionSpec::new("update", "", "Fetch the weather now"),
+    ActionSpec::new(ACTION_ADD, "<name> <latitude> <longitude>", "Add a city"),
+];
 
 #[derive(Debug)]
 pub struct PluginUnit {
@@ -18,8 +33,10 @@ pub struct PluginUnit {
     msg_tx: Sender<Msg>,
     shutdown_tx: broadcast::Sender<()>,
     inited: bool,
-    gui_panel: String,
     cities: Vec<City>,
+    // weather.cities and weather.polling as of init or the last reload
+    cfg: WeatherCfg,
+    polling_task: Option<JoinHandle<()>>,
 }
 
 impl PluginUnit {
@@ -31,116 +48,87 @@ impl PluginUnit {
             msg_tx,
             shutdown_tx,
             inited: false,
-            gui_panel: "infos".to_string(),
             cities: vec![],
+            cfg: WeatherCfg::default(),
+            polling_task: None,
         }
     }
 
-    async fn handle_cmd_update(&mut self) {
+    async fn handle_cmd_update(&mut self) -> Result<(), CommandError> {
         if !self.inited {
-            return;
+            return Ok(());
         }
 
         let cities = self.cities.clone();
         let msg_tx_clone = self.msg_tx.clone();
-        let gui_panel_clone = self.gui_panel.clone();
         tokio::spawn(async move {
-            for city in &cities {
+            for city in cities {
                 let weather = weather::get_weather(city.latitude, city.longitude).await;
                 if let Ok(weather) = weather {
-                    utils::msg::cmd(
-                        &msg_tx_clone,
-                        MODULE,
-                        format!(
-                            "p weather update_item summary {} {} {} {}",
-                            city.name, weather.time, weather.temperature, weather.weathercode
-                        ),
-                    )
-                    .await;
-
-                    utils::msg::cmd(
-                        &msg_tx_clone,
-                        MODULE,
-                        format!(
-                            "p {gui_panel_clone} weather update_item summary {} {} {} {}",
-                            city.name, weather.time, weather.temperature, weather.weathercode
-                        ),
-                    )
-                    .await;
-
-                    for (idx, daily) in weather.daily.iter().enumerate() {
-                        utils::msg::cmd(
-                            &msg_tx_clone,
-                            MODULE,
-                            format!(
-                                "p weather update_item daily {} {} {} {} {} {} {}",
-                                city.name,
-                                idx,
-                                daily.time,
-                                daily.temperature_2m_max,
-                                daily.temperature_2m_min,
-                                daily.precipitation_probability_max,
-                                daily.weather_code,
-                            ),
-                        )
-                        .await;
-
-                        utils::msg::cmd(
-                            &msg_tx_clone,
-                            MODULE,
-                            format!(
-                                "p {gui_panel_clone} weather update_item daily {} {} {} {} {} {} {}",
-                                city.name,
-                                idx,
-                                daily.time,
-                                daily.temperature_2m_max,
-                                daily.temperature_2m_min,
-                                daily.precipitation_probability_max,
-                                daily.weather_code,
-                            )
-                        )
-                        .await;
-                    }
+                    let city = City {
+                        weather: Some(weather),
+                        ..city
+                    };
+                    utils::msg::emit(&msg_tx_clone, MODULE, Event::WeatherUpdated { city }).await;
                 }
             }
         });
+
+        Ok(())
     }
 
-    async fn handle_cmd_init(&mut self) {
+    async fn handle_cmd_init(&mut self) -> Result<(), CommandError> {
         if self.inited {
-            return;
+            return Ok(());
         }
         self.inited = true;
+        self.cfg = cfg::get().weather;
+
+        self.add_cfg_cities().await;
+        self.command(MODULE, Command::new(MODULE, "update")).await;
+        self.start_polling();
+
+        self.info(MODULE, format!("[{MODULE}] init")).await;
+
+        Ok(())
+    }
+
+    async fn add_cfg_cities(&self) {
+        for city in &self.cfg.cities {
+            self.command(
+                MODULE,
+                Command::new(MODULE, ACTION_ADD)
+                    .arg(&city.name)
+                    .arg(city.latitude)
+                    .arg(city.longitude),
+            )
+            .await;
+        }
+    }
 
-        self.cmd(MODULE, "p weather update".to_string()).await;
+    fn start_polling(&mut self) {
+        if let Some(task) = self.polling_task.take() {
+            task.abort();
+        }
 
         let mut shutdown_rx = self.shutdown_tx.subscribe();
         let msg_tx_clone = self.msg_tx.clone();
-        tokio::spawn(async move {
+        let polling = self.cfg.polling;
+        self.polling_task = Some(tokio::spawn(async move {
             loop {
                 tokio::select! {
                     _ = shutdown_rx.recv() => {
                         break;
                     }
-                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(WEATHER_POLLING)) => {
-                        let msg = Msg {
-                            ts: utils::time::ts(),
-                            module: MODULE.to_string(),
-                            data: Data::Cmd(Cmd {
-                                cmd: "p weather update".to_string(),
-                            }),
-                        };
-
-                        let _ = msg_tx_clone.send(msg).await;
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(polling)) => {
+                        utils::msg::command(&msg_tx_clone, MODULE, Command::new(MODULE, "update")).await;
                     }
                 }
             }
-        });
-
-        self.info(MODULE, format!("[{MODULE}] init")).await;
+        }));
     }
 
-    async fn handle_cmd_show(&mut self) {
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
         self.info(MODULE, format!("[{MODULE}] Inited: {:?}", self.inited))
             .await;
         self.info(MODULE, format!("{:<12} {:<7}", "Name", "Temp"))
@@ -154,118 +142,43 @@ impl PluginUnit {
             self.info(MODULE, format!("{:<12} {temperature:<7}", city.name,))
                 .await;
         }
+
+        Ok(())
     }
 
-    async fn handle_cmd_add(&mut self, cmd_parts: &[String]) {
-        if let (Some(name), Some(latitude), Some(longitude)) =
-            (cmd_parts.get(3), cmd_parts.get(4), cmd_parts.get(5))
-        {
-            if !self.cities.iter().any(|city| city.name == *name) {
-                self.cities.push(City {
-                    name: name.to_string(),
-                    latitude: latitude.parse::<f32>().unwrap(),
-                    longitude: longitude.parse::<f32>().unwrap(),
-                    weather: None,
-                });
-
-                self.cmd(
-                    MODULE,
-                    format!(
-                        "p {} weather add {name} {latitude} {longitude}",
-                        self.gui_panel
-                    ),
-                )
-                .await;
-            }
+    async fn handle_cmd_add(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+        let latitude = cmd.get::<f32>(1, "latitude")?;
+        let longitude = cmd.get::<f32>(2, "longitude")?;
 
-            self.info(
-                MODULE,
-                format!("[{MODULE}] Add: {name} {latitude} {longitude}"),
-            )
-            .await;
+        if !self.cities.iter().any(|city| city.name == *name) {
+            let city = City {
+                name: name.to_string(),
+                latitude,
+                longitude,
+                weather: None,
+            };
+            self.cities.push(city.clone());
+
+            self.emit(MODULE, Event::WeatherUpdated { city }).await;
         }
+
+        self.info(
+            MODULE,
+            format!("[{MODULE}] Add: {name} {latitude} {longitude}"),
+        )
+        .await;
+
+        Ok(())
     }
 
-    async fn handle_cmd_update_item(&mut self, cmd_parts: &[String]) {
-        if let Some(class) = cmd_parts.get(3) {
-            match class.as_str() {
-                "summary" => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (Some(name), Some(time), Some(temperature), Some(weathercode)) = (
-                        cmd_parts.get(4),
-                        cmd_parts.get(5),
-                        cmd_parts.get(6),
-                        cmd_parts.get(7),
-                    ) {
-                        if let Some(city) = self.cities.iter_mut().find(|city| city.name == *name) {
-                            let time = time.to_string();
-                            let temperature = temperature.parse::<f32>().unwrap();
-                            let weathercode = weathercode.parse::<u8>().unwrap();
-
-                            if let Some(weather) = city.weather.as_mut() {
-                                weather.time = time;
-                                weather.temperature = temperature;
-                                weather.weathercode = weathercode;
-                            } else {
-                                city.weather = Some(Weather {
-                                    time,
-                                    temperature,
-                                    weathercode,
-                                    daily: vec![],
-                                });
-                            }
-                        }
-                    }
-                }
-                "daily" => {
-                    #[allow(clippy::collapsible_if)]
-                    if let (
-                        Some(name),
-                        Some(idx),
-                        Some(time),
-                        Some(temperature_2m_max),
-                        Some(temperature_2m_min),
-                        Some(precipitation_probability_max),
-                        Some(weather_code),
-                    ) = (
-                        cmd_parts.get(4),
-                        cmd_parts.get(5),
-                        cmd_parts.get(6),
-                        cmd_parts.get(7),
-                        cmd_parts.get(8),
-                        cmd_parts.get(9),
-                        cmd_parts.get(10),
-                    ) {
-                        if let Some(city) = self.cities.iter_mut().find(|city| city.name == *name) {
-                            let idx = idx.parse::<usize>().unwrap();
-                            let daily = WeatherDaily {
-                                time: time.to_string(),
-                                temperature_2m_max: temperature_2m_max.parse::<f32>().unwrap(),
-                                temperature_2m_min: temperature_2m_min.parse::<f32>().unwrap(),
-                                precipitation_probability_max: precipitation_probability_max
-                                    .parse::<u8>()
-                                    .unwrap(),
-                                weather_code: weather_code.parse::<u8>().unwrap(),
-                            };
-
-                            if let Some(weather) = city.weather.as_mut() {
-                                if weather.daily.len() <= idx {
-                                    weather.daily.resize_with(idx + 1, || WeatherDaily {
-                                        time: String::new(),
-                                        temperature_2m_max: 0.0,
-                                        temperature_2m_min: 0.0,
-                                        precipitation_probability_max: 0,
-                                        weather_code: 0,
-                                    });
-                                }
-
-                                weather.daily[idx] = daily;
-                            }
-                        }
-                    }
-                }
-                _ => (),
-            }
+    async fn handle_event_weather_updated(&mut self, updated: &City) {
+        if let Some(city) = self
+            .cities
+            .iter_mut()
+            .find(|city| city.name == updated.name)
+        {
+            city.weather = updated.weather.clone();
         }
     }
 }
@@ -280,33 +193,76 @@ impl plugins_main::Plugin for PluginUnit {
         let _ = self.msg_tx.send(msg).await;
     }
 
+    fn actions(&self) -> &'static [ActionSpec] {
+        ACTIONS
+    }
+
+    fn replays_init(&self) -> bool {
+        true
+    }
+
+    fn subscriptions(&self) -> &[&str] {
+        &[EVENT_WEATHER_UPDATED]
+    }
+
+    async fn handle_event(&mut self, event: &Event) {
+        if let Event::WeatherUpdated { city } = event {
+            self.handle_event_weather_updated(city).await;
+        }
+    }
+
+    async fn query(&mut self, cmd: &Command) -> QueryResult {
+        match cmd.action.as_str() {
+            ACTION_LIST => Ok(Reply::Cities(self.cities.clone())),
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        }
+    }
+
+    async fn reconfigure(&mut self, cfg: &Cfg) {
+        if !self.inited || self.cfg == cfg.weather {
+            return;
+        }
+        let old = std::mem::replace(&mut self.cfg, cfg.weather.clone());
+
+        if old.polling != self.cfg.polling {
+            self.start_polling();
+        }
+        if old.cities != self.cfg.cities {
+            // moved or gone from the cfg, added again below when moved
+            self.cities.retain(|city| {
+                !old.cities.iter().any(|old_city| old_city.name == city.name)
+                    || self.cfg.cities.contains(&CityCfg {
+                        name: city.name.clone(),
+                        latitude: city.latitude,
+                        longitude: city.longitude,
+                    })
+            });
+            self.add_cfg_cities().await;
+            self.command(MODULE, Command::new(MODULE, "update")).await;
+        }
+
+        self.info(
+            MODULE,
+            format!(
+                "[{MODULE}] Reconfigured: {} cities, every {} secs",
+                self.cfg.cities.len(),
+                self.cfg.polling
+            ),
+        )
+        .await;
+    }
+
     async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(action) = cmd_parts.get(2) {
-                match action.as_str() {
-                    ACTION_INIT => self.handle_cmd_init().await,
-                    ACTION_SHOW => self.handle_cmd_show().await,
-                    "update" => self.handle_cmd_update().await,
-                    "update_item" => self.handle_cmd_update_item(&cmd_parts).await,
-                    ACTION_ADD => self.handle_cmd_add(&cmd_parts).await,
-                    _ => {
-                        self.warn(
-                            MODULE,
-                            format!(
-                                "[{MODULE}] Unknown action ({action}) for cmd `{}`.",
-                                cmd.cmd
-                            ),
-                        )
-                        .await;
-                    }
-                }
-            } else {
-                self.warn(
-                    MODULE,
-                    format!("[{MODULE}] Missing action for cmd `{}`.", cmd.cmd),
-                )
-                .await;
+        if let Data::Command(cmd) = &msg.data {
+            let result = match cmd.action.as_str() {
+                ACTION_INIT => self.handle_cmd_init().await,
+                ACTION_SHOW => self.handle_cmd_show().await,
+                "update" => self.handle_cmd_update().await,
+                ACTION_ADD => self.handle_cmd_add(cmd).await,
+                action => Err(CommandError::UnknownAction(action.to_string())),
+            };
+            if let Err(e) = result {
+                self.cmd_error(MODULE, cmd, e).await;
             }
         }
     }
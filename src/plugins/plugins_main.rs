@@ -1,16 +1,55 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use async_trait::async_trait;
-use log::Level::{Info, Warn};
-use tokio::sync::broadcast;
-
-use crate::messages::{ACTION_SHOW, Cmd, Data, Log, Msg};
-use crate::plugins::{
-    plugin_cli, plugin_devices, plugin_infos, plugin_log, plugin_monitor, plugin_mqtt,
-    plugin_music, plugin_nas, plugin_panels, plugin_runtipi, plugin_scripts, plugin_system,
-    plugin_todos, plugin_weather,
+use futures_util::FutureExt;
+use futures_util::future::BoxFuture;
+use log::Level::{Debug, Error, Info, Trace, Warn};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::cfg::Cfg;
+use crate::messages::{
+    ACTION_DISABLE, ACTION_ENABLE, ACTION_HELP, ACTION_INIT, ACTION_LIST, ACTION_SHOW,
+    ACTION_STATS, ACTION_STATUS, ActionSpec, Cmd, Command, CommandError, Data, EVENT_CFG_CHANGED,
+    Event, Log, Msg, QueryResult, Reply, topic_matches,
 };
-use crate::utils;
+use crate::plugins::registry::{self, REQUIRED, Registration};
+use crate::utils::{
+    self,
+    stats::{self, Histogram, MsgCount, PluginLatency, QueueStats, Stats},
+};
+
+pub const MODULE: &str = "plugins";
+const HELP_WIDTH: usize = 44;
 
-const MODULE: &str = "plugins";
+pub const ACTIONS: &[ActionSpec] = &[
+    ActionSpec::new(ACTION_SHOW, "", "List the plugins and their subscriptions"),
+    ActionSpec::new(ACTION_STATUS, "", "Show running state and crash counts"),
+    ActionSpec::new(
+        ACTION_STATS,
+        "",
+        "Show queue depth, message counts and handler latency",
+    ),
+    ActionSpec::new(ACTION_ENABLE, "<name>", "Start a registered plugin"),
+    ActionSpec::new(ACTION_DISABLE, "<name>", "Shut a plugin down"),
+    ActionSpec::new(ACTION_HELP, "", "Show the actions of every plugin"),
+];
+
+tokio::task_local! {
+    // set while a plugin handles a message
+    static HANDLING: ();
+}
+
+// a panic here is caught and the plugin restarted. the tasks plugins spawn and
+// the router are not supervised
+pub fn handling() -> bool {
+    HANDLING.try_with(|_| ()).is_ok()
+}
 
 #[async_trait]
 pub trait Plugin {
@@ -24,6 +63,34 @@ pub trait Plugin {
         panic!("send: Unhandled msg ({msg:?})")
     }
 
+    // the actions `handle_cmd` takes, for `p <plugin> help` and for
+    // validating commands before they reach the plugin
+    fn actions(&self) -> &'static [ActionSpec] {
+        &[]
+    }
+
+    // event topics to receive, e.g. `file.modified` or `device.*`
+    fn subscriptions(&self) -> &[&str] {
+        &[]
+    }
+
+    async fn handle_event(&mut self, _event: &Event) {}
+
+    // replay the last `init` on the instance replacing a panicked one. only
+    // for an init that is fine to run twice, not e.g. the init script
+    fn replays_init(&self) -> bool {
+        false
+    }
+
+    // cfg.json changed on disk and passed validation, `cfg::get()` returns
+    // the same. pick up what is read only once, e.g. a broker or an interval
+    async fn reconfigure(&mut self, _cfg: &Cfg) {}
+
+    // answer with structured data, e.g. `devices list`
+    async fn query(&mut self, cmd: &Command) -> QueryResult {
+        Err(CommandError::UnknownAction(cmd.action.clone()))
+    }
+
     async fn log(&self, module: &str, level: log::Level, msg: String) {
         let msg = Msg {
             ts: utils::time::ts(),
@@ -33,14 +100,26 @@ pub trait Plugin {
         let _ = self.send(msg).await;
     }
 
-    async fn info(&self, module: &str, msg: String) {
-        let _ = self.log(module, Info, msg).await;
+    async fn error(&self, module: &str, msg: String) {
+        let _ = self.log(module, Error, msg).await;
     }
 
     async fn warn(&self, module: &str, msg: String) {
         let _ = self.log(module, Warn, msg).await;
     }
 
+    async fn info(&self, module: &str, msg: String) {
+        let _ = self.log(module, Info, msg).await;
+    }
+
+    async fn debug(&self, module: &str, msg: String) {
+        let _ = self.log(module, Debug, msg).await;
+    }
+
+    async fn trace(&self, module: &str, msg: String) {
+        let _ = self.log(module, Trace, msg).await;
+    }
+
     async fn cmd(&self, module: &str, cmd: String) {
         let msg = Msg {
             ts: utils::time::ts(),
@@ -49,52 +128,315 @@ pub trait Plugin {
         };
         let _ = self.send(msg).await;
     }
+
+    async fn command(&self, module: &str, cmd: Command) {
+        let msg = Msg {
+            ts: utils::time::ts(),
+            module: module.to_string(),
+            data: Data::Command(cmd),
+        };
+        let _ = self.send(msg).await;
+    }
+
+    async fn emit(&self, module: &str, event: Event) {
+        let msg = Msg {
+            ts: utils::time::ts(),
+            module: module.to_string(),
+            data: Data::Event(event),
+        };
+        let _ = self.send(msg).await;
+    }
+
+    // last call before the instance is dropped, on `p plugins disable` or
+    // app shutdown: flush state, say goodbye. the shutdown channel handed to
+    // `new` fires right after to stop the tasks it spawned
+    async fn shutdown(&mut self) {}
+
+    async fn cmd_error(&self, module: &str, cmd: &Command, e: CommandError) {
+        self.warn(module, format!("[{module}] {e} for cmd `{cmd}`."))
+            .await;
+    }
+}
+
+pub type PluginBox = Box<dyn Plugin + Send + Sync>;
+
+// builds a fresh instance, used at start, on enable and again after a panic
+pub type PluginFactory = fn(Sender<Msg>, broadcast::Sender<()>) -> BoxFuture<'static, PluginBox>;
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PluginState {
+    Running,
+    Restarting,
+}
+
+#[derive(Debug)]
+struct PluginStatus {
+    state: PluginState,
+    crashes: u32,
+    last_panic: Option<String>,
+    last_crash: Option<Instant>,
+    backoff: Duration,
+    latency: Histogram,
+}
+
+impl PluginStatus {
+    fn new() -> Self {
+        Self {
+            state: PluginState::Running,
+            crashes: 0,
+            last_panic: None,
+            last_crash: None,
+            backoff: BACKOFF_MIN,
+            latency: Histogram::default(),
+        }
+    }
+
+    // double the delay on back-to-back crashes, start over once it has been stable
+    fn crashed(&mut self, reason: String) -> Duration {
+        let now = Instant::now();
+        self.backoff = match self.last_crash {
+            Some(last) if now.duration_since(last) < self.backoff + BACKOFF_MAX => {
+                (self.backoff * 2).min(BACKOFF_MAX)
+            }
+            _ => BACKOFF_MIN,
+        };
+        self.state = PluginState::Restarting;
+        self.crashes += 1;
+        self.last_panic = Some(reason);
+        self.last_crash = Some(now);
+        self.backoff
+    }
+}
+
+// a plugin running on its own task, fed in order through its mailbox
+struct PluginHandle {
+    name: String,
+    actions: &'static [ActionSpec],
+    subscriptions: Vec<String>,
+    mailbox: mpsc::UnboundedSender<Msg>,
+    status: Arc<Mutex<PluginStatus>>,
+    // taken by the ordered shutdown
+    stop_tx: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl PluginHandle {
+    async fn spawn(factory: PluginFactory, msg_tx: Sender<Msg>) -> Self {
+        let (instance_tx, _) = broadcast::channel::<()>(1);
+        let plugin = factory(msg_tx.clone(), instance_tx.clone()).await;
+        let name = plugin.name().to_string();
+        let actions = plugin.actions();
+        let subscriptions = plugin
+            .subscriptions()
+            .iter()
+            .map(|topic| topic.to_string())
+            .collect();
+        let (mailbox, mailbox_rx) = mpsc::unbounded_channel::<Msg>();
+        let status = Arc::new(Mutex::new(PluginStatus::new()));
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+        let task = tokio::spawn(supervise(
+            plugin,
+            instance_tx,
+            factory,
+            msg_tx,
+            mailbox_rx,
+            stop_rx,
+            status.clone(),
+        ));
+
+        Self {
+            name,
+            actions,
+            subscriptions,
+            mailbox,
+            status,
+            stop_tx: Some(stop_tx),
+            task: Some(task),
+        }
+    }
+
+    fn subscribed(&self, topic: &str) -> bool {
+        self.subscriptions
+            .iter()
+            .any(|pattern| topic_matches(pattern, topic))
+    }
+}
+
+// a panic only takes down the current instance: queued messages wait in the
+// mailbox while it is rebuilt, and the last `init` is replayed on the new one
+// when the plugin `replays_init`.
+// the loop ends once the mailbox is idle after a stop request, or when the
+// handle is dropped, i.e. the plugin was disabled.
+//
+// each instance gets its own shutdown channel for the tasks it spawns, fired
+// after `Plugin::shutdown` or when the instance is replaced after a panic
+async fn supervise(
+    mut plugin: PluginBox,
+    mut instance_tx: broadcast::Sender<()>,
+    factory: PluginFactory,
+    msg_tx: Sender<Msg>,
+    mut mailbox_rx: mpsc::UnboundedReceiver<Msg>,
+    mut stop_rx: oneshot::Receiver<()>,
+    status: Arc<Mutex<PluginStatus>>,
+) {
+    let name = plugin.name().to_string();
+    let mut init: Option<Command> = None;
+    let mut replay: Option<Msg> = None;
+
+    loop {
+        let msg = match replay.take() {
+            Some(msg) => msg,
+            None => tokio::select! {
+                biased;
+                msg = mailbox_rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = &mut stop_rx => break,
+            },
+        };
+
+        #[allow(clippy::collapsible_if)]
+        if let Data::Command(cmd) = &msg.data {
+            if cmd.action == ACTION_INIT && plugin.replays_init() {
+                init = Some(cmd.clone());
+            }
+        }
+
+        let start = Instant::now();
+        let result = AssertUnwindSafe(HANDLING.scope((), dispatch(&mut plugin, msg)))
+            .catch_unwind()
+            .await;
+        status.lock().unwrap().latency.record(start.elapsed());
+
+        let Err(payload) = result else {
+            continue;
+        };
+
+        let reason = panic_message(payload.as_ref());
+        let (crashes, backoff) = {
+            let mut status = status.lock().unwrap();
+            let backoff = status.crashed(reason.clone());
+            (status.crashes, backoff)
+        };
+        utils::msg::log_error(
+            &msg_tx,
+            MODULE,
+            format!(
+                "[{MODULE}] `{name}` panicked: {reason}. Restart in {}s (crashes: {crashes}).",
+                backoff.as_secs()
+            ),
+        )
+        .await;
+
+        // stop the tasks the broken instance left behind
+        let _ = instance_tx.send(());
+
+        tokio::time::sleep(backoff).await;
+
+        (instance_tx, _) = broadcast::channel::<()>(1);
+        plugin = factory(msg_tx.clone(), instance_tx.clone()).await;
+        status.lock().unwrap().state = PluginState::Running;
+        replay = init.clone().map(|cmd| Msg {
+            ts: utils::time::ts(),
+            module: MODULE.to_string(),
+            data: Data::Command(cmd),
+        });
+        utils::msg::log_info(&msg_tx, MODULE, format!("[{MODULE}] `{name}` restarted")).await;
+    }
+
+    let _ = AssertUnwindSafe(HANDLING.scope((), plugin.shutdown()))
+        .catch_unwind()
+        .await;
+    let _ = instance_tx.send(());
+
+    utils::msg::log_info(&msg_tx, MODULE, format!("[{MODULE}] `{name}` stopped")).await;
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown panic message".to_string())
+}
+
+async fn dispatch(plugin: &mut PluginBox, msg: Msg) {
+    match msg.data {
+        Data::Command(_) => plugin.handle_cmd(&msg).await,
+        Data::Event(Event::CfgChanged { ref cfg }) => plugin.reconfigure(cfg).await,
+        Data::Event(ref event) => plugin.handle_event(event).await,
+        Data::Query(query) => {
+            let result = plugin.query(&query.cmd).await;
+            let _ = query.reply_tx.send(result);
+        }
+        Data::Log(_) | Data::Cmd(_) | Data::Tap(_) => (),
+    }
 }
 
 pub struct Plugins {
-    msg_tx: tokio::sync::mpsc::Sender<Msg>,
-    plugins: Vec<Box<dyn Plugin + Send + Sync>>,
+    msg_tx: Sender<Msg>,
+    shutdown_tx: broadcast::Sender<()>,
+    registry: Vec<Registration>,
+    plugins: Vec<PluginHandle>,
+    shutting_down: bool,
+    counts: BTreeMap<(String, String), u64>,
+    queue: QueueStats,
 }
 
 impl Plugins {
+    // `selected` picks what starts on this node, all when None
     pub async fn new(
-        msg_tx: tokio::sync::mpsc::Sender<Msg>,
+        msg_tx: Sender<Msg>,
         shutdown_tx: broadcast::Sender<()>,
+        selected: Option<Vec<String>>,
     ) -> Self {
-        let plugins = vec![
-            Box::new(plugin_log::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_scripts::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_cli::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_system::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_panels::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_mqtt::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_devices::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_infos::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_nas::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_monitor::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_weather::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_music::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_runtipi::PluginUnit::new(msg_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-            Box::new(plugin_todos::PluginUnit::new(msg_tx.clone(), shutdown_tx.clone()).await)
-                as Box<dyn Plugin + Send + Sync>,
-        ];
+        let mut plugins = Self {
+            msg_tx: msg_tx.clone(),
+            shutdown_tx,
+            registry: registry::registry(),
+            plugins: vec![],
+            shutting_down: false,
+            counts: BTreeMap::new(),
+            queue: QueueStats::new(msg_tx.max_capacity()),
+        };
+
+        if let Some(selected) = &selected {
+            for name in selected {
+                if plugins.get_registration(name).is_none() {
+                    plugins
+                        .warn(format!(
+                            "[{MODULE}] {} in selection.",
+                            CommandError::UnknownPlugin(name.clone())
+                        ))
+                        .await;
+                }
+            }
+        }
+
+        let names: Vec<&str> = plugins
+            .registry
+            .iter()
+            .map(|registration| registration.name)
+            .filter(|name| {
+                REQUIRED.contains(name)
+                    || selected
+                        .as_ref()
+                        .is_none_or(|selected| selected.iter().any(|s| s == name))
+            })
+            .collect();
+        for name in names {
+            plugins.start(name).await;
+        }
 
         utils::msg::log_new(&msg_tx, MODULE).await;
 
-        Self { msg_tx, plugins }
+        plugins
     }
 
     async fn log(&self, level: log::Level, msg: String) {
@@ -114,50 +456,399 @@ impl Plugins {
         self.log(log::Level::Warn, msg).await;
     }
 
-    async fn my_handle_cmd(&self, cmd_parts: &[String]) {
-        if let Some(action) = cmd_parts.get(2) {
-            #[allow(clippy::single_match)]
-            match action.as_str() {
-                ACTION_SHOW => {
-                    self.info(format!("{MODULE:<12}")).await;
-                    for plugin in &self.plugins {
-                        self.info(format!("{:<12}", plugin.name())).await;
-                    }
-                }
-                _ => (),
+    async fn start(&mut self, name: &str) {
+        if let Some(registration) = self.get_registration(name) {
+            let factory = registration.factory;
+            let handle = PluginHandle::spawn(factory, self.msg_tx.clone()).await;
+            self.plugins.push(handle);
+            self.handle_event(Msg {
+                ts: utils::time::ts(),
+                module: MODULE.to_string(),
+                data: Data::Event(Event::PluginEnabled {
+                    name: name.to_string(),
+                }),
+            });
+        }
+    }
+
+    async fn my_handle_cmd(&mut self, cmd: &Command) {
+        let result = match cmd.action.as_str() {
+            ACTION_SHOW => self.handle_cmd_show().await,
+            ACTION_STATUS => self.handle_cmd_status().await,
+            ACTION_STATS => self.handle_cmd_stats().await,
+            ACTION_ENABLE => self.handle_cmd_enable(cmd).await,
+            ACTION_DISABLE => self.handle_cmd_disable(cmd).await,
+            ACTION_HELP => self.handle_cmd_help().await,
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        };
+        if let Err(e) = result {
+            self.warn(format!("[{MODULE}] {e} for cmd `{cmd}`.")).await;
+        }
+    }
+
+    async fn help(&self, name: &str, actions: &[ActionSpec]) {
+        self.info(format!("[{name}] actions")).await;
+        for spec in actions {
+            self.info(format!("  {:<HELP_WIDTH$} {}", spec.render(), spec.help))
+                .await;
+        }
+    }
+
+    async fn handle_cmd_help(&mut self) -> Result<(), CommandError> {
+        self.help(MODULE, ACTIONS).await;
+        for plugin in &self.plugins {
+            self.help(&plugin.name, plugin.actions).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_show(&mut self) -> Result<(), CommandError> {
+        self.info(format!("{:<12} {:<8} Subscriptions", "Name", "Enabled"))
+            .await;
+        for registration in &self.registry {
+            let line = match self.get_plugin(registration.name) {
+                Some(plugin) => format!(
+                    "{:<12} {:<8} {}",
+                    plugin.name,
+                    "yes",
+                    plugin.subscriptions.join(" ")
+                ),
+                None => format!("{:<12} {:<8}", registration.name, "no"),
+            };
+            self.info(line).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_status(&mut self) -> Result<(), CommandError> {
+        self.info(format!(
+            "{:<12} {:<10} {:>7} Last panic",
+            "Name", "State", "Crashes"
+        ))
+        .await;
+        for plugin in &self.plugins {
+            let line = {
+                let status = plugin.status.lock().unwrap();
+                format!(
+                    "{:<12} {:<10} {:>7} {}",
+                    plugin.name,
+                    format!("{:?}", status.state),
+                    status.crashes,
+                    status.last_panic.as_deref().unwrap_or("-")
+                )
+            };
+            self.info(line).await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_stats(&mut self) -> Result<(), CommandError> {
+        let stats = self.stats();
+
+        self.info(format!(
+            "[{MODULE}] queue depth {}, peak {} of {}",
+            stats.queue.depth, stats.queue.peak, stats.queue.capacity
+        ))
+        .await;
+
+        let buckets: Vec<String> = stats::bucket_names()
+            .iter()
+            .map(|name| format!("{name:>8}"))
+            .collect();
+        self.info(format!(
+            "{:<12} {:>7} {:>9} {:>9} {}",
+            "Name",
+            "Count",
+            "Avg",
+            "Max",
+            buckets.join(" ")
+        ))
+        .await;
+        for PluginLatency { name, latency } in &stats.latencies {
+            let buckets: Vec<String> = latency
+                .buckets
+                .iter()
+                .map(|count| format!("{count:>8}"))
+                .collect();
+            self.info(format!(
+                "{name:<12} {:>7} {:>9} {:>9} {}",
+                latency.count,
+                stats::us_str(latency.avg_us()),
+                stats::us_str(latency.max_us),
+                buckets.join(" ")
+            ))
+            .await;
+        }
+
+        self.info(format!("{:<12} {:<16} {:>7}", "Module", "Action", "Count"))
+            .await;
+        for MsgCount {
+            module,
+            action,
+            count,
+        } in &stats.counts
+        {
+            self.info(format!("{module:<12} {action:<16} {count:>7}"))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    async fn handle_cmd_enable(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+
+        if self.get_registration(name).is_none() {
+            return Err(CommandError::UnknownPlugin(name.to_string()));
+        }
+        if self.get_plugin(name).is_some() {
+            self.info(format!("[{MODULE}] `{name}` is enabled already"))
+                .await;
+            return Ok(());
+        }
+
+        self.start(name).await;
+        self.info(format!("[{MODULE}] `{name}` enabled")).await;
+
+        Ok(())
+    }
+
+    async fn handle_cmd_disable(&mut self, cmd: &Command) -> Result<(), CommandError> {
+        let name = cmd.get_str(0, "name")?;
+
+        if self.get_registration(name).is_none() {
+            return Err(CommandError::UnknownPlugin(name.to_string()));
+        }
+        if REQUIRED.contains(&name) {
+            return Err(CommandError::InvalidArg {
+                name: "name".to_string(),
+                value: name.to_string(),
+                expected: format!("a plugin other than {}", REQUIRED.join("/")),
+            });
+        }
+
+        // dropping the handle closes the mailbox, the supervisor then drains
+        // what is queued and calls `shutdown`
+        match self.plugins.iter().position(|p| p.name == name) {
+            Some(index) => {
+                self.plugins.remove(index);
+                // straight to the mailboxes, e.g. log stops printing to panels
+                self.handle_event(Msg {
+                    ts: utils::time::ts(),
+                    module: MODULE.to_string(),
+                    data: Data::Event(Event::PluginDisabled {
+                        name: name.to_string(),
+                    }),
+                });
+                self.info(format!("[{MODULE}] `{name}` disabled")).await;
+            }
+            None => {
+                self.info(format!("[{MODULE}] `{name}` is disabled already"))
+                    .await;
             }
         }
+
+        Ok(())
     }
 
-    pub async fn handle_cmd(&mut self, msg: &Msg) {
-        if let Data::Cmd(cmd) = &msg.data {
-            let cmd_parts = shell_words::split(&cmd.cmd).expect("Failed to parse cmd.");
-            if let Some(plugin_name) = cmd_parts.get(1) {
-                #[allow(clippy::collapsible_else_if)]
-                if plugin_name == MODULE {
-                    self.my_handle_cmd(&cmd_parts).await;
-                } else {
-                    if let Some(plugin) = self.get_plugin_mut(plugin_name) {
-                        plugin.handle_cmd(msg).await;
-                    } else {
-                        self.warn(format!(
-                            "[{MODULE}] Unknown plugin name (`{plugin_name}`) for cmd `{}`.",
-                            cmd.cmd
-                        ))
-                        .await;
+    fn my_query(&self, cmd: &Command) -> QueryResult {
+        match cmd.action.as_str() {
+            ACTION_LIST => Ok(Reply::Plugins(
+                self.plugins.iter().map(|p| p.name.clone()).collect(),
+            )),
+            ACTION_STATS => Ok(Reply::Stats(self.stats())),
+            action => Err(CommandError::UnknownAction(action.to_string())),
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        Stats {
+            queue: self.queue.clone(),
+            counts: self
+                .counts
+                .iter()
+                .map(|((module, action), count)| MsgCount {
+                    module: module.clone(),
+                    action: action.clone(),
+                    count: *count,
+                })
+                .collect(),
+            latencies: self
+                .plugins
+                .iter()
+                .map(|plugin| PluginLatency {
+                    name: plugin.name.clone(),
+                    latency: plugin.status.lock().unwrap().latency.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    // the router's channel, sampled as each message comes out
+    pub fn sample_queue(&mut self, depth: usize) {
+        self.queue.sample(depth);
+    }
+
+    fn count(&mut self, module: &str, action: &str) {
+        *self
+            .counts
+            .entry((module.to_string(), action.to_string()))
+            .or_default() += 1;
+    }
+
+    // stop the plugins one at a time in reverse start order, each given
+    // SHUTDOWN_TIMEOUT for `Plugin::shutdown`, then signal the app-wide
+    // shutdown so the web server drains and the router exits.
+    // the router keeps running meanwhile, so their last logs still get out.
+    pub async fn shutdown(&mut self) {
+        if self.shutting_down {
+            return;
+        }
+        self.shutting_down = true;
+
+        self.info(format!("[{MODULE}] shutdown")).await;
+
+        let stops: Vec<(String, oneshot::Sender<()>, JoinHandle<()>)> = self
+            .plugins
+            .iter_mut()
+            .rev()
+            .filter_map(|plugin| {
+                Some((
+                    plugin.name.clone(),
+                    plugin.stop_tx.take()?,
+                    plugin.task.take()?,
+                ))
+            })
+            .collect();
+
+        let msg_tx = self.msg_tx.clone();
+        let shutdown_tx = self.shutdown_tx.clone();
+        tokio::spawn(async move {
+            for (name, stop_tx, mut task) in stops {
+                // a round trip through the router: once answered, everything
+                // sent before (e.g. the previous plugin's last logs) has been
+                // delivered to the mailboxes, and mailboxes drain before a stop
+                let _ = utils::msg::query(&msg_tx, MODULE, Command::new(MODULE, ACTION_LIST)).await;
+
+                let _ = stop_tx.send(());
+                if tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut task)
+                    .await
+                    .is_err()
+                {
+                    task.abort();
+                    utils::msg::log_warn(
+                        &msg_tx,
+                        MODULE,
+                        format!(
+                            "[{MODULE}] `{name}` did not stop in {}s. Aborted.",
+                            SHUTDOWN_TIMEOUT.as_secs()
+                        ),
+                    )
+                    .await;
+                }
+            }
+
+            let _ = shutdown_tx.send(());
+        });
+    }
+
+    // forward to the plugin's mailbox, never waiting for the handler
+    pub async fn handle_cmd(&mut self, msg: Msg) {
+        if let Data::Command(cmd) = &msg.data {
+            self.count(&cmd.plugin, &cmd.action);
+            #[allow(clippy::collapsible_else_if)]
+            if cmd.plugin == MODULE {
+                match ActionSpec::find(ACTIONS, &cmd.action).map(|spec| spec.validate(cmd)) {
+                    Some(Err(e)) => self.warn(format!("[{MODULE}] {e} for cmd `{cmd}`.")).await,
+                    _ => self.my_handle_cmd(cmd).await,
+                }
+            } else {
+                if let Some(plugin) = self.get_plugin(&cmd.plugin) {
+                    match self.validate(plugin, cmd) {
+                        Ok(()) if cmd.action == ACTION_HELP => {
+                            self.help(&plugin.name, plugin.actions).await;
+                        }
+                        Ok(()) => {
+                            let _ = plugin.mailbox.send(msg);
+                        }
+                        Err(e) => {
+                            self.warn(format!(
+                                "[{}] {e} for cmd `{cmd}`. See `p {} help`.",
+                                plugin.name, plugin.name
+                            ))
+                            .await;
+                        }
                     }
+                } else {
+                    self.warn(format!(
+                        "[{MODULE}] {} for cmd `{cmd}`.",
+                        CommandError::UnknownPlugin(cmd.plugin.clone())
+                    ))
+                    .await;
                 }
+            }
+        }
+    }
+
+    pub fn handle_event(&mut self, msg: Msg) {
+        if let Data::Event(event) = &msg.data {
+            self.count("event", event.topic());
+            // everyone reconfigures, no need to subscribe
+            let all = event.topic() == EVENT_CFG_CHANGED;
+            for plugin in self
+                .plugins
+                .iter()
+                .filter(|p| all || p.subscribed(event.topic()))
+            {
+                let _ = plugin.mailbox.send(Msg {
+                    ts: msg.ts,
+                    module: msg.module.clone(),
+                    data: Data::Event(event.clone()),
+                });
+            }
+        }
+    }
+
+    pub fn handle_query(&mut self, msg: Msg) {
+        if let Data::Query(query) = msg.data {
+            self.count(&query.cmd.plugin, &query.cmd.action);
+            if query.cmd.plugin == MODULE {
+                let result = self.my_query(&query.cmd);
+                let _ = query.reply_tx.send(result);
+            } else if let Some(plugin) = self.get_plugin(&query.cmd.plugin) {
+                let _ = plugin.mailbox.send(Msg {
+                    ts: msg.ts,
+                    module: msg.module,
+                    data: Data::Query(query),
+                });
             } else {
-                self.warn(format!(
-                    "[{MODULE}] Missing plugin name for cmd `{}`.",
-                    cmd.cmd
-                ))
-                .await;
+                let plugin = query.cmd.plugin.clone();
+                let _ = query
+                    .reply_tx
+                    .send(Err(CommandError::UnknownPlugin(plugin)));
             }
         }
     }
 
-    fn get_plugin_mut(&mut self, name: &str) -> Option<&mut Box<dyn Plugin + Send + Sync>> {
-        self.plugins.iter_mut().find(|p| p.name() == name)
+    // against the plugin's action specs; `help` is answered here for all
+    fn validate(&self, plugin: &PluginHandle, cmd: &Command) -> Result<(), CommandError> {
+        if cmd.action == ACTION_HELP || plugin.actions.is_empty() {
+            return Ok(());
+        }
+        match ActionSpec::find(plugin.actions, &cmd.action) {
+            Some(spec) => spec.validate(cmd),
+            None => Err(CommandError::UnknownAction(cmd.action.clone())),
+        }
+    }
+
+    fn get_plugin(&self, name: &str) -> Option<&PluginHandle> {
+        self.plugins.iter().find(|p| p.name == name)
+    }
+
+    fn get_registration(&self, name: &str) -> Option<&Registration> {
+        self.registry.iter().find(|r| r.name == name)
     }
 }
@@ -0,0 +1,125 @@
+use crate::messages::ActionSpec;
+use crate::plugins::plugins_main::{self, PluginBox, PluginFactory};
+use crate::plugins::{
+    plugin_cfg, plugin_cli, plugin_cron, plugin_devices, plugin_infos, plugin_log, plugin_monitor,
+    plugin_mqtt, plugin_music, plugin_nas, plugin_panels, plugin_runtipi, plugin_scripts,
+    plugin_system, plugin_todos, plugin_weather,
+};
+
+// always started, cannot be disabled
+pub const REQUIRED: &[&str] = &["log", "scripts"];
+
+pub struct Registration {
+    pub name: &'static str,
+    // the same as the plugin's `actions`, known without starting it
+    pub actions: &'static [ActionSpec],
+    pub factory: PluginFactory,
+}
+
+impl Registration {
+    fn new(name: &'static str, actions: &'static [ActionSpec], factory: PluginFactory) -> Self {
+        Self {
+            name,
+            actions,
+            factory,
+        }
+    }
+}
+
+// the actions of a registered plugin, or of `plugins` itself
+pub fn actions(name: &str) -> Option<&'static [ActionSpec]> {
+    if name == plugins_main::MODULE {
+        return Some(plugins_main::ACTIONS);
+    }
+    registry()
+        .into_iter()
+        .find(|registration| registration.name == name)
+        .map(|registration| registration.actions)
+}
+
+// every plugin the binary knows about, in start order
+// to add a plugin: create plugin_xxx.rs, add it to mod.rs and register it here
+pub fn registry() -> Vec<Registration> {
+    vec![
+        Registration::new("log", plugin_log::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_log::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("scripts", plugin_scripts::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_scripts::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("cfg", plugin_cfg::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_cfg::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("cli", plugin_cli::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_cli::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("system", plugin_system::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_system::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("panels", plugin_panels::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_panels::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("mqtt", plugin_mqtt::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_mqtt::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("devices", plugin_devices::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_devices::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("infos", plugin_infos::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_infos::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("nas", plugin_nas::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_nas::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("monitor", plugin_monitor::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_monitor::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("weather", plugin_weather::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_weather::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("music", plugin_music::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_music::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("runtipi", plugin_runtipi::ACTIONS, |msg_tx, _| {
+            Box::pin(
+                async move { Box::new(plugin_runtipi::PluginUnit::new(msg_tx).await) as PluginBox },
+            )
+        }),
+        Registration::new("todos", plugin_todos::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_todos::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+        Registration::new("cron", plugin_cron::ACTIONS, |msg_tx, shutdown_tx| {
+            Box::pin(async move {
+                Box::new(plugin_cron::PluginUnit::new(msg_tx, shutdown_tx).await) as PluginBox
+            })
+        }),
+    ]
+}
@@ -0,0 +1,168 @@
+use std::fs::{self, File};
+use std::io::{LineWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::messages::{self, ACTION_GUI, Cmd, Command, Data, Event, Msg, Query};
+
+// replay runs headless and off the network: no terminal UI, no broker.
+// scripts are left out too, the commands they sent are in the recording
+const REPLAY_SKIP_PLUGINS: &[&str] = &["cli", "panels", "mqtt", "scripts"];
+
+// one line of a recording, e.g.
+// {"ts":1756000000,"module":"mqtt","data":{"command":{"plugin":"devices","action":"onboard","args":["cng3_pi5","1"]}}}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub ts: u64,
+    pub module: String,
+    pub data: RecordData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordData {
+    Log { level: String, msg: String },
+    Cmd(String),
+    Command(Command),
+    // the reply is not recorded
+    Query(Command),
+    Event(Event),
+}
+
+impl Record {
+    // None for router state, i.e. taps
+    pub fn new(msg: &Msg) -> Option<Self> {
+        let data = match &msg.data {
+            Data::Log(log) => RecordData::Log {
+                level: log.level.to_string(),
+                msg: log.msg.clone(),
+            },
+            Data::Cmd(cmd) => RecordData::Cmd(cmd.cmd.clone()),
+            Data::Command(cmd) => RecordData::Command(cmd.clone()),
+            Data::Query(query) => RecordData::Query(query.cmd.clone()),
+            Data::Event(event) => RecordData::Event(event.clone()),
+            Data::Tap(_) => return None,
+        };
+
+        Some(Self {
+            ts: msg.ts,
+            module: msg.module.clone(),
+            data,
+        })
+    }
+
+    fn into_msg(self) -> Option<Msg> {
+        let data = match self.data {
+            // logs are produced again by the replaying instance
+            RecordData::Log { .. } => return None,
+            RecordData::Cmd(cmd) => {
+                let command = messages::split(&cmd).and_then(Command::from_words);
+                if command.is_ok_and(|command| !replayable(&command)) {
+                    return None;
+                }
+                Data::Cmd(Cmd { cmd })
+            }
+            RecordData::Command(cmd) if !replayable(&cmd) => return None,
+            RecordData::Command(cmd) => Data::Command(cmd),
+            // nobody waits for the answer
+            RecordData::Query(cmd) => Data::Query(Query {
+                cmd,
+                reply_tx: oneshot::channel().0,
+            }),
+            RecordData::Event(event) => Data::Event(event),
+        };
+
+        Some(Msg {
+            ts: self.ts,
+            module: self.module,
+            data,
+        })
+    }
+}
+
+// e.g. `p mqtt init gui mqtt` or `p log gui log` are left out
+fn replayable(cmd: &Command) -> bool {
+    !REPLAY_SKIP_PLUGINS.contains(&cmd.plugin.as_str())
+        && cmd.action != ACTION_GUI
+        && cmd.args.first().map(|arg| arg.as_str()) != Some(ACTION_GUI)
+}
+
+pub type MemorySink = Arc<Mutex<Vec<Record>>>;
+
+enum Sink {
+    File(LineWriter<File>),
+    Memory(MemorySink),
+}
+
+// keeps every Msg the router sees, one JSON line each in a file,
+// or in memory for the harness
+pub struct Recorder {
+    filename: String,
+    sink: Sink,
+}
+
+impl Recorder {
+    pub fn create(filename: &str) -> anyhow::Result<Self> {
+        let file =
+            File::create(filename).with_context(|| format!("Failed to create `{filename}`"))?;
+
+        Ok(Self {
+            filename: filename.to_string(),
+            sink: Sink::File(LineWriter::new(file)),
+        })
+    }
+
+    pub fn memory(records: MemorySink) -> Self {
+        Self {
+            filename: "memory".to_string(),
+            sink: Sink::Memory(records),
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn record(&mut self, msg: &Msg) -> anyhow::Result<()> {
+        let Some(record) = Record::new(msg) else {
+            return Ok(());
+        };
+        match &mut self.sink {
+            Sink::File(file) => {
+                let line = serde_json::to_string(&record)?;
+                writeln!(file, "{line}")?;
+            }
+            Sink::Memory(records) => records.lock().unwrap().push(record),
+        }
+
+        Ok(())
+    }
+}
+
+// feed a recording in order; returns how many messages were sent
+pub async fn replay(filename: &str, replay_tx: &Sender<Msg>) -> anyhow::Result<usize> {
+    let content =
+        fs::read_to_string(filename).with_context(|| format!("Failed to read `{filename}`"))?;
+
+    let mut count = 0;
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: Record = serde_json::from_str(line)
+            .with_context(|| format!("{filename}:{}: invalid record", idx + 1))?;
+        if let Some(msg) = record.into_msg() {
+            if replay_tx.send(msg).await.is_err() {
+                break;
+            }
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+pub const BOOT: &str = "@boot";
+// far enough for `0 0 29 2 *` and then some
+const SEARCH_DAYS: i64 = 366 * 8;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// when to run: `@boot`, or `minute hour day-of-month month day-of-week` with
+// `*`, `1,15`, `1-5`, `*/10`, `1-30/2` and jan-dec/sun-sat, or one of
+// @yearly/@annually/@monthly/@weekly/@daily/@midnight/@hourly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    text: String,
+    fields: Option<Fields>,
+}
+
+// a bit per allowed value
+#[derive(Debug, Clone, PartialEq)]
+struct Fields {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // with both restricted, either one will do
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+impl Schedule {
+    pub fn is_boot(&self) -> bool {
+        self.fields.is_none()
+    }
+
+    // the first minute strictly after `after`, None for @boot
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let fields = self.fields.as_ref()?;
+        let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = start + Duration::days(SEARCH_DAYS);
+
+        let mut t = start;
+        while t < end {
+            if !bit(fields.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !fields.day_matches(t.date()) {
+                t = (t.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if !bit(fields.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if !bit(fields.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+
+        None
+    }
+}
+
+impl Fields {
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        if self.days_any || self.weekdays_any {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let expr = match text {
+            BOOT => {
+                return Ok(Self {
+                    text: text.to_string(),
+                    fields: None,
+                });
+            }
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            _ if text.starts_with('@') => return Err(format!("unknown schedule `{text}`")),
+            _ => text,
+        };
+
+        let words: Vec<&str> = expr.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = words[..] else {
+            return Err(format!(
+                "`{text}` is not `minute hour day-of-month month day-of-week`"
+            ));
+        };
+        // 7 is sunday too
+        let weekdays_mask = field(weekdays, "day-of-week", 0, 7, WEEKDAYS)?;
+        let fields = Fields {
+            minutes: field(minutes, "minute", 0, 59, &[])?,
+            hours: field(hours, "hour", 0, 23, &[])?,
+            days: field(days, "day-of-month", 1, 31, &[])?,
+            months: field(months, "month", 1, 12, MONTHS)?,
+            weekdays: (weekdays_mask | (weekdays_mask >> 7)) & 0x7f,
+            days_any: days.starts_with('*'),
+            weekdays_any: weekdays.starts_with('*'),
+        };
+
+        Ok(Self {
+            text: text.to_string(),
+            fields: Some(fields),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// `names` start at `min`
+fn field(text: &str, name: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |word: &str| -> Result<u32, String> {
+        let lower = word.to_ascii_lowercase();
+        let value = match names.iter().position(|name| *name == lower) {
+            Some(idx) => idx as u32 + min,
+            None => word
+                .parse::<u32>()
+                .map_err(|_| format!("{name}: `{word}` is not a number"))?,
+        };
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(format!("{name}: {value} is not in {min}-{max}"))
+        }
+    };
+
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("{name}: `{step}` is not a step"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (value(first)?, value(last)?),
+                // `5/15` is 5 to the end in steps of 15
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let value = value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if first > last {
+            return Err(format!("{name}: {first}-{last} is backwards"));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
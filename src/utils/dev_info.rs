@@ -1,7 +1,9 @@
+use serde::Serialize;
+
 use crate::utils;
 
 // DevInfo
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DevInfo {
     pub ts: u64,
     pub name: String,
@@ -0,0 +1,211 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+pub const LOG_FILE: &str = "./logs/cng3.log";
+const LOG_MAX_SIZE: u64 = 1024 * 1024;
+const LOG_MAX_FILES: usize = 5;
+const LOG_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// a quiet node may not rotate for weeks
+const PRUNE_INTERVAL: u64 = 24 * 60 * 60;
+
+// where the log plugin starts writing, None for no file, e.g. in the harness
+static DEFAULT_PATH: Lazy<Mutex<Option<String>>> =
+    Lazy::new(|| Mutex::new(Some(LOG_FILE.to_string())));
+
+pub fn set_default_path(path: Option<&str>) {
+    *DEFAULT_PATH.lock().unwrap() = path.map(|path| path.to_string());
+}
+
+pub fn default_path() -> Option<String> {
+    DEFAULT_PATH.lock().unwrap().clone()
+}
+
+// a line of the json format, e.g.
+// {"timestamp":"2026-10-16T07:55:00+08:00","device":"cng3_pi5","module":"devices","level":"INFO","message":"[devices] ..."}
+#[derive(Serialize, Deserialize)]
+struct JsonLine {
+    timestamp: String,
+    device: String,
+    module: String,
+    level: String,
+    message: String,
+}
+
+#[derive(Debug)]
+enum LogFormat {
+    Text,
+    // device name stamped on every line
+    Json(String),
+}
+
+// one line per log, in text, e.g.
+// 2026-10-16T07:55:00+08:00 INFO devices [devices] cng3_pi5 on at ...
+// or as JSON lines for log shipping.
+// rotated to `<path>.1` ... `<path>.N` once LOG_MAX_SIZE is reached,
+// rotated files older than LOG_MAX_AGE are removed on the first write, on
+// rotation and once a day
+#[derive(Debug)]
+pub struct LogFile {
+    path: PathBuf,
+    format: LogFormat,
+    file: Option<File>,
+    size: u64,
+    // ts of the next prune
+    prune_at: u64,
+}
+
+impl LogFile {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            format: LogFormat::Text,
+            file: None,
+            size: 0,
+            prune_at: 0,
+        }
+    }
+
+    pub fn json(path: &str, device: &str) -> Self {
+        Self {
+            format: LogFormat::Json(device.to_string()),
+            ..Self::new(path)
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&mut self, ts: u64, level: log::Level, module: &str, msg: &str) -> io::Result<()> {
+        if self.size >= LOG_MAX_SIZE {
+            self.rotate()?;
+        }
+        if ts >= self.prune_at {
+            self.prune();
+            self.prune_at = ts + PRUNE_INTERVAL;
+        }
+
+        let line = match &self.format {
+            LogFormat::Text => format!(
+                "{} {level} {module} {}\n",
+                utils::time::ts_str_rfc3339(ts),
+                msg.replace('\n', "\\n")
+            ),
+            LogFormat::Json(device) => {
+                let line = serde_json::to_string(&JsonLine {
+                    timestamp: utils::time::ts_str_rfc3339(ts),
+                    device: device.clone(),
+                    module: module.to_string(),
+                    level: level.to_string(),
+                    message: msg.to_string(),
+                })?;
+                format!("{line}\n")
+            }
+        };
+
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                self.size = file.metadata()?.len();
+                self.file.insert(file)
+            }
+        };
+        file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        self.size = 0;
+
+        let _ = fs::remove_file(rotated(&self.path, LOG_MAX_FILES));
+        for idx in (1..LOG_MAX_FILES).rev() {
+            let from = rotated(&self.path, idx);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, idx + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        self.prune();
+
+        Ok(())
+    }
+
+    fn prune(&self) {
+        for idx in 1..=LOG_MAX_FILES {
+            let path = rotated(&self.path, idx);
+            let expired = fs::metadata(&path)
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > LOG_MAX_AGE);
+            if expired {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    fn line_module(&self, line: &str) -> Option<String> {
+        match self.format {
+            LogFormat::Text => line.split(' ').nth(2).map(|module| module.to_string()),
+            LogFormat::Json(_) => serde_json::from_str::<JsonLine>(line)
+                .ok()
+                .map(|line| line.module),
+        }
+    }
+
+    // the last `n` lines, oldest first, across the rotated files
+    pub fn tail(&self, n: usize, module: Option<&str>) -> io::Result<Vec<String>> {
+        let mut lines = vec![];
+
+        let paths = std::iter::once(self.path.clone())
+            .chain((1..=LOG_MAX_FILES).map(|idx| rotated(&self.path, idx)));
+        for path in paths {
+            if lines.len() >= n {
+                break;
+            }
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            let mut matched: Vec<String> = BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| {
+                    module.is_none_or(|module| self.line_module(line).as_deref() == Some(module))
+                })
+                .collect();
+            let skip = matched.len().saturating_sub(n - lines.len());
+            matched.drain(..skip);
+            matched.append(&mut lines);
+            lines = matched;
+        }
+
+        Ok(lines)
+    }
+}
+
+fn rotated(path: &Path, idx: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{idx}"));
+    PathBuf::from(path)
+}
@@ -1,11 +1,17 @@
+pub mod cron;
 pub mod dev_info;
 pub mod ffmpeg;
+pub mod line_edit;
+pub mod log_file;
 pub mod mode;
 pub mod msg;
 pub mod nas_info;
 pub mod panel;
+pub mod script;
+pub mod stats;
 pub mod system;
 pub mod task;
 pub mod time;
+pub mod watch;
 pub mod weather;
 pub mod yt_dlp;
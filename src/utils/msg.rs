@@ -1,6 +1,7 @@
 use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
 
-use crate::messages::{Cmd, Data, Log, Msg};
+use crate::messages::{Cmd, Command, CommandError, Data, Event, Log, Msg, Query, QueryResult};
 use crate::utils;
 
 pub async fn log_new(msg_tx: &Sender<Msg>, module: &str) {
@@ -15,35 +16,71 @@ pub async fn log_new(msg_tx: &Sender<Msg>, module: &str) {
     let _ = msg_tx.send(msg).await;
 }
 
-pub async fn log_info(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+pub async fn log(msg_tx: &Sender<Msg>, module: &str, level: log::Level, msg: String) {
     let msg = Msg {
         ts: utils::time::ts(),
         module: module.to_string(),
-        data: Data::Log(Log {
-            level: log::Level::Info,
-            msg,
-        }),
+        data: Data::Log(Log { level, msg }),
     };
     let _ = msg_tx.send(msg).await;
 }
 
+pub async fn log_error(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+    log(msg_tx, module, log::Level::Error, msg).await;
+}
+
 pub async fn log_warn(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+    log(msg_tx, module, log::Level::Warn, msg).await;
+}
+
+pub async fn log_info(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+    log(msg_tx, module, log::Level::Info, msg).await;
+}
+
+pub async fn log_debug(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+    log(msg_tx, module, log::Level::Debug, msg).await;
+}
+
+pub async fn log_trace(msg_tx: &Sender<Msg>, module: &str, msg: String) {
+    log(msg_tx, module, log::Level::Trace, msg).await;
+}
+
+// a line as typed in the cli, e.g. `p weather update`
+pub async fn cmd(msg_tx: &Sender<Msg>, module: &str, cmd: String) {
     let msg = Msg {
         ts: utils::time::ts(),
         module: module.to_string(),
-        data: Data::Log(Log {
-            level: log::Level::Warn,
-            msg,
-        }),
+        data: Data::Cmd(Cmd { cmd }),
     };
     let _ = msg_tx.send(msg).await;
 }
 
-pub async fn cmd(msg_tx: &Sender<Msg>, module: &str, cmd: String) {
+pub async fn command(msg_tx: &Sender<Msg>, module: &str, cmd: Command) {
     let msg = Msg {
         ts: utils::time::ts(),
         module: module.to_string(),
-        data: Data::Cmd(Cmd { cmd }),
+        data: Data::Command(cmd),
+    };
+    let _ = msg_tx.send(msg).await;
+}
+
+pub async fn query(msg_tx: &Sender<Msg>, module: &str, cmd: Command) -> QueryResult {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let msg = Msg {
+        ts: utils::time::ts(),
+        module: module.to_string(),
+        data: Data::Query(Query { cmd, reply_tx }),
+    };
+    let _ = msg_tx.send(msg).await;
+
+    reply_rx.await.unwrap_or(Err(CommandError::NoReply))
+}
+
+pub async fn emit(msg_tx: &Sender<Msg>, module: &str, event: Event) {
+    let msg = Msg {
+        ts: utils::time::ts(),
+        module: module.to_string(),
+        data: Data::Event(event),
     };
     let _ = msg_tx.send(msg).await;
 }
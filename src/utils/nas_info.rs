@@ -14,11 +14,12 @@ use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
 
 // NasInfo
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NasState {
     Unsync,
     Synced,
     Syncing,
+    #[allow(dead_code)]
     Err,
 }
 
@@ -28,7 +29,7 @@ pub enum NasEvent {
     Offboard,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct NasInfo {
     pub ts: u64,
     pub name: String,
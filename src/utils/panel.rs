@@ -1,6 +1,6 @@
 use tokio::sync::mpsc::Sender;
 
-use crate::messages::{Cmd, Data, Log, Msg};
+use crate::messages::{Command, Data, Log, Msg};
 use crate::utils::{self, mode::Mode};
 
 // Panel
@@ -10,17 +10,33 @@ pub async fn output_update_gui_simple(
     gui_panel: &str,
     output: String,
 ) {
-    let ts = utils::time::ts();
-    let module = module.to_string();
+    utils::msg::command(
+        msg_tx,
+        module,
+        Command::new("panels", "output_update")
+            .arg(gui_panel)
+            .arg(output),
+    )
+    .await;
+}
 
-    let msg = Msg {
-        ts,
+// the same, with the cursor that many columns into the line
+pub async fn output_update_gui_cursor(
+    module: &str,
+    msg_tx: &Sender<Msg>,
+    gui_panel: &str,
+    output: String,
+    cursor: usize,
+) {
+    utils::msg::command(
+        msg_tx,
         module,
-        data: Data::Cmd(Cmd {
-            cmd: format!("p panels output_update {gui_panel} '{output}'"),
-        }),
-    };
-    let _ = msg_tx.send(msg).await;
+        Command::new("panels", "output_update")
+            .arg(gui_panel)
+            .arg(output)
+            .arg(cursor),
+    )
+    .await;
 }
 
 pub async fn output_push(
@@ -38,12 +54,11 @@ pub async fn output_push(
             let msg = Msg {
                 ts,
                 module,
-                data: Data::Cmd(Cmd {
-                    cmd: format!(
-                        "p panels output_push {gui_panel} '{} [{level}] {output}'",
-                        utils::time::ts_str(ts)
-                    ),
-                }),
+                data: Data::Command(
+                    Command::new("panels", "output_push")
+                        .arg(gui_panel)
+                        .arg(format!("{} [{level}] {output}", utils::time::ts_str(ts))),
+                ),
             };
             let _ = msg_tx.send(msg).await;
         }
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::messages::{
+    self, ACTION_HELP, ActionSpec, Command, CommandError, EXIT_CMDS, QUERY_PREFIX,
+};
+use crate::plugins::registry;
+
+const SET: &str = "set";
+const INCLUDE: &str = "include";
+const IF: &str = "if";
+const ELSE: &str = "else";
+const END: &str = "end";
+const SLEEP: &str = "sleep";
+const WAIT: &str = "wait";
+// set to the cfg name before the first line
+const HOST_VAR: &str = "HOST";
+
+// a step of a script, after includes, variables and host blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub file: String,
+    // 1-based
+    pub no: usize,
+    pub step: Step,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    // sent as typed in the cli, e.g. `p weather update`
+    Cmd(String),
+    // `sleep <secs>`, fractions allowed
+    Sleep(Duration),
+    // `wait <topic> [timeout secs]`, for an event since the last wait, e.g.
+    // `wait mqtt.connected 30`. the job stops on timeout. the first wait only
+    // sees the events since the job started, without a timeout one that came
+    // before waits forever
+    Wait {
+        topic: String,
+        timeout: Option<Duration>,
+    },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Cmd(cmd) => write!(f, "{cmd}"),
+            Step::Sleep(duration) => write!(f, "{SLEEP} {}", duration.as_secs_f64()),
+            Step::Wait {
+                topic,
+                timeout: None,
+            } => write!(f, "{WAIT} {topic}"),
+            Step::Wait {
+                topic,
+                timeout: Some(timeout),
+            } => write!(f, "{WAIT} {topic} {}", timeout.as_secs_f64()),
+        }
+    }
+}
+
+// e.g. `./gui.scripts:12: unknown variable `$SERVER``
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub file: String,
+    // 0 for the file as a whole
+    pub no: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.no == 0 {
+            write!(f, "{}: {}", self.file, self.msg)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.no, self.msg)
+        }
+    }
+}
+
+// an open `if`, the lines up to its `end` run when every open block is taken
+struct Block {
+    no: usize,
+    cond: bool,
+    in_else: bool,
+}
+
+impl Block {
+    fn taken(&self) -> bool {
+        self.cond != self.in_else
+    }
+}
+
+struct Loader<'a> {
+    host: &'a str,
+    vars: HashMap<String, String>,
+    // the files being read, to catch an include of itself
+    stack: Vec<PathBuf>,
+    lines: Vec<Line>,
+    errors: Vec<ScriptError>,
+}
+
+// the steps of `file` for the cfg name `host`:
+// - `set VAR value`, then `$VAR` or `${VAR}` anywhere later, `$$` for `$`
+// - `include other.scripts`, relative to the including file
+// - `if host == cng3_pi5`, or `!=`, then lines, an optional `else` and `end`
+// - `sleep` and `wait`, see `Step`
+// blank lines and `#` comments are skipped. every error is reported
+pub fn load(file: &str, host: &str) -> Result<Vec<Line>, Vec<ScriptError>> {
+    let mut loader = Loader {
+        host,
+        vars: HashMap::from([(HOST_VAR.to_string(), host.to_string())]),
+        stack: vec![],
+        lines: vec![],
+        errors: vec![],
+    };
+
+    match fs::read_to_string(file) {
+        Ok(content) => loader.load(Path::new(file), &content),
+        Err(e) => loader.error(file, 0, e.to_string()),
+    }
+
+    if loader.errors.is_empty() {
+        Ok(loader.lines)
+    } else {
+        Err(loader.errors)
+    }
+}
+
+impl Loader<'_> {
+    fn error(&mut self, file: &str, no: usize, msg: String) {
+        self.errors.push(ScriptError {
+            file: file.to_string(),
+            no,
+            msg,
+        });
+    }
+
+    fn load(&mut self, path: &Path, content: &str) {
+        let file = path.display().to_string();
+        self.stack.push(path.to_path_buf());
+        let mut blocks: Vec<Block> = vec![];
+
+        for (idx, text) in content.lines().enumerate() {
+            let no = idx + 1;
+            let text = text.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            let (word, rest) = match text.split_once(char::is_whitespace) {
+                Some((word, rest)) => (word, rest.trim()),
+                None => (text, ""),
+            };
+            let active = blocks.iter().all(Block::taken);
+
+            match word {
+                IF => {
+                    // skipped blocks are only checked for their shape
+                    let cond = if active {
+                        match self.condition(rest) {
+                            Ok(cond) => cond,
+                            Err(msg) => {
+                                self.error(&file, no, msg);
+                                false
+                            }
+                        }
+                    } else {
+                        false
+                    };
+                    blocks.push(Block {
+                        no,
+                        cond,
+                        in_else: false,
+                    });
+                }
+                ELSE => match blocks.last_mut() {
+                    Some(block) if !block.in_else => block.in_else = true,
+                    Some(_) => self.error(&file, no, format!("second `{ELSE}`")),
+                    None => self.error(&file, no, format!("`{ELSE}` without `{IF}`")),
+                },
+                END => {
+                    if blocks.pop().is_none() {
+                        self.error(&file, no, format!("`{END}` without `{IF}`"));
+                    }
+                }
+                _ if !active => (),
+                SET => {
+                    if let Err(msg) = self.set(rest) {
+                        self.error(&file, no, msg);
+                    }
+                }
+                INCLUDE => self.include(path, &file, no, rest),
+                _ => match self.expand(text).and_then(|text| step(&text)) {
+                    Ok(step) => self.lines.push(Line {
+                        file: file.clone(),
+                        no,
+                        step,
+                    }),
+                    Err(msg) => self.error(&file, no, msg),
+                },
+            }
+        }
+
+        for block in blocks {
+            self.error(&file, block.no, format!("`{IF}` without `{END}`"));
+        }
+        self.stack.pop();
+    }
+
+    // `host == <name>` or `host != <name>`
+    fn condition(&self, text: &str) -> Result<bool, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let [subject, op, name] = words[..] else {
+            return Err(format!("expected `{IF} host == <name>`, got `{IF} {text}`"));
+        };
+        if subject != "host" {
+            return Err(format!("unknown subject `{subject}`, only `host`"));
+        }
+        let name = self.expand(name)?;
+        match op {
+            "==" => Ok(self.host == name),
+            "!=" => Ok(self.host != name),
+            op => Err(format!("unknown operator `{op}`, only `==` and `!=`")),
+        }
+    }
+
+    fn set(&mut self, text: &str) -> Result<(), String> {
+        let (name, value) = text
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("expected `{SET} <VAR> <value>`, got `{SET} {text}`"))?;
+        if !is_name(name) {
+            return Err(format!("`{name}` is not a variable name"));
+        }
+        let value = self.expand(value.trim())?;
+        self.vars.insert(name.to_string(), value);
+
+        Ok(())
+    }
+
+    fn include(&mut self, path: &Path, file: &str, no: usize, text: &str) {
+        let name = match self.expand(text) {
+            Ok(name) if !name.is_empty() => name,
+            Ok(_) => {
+                self.error(file, no, format!("expected `{INCLUDE} <file>`"));
+                return;
+            }
+            Err(msg) => {
+                self.error(file, no, msg);
+                return;
+            }
+        };
+        let included = path.parent().unwrap_or(Path::new("")).join(&name);
+
+        if self.stack.contains(&included) {
+            self.error(file, no, format!("`{name}` includes itself"));
+            return;
+        }
+        match fs::read_to_string(&included) {
+            Ok(content) => self.load(&included, &content),
+            Err(e) => self.error(file, no, format!("cannot include `{name}` ({e})")),
+        }
+    }
+
+    fn expand(&self, text: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(idx) = rest.find('$') {
+            out.push_str(&rest[..idx]);
+            rest = &rest[idx + 1..];
+
+            let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+                let end = braced
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed `${{` in `{text}`"))?;
+                (&braced[..end], end + 2)
+            } else if rest.starts_with('$') {
+                out.push('$');
+                rest = &rest[1..];
+                continue;
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            };
+
+            if name.is_empty() {
+                // e.g. a price, left alone
+                out.push('$');
+                continue;
+            }
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| format!("unknown variable `${name}`"))?;
+            out.push_str(value);
+            rest = &rest[len..];
+        }
+        out.push_str(rest);
+
+        Ok(out)
+    }
+}
+
+// `load`, then every command against the actions of its plugin: the plugin,
+// the action and the number of arguments. nothing is run
+pub fn check(file: &str, host: &str) -> Result<Vec<Line>, Vec<ScriptError>> {
+    let lines = load(file, host)?;
+
+    let errors: Vec<ScriptError> = lines
+        .iter()
+        .filter_map(|line| match &line.step {
+            Step::Cmd(cmd) => check_cmd(cmd).err().map(|e| ScriptError {
+                file: line.file.clone(),
+                no: line.no,
+                msg: format!("{e} for cmd `{cmd}`"),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(lines)
+    } else {
+        Err(errors)
+    }
+}
+
+// one command, e.g. `p weather add 新店 24.9676 121.542`, the same way
+pub fn check_cmd(cmd: &str) -> Result<(), String> {
+    let words = messages::split(cmd).map_err(|e| e.to_string())?;
+    let Some(first) = words.first() else {
+        return Ok(());
+    };
+    if EXIT_CMDS.contains(&first.as_str()) {
+        return Ok(());
+    }
+
+    let query = first == QUERY_PREFIX;
+    let command = if query {
+        Command::from_args(words.into_iter().skip(1))
+    } else {
+        Command::from_words(words)
+    }
+    .map_err(|e| e.to_string())?;
+    let actions = registry::actions(&command.plugin)
+        .ok_or_else(|| CommandError::UnknownPlugin(command.plugin.clone()).to_string())?;
+    // queries have their own actions, not declared
+    if query || command.action == ACTION_HELP || actions.is_empty() {
+        return Ok(());
+    }
+
+    let spec = ActionSpec::find(actions, &command.action)
+        .ok_or_else(|| CommandError::UnknownAction(command.action.clone()).to_string())?;
+    spec.validate(&command).map_err(|e| e.to_string())?;
+    if spec.max_args().is_some_and(|max| command.args.len() > max) {
+        return Err(format!("Too many arguments, expected `{}`", spec.render()));
+    }
+
+    Ok(())
+}
+
+fn step(text: &str) -> Result<Step, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words[..] {
+        [SLEEP, secs] => Ok(Step::Sleep(secs_arg(secs)?)),
+        [SLEEP, ..] => Err(format!("expected `{SLEEP} <secs>`, got `{text}`")),
+        [WAIT, topic] => Ok(Step::Wait {
+            topic: topic.to_string(),
+            timeout: None,
+        }),
+        [WAIT, topic, secs] => Ok(Step::Wait {
+            topic: topic.to_string(),
+            timeout: Some(secs_arg(secs)?),
+        }),
+        [WAIT, ..] => Err(format!("expected `{WAIT} <topic> [timeout]`, got `{text}`")),
+        _ => Ok(Step::Cmd(text.to_string())),
+    }
+}
+
+fn secs_arg(secs: &str) -> Result<Duration, String> {
+    secs.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("`{secs}` is not a number of seconds"))
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
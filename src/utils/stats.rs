@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+// upper bounds of the latency buckets, the last bucket takes the rest
+pub const LATENCY_BOUNDS_MS: [u64; 4] = [1, 10, 100, 1000];
+
+// Stats: a snapshot for `p plugins stats` and `query plugins stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub queue: QueueStats,
+    pub counts: Vec<MsgCount>,
+    pub latencies: Vec<PluginLatency>,
+}
+
+// messages waiting in the router's channel, sampled on each receive
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueStats {
+    pub capacity: usize,
+    pub depth: usize,
+    pub peak: usize,
+}
+
+impl QueueStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    pub fn sample(&mut self, depth: usize) {
+        self.depth = depth;
+        self.peak = self.peak.max(depth);
+    }
+}
+
+// routed messages by target module and action, e.g. `devices onboard`
+#[derive(Debug, Clone, Serialize)]
+pub struct MsgCount {
+    pub module: String,
+    pub action: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginLatency {
+    pub name: String,
+    pub latency: Histogram,
+}
+
+// handler latency, bucketed by LATENCY_BOUNDS_MS
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Histogram {
+    pub buckets: [u64; LATENCY_BOUNDS_MS.len() + 1],
+    pub count: u64,
+    pub total_us: u64,
+    pub max_us: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        let idx = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|bound| ms < *bound)
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+        let us = elapsed.as_micros() as u64;
+
+        self.buckets[idx] += 1;
+        self.count += 1;
+        self.total_us += us;
+        self.max_us = self.max_us.max(us);
+    }
+
+    pub fn avg_us(&self) -> u64 {
+        self.total_us.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+pub fn bucket_names() -> Vec<String> {
+    LATENCY_BOUNDS_MS
+        .iter()
+        .map(|bound| format!("<{bound}ms"))
+        .chain(LATENCY_BOUNDS_MS.last().map(|bound| format!(">={bound}ms")))
+        .collect()
+}
+
+pub fn us_str(us: u64) -> String {
+    format!("{:.1}ms", us as f64 / 1000.0)
+}
@@ -1,13 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use chrono::{DateTime, Local, NaiveDateTime};
+use once_cell::sync::Lazy;
 use sysinfo::System;
 
+static CLOCK: Lazy<RwLock<Arc<dyn Clock>>> = Lazy::new(|| RwLock::new(Arc::new(SystemClock)));
+
+// where every "now" comes from, so tests can move time by hand
+pub trait Clock: Send + Sync {
+    fn ts(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn ts(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before UNIX epoch!")
+            .as_secs()
+    }
+}
+
+// stands still until set or advanced
+pub struct MockClock {
+    ts: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(ts: u64) -> Self {
+        Self {
+            ts: AtomicU64::new(ts),
+        }
+    }
+
+    pub fn set(&self, ts: u64) {
+        self.ts.store(ts, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.ts.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn ts(&self) -> u64 {
+        self.ts.load(Ordering::SeqCst)
+    }
+}
+
+// process wide, e.g. `set_clock(Arc::new(MockClock::new(ts)))` before the harness starts
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    *CLOCK.write().unwrap() = clock;
+}
+
 pub fn ts() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("System time is before UNIX epoch!")
-        .as_secs()
+    CLOCK.read().unwrap().ts()
+}
+
+pub fn now() -> DateTime<Local> {
+    DateTime::from_timestamp(ts() as i64, 0)
+        .expect("Clock out of range")
+        .with_timezone(&Local)
 }
 
 pub fn ts_str(ts: u64) -> String {
@@ -26,6 +82,14 @@ pub fn ts_str_full(ts: u64) -> String {
     datetime_local.format("%Y-%m-%d %H:%M:%S %:z").to_string()
 }
 
+pub fn ts_str_rfc3339(ts: u64) -> String {
+    let datetime_local: DateTime<Local> = DateTime::from_timestamp(ts as i64, 0)
+        .unwrap_or_else(|| panic!("Failed to parse ts ({ts})"))
+        .with_timezone(&Local);
+
+    datetime_local.to_rfc3339()
+}
+
 pub fn ts_str_no_tz_no_sec(ts: u64) -> String {
     let datetime_local: DateTime<Local> = DateTime::from_timestamp(ts as i64, 0)
         .unwrap_or_else(|| panic!("Failed to parse ts ({ts})"))
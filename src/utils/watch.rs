@@ -0,0 +1,45 @@
+use std::path::Path;
+use std::thread;
+
+use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task;
+use tokio::time::Duration;
+
+const EVENT_SIZE: usize = 1024;
+
+// file system events under `path` until shutdown, or until the receiver is
+// dropped. the watcher lives on a blocking thread which checks once a second
+pub fn watch(
+    path: &Path,
+    mode: RecursiveMode,
+    mut shutdown_rx: broadcast::Receiver<()>,
+) -> notify::Result<mpsc::Receiver<Event>> {
+    let (tx, rx) = mpsc::channel(EVENT_SIZE);
+    let event_tx = tx.clone();
+    let mut watcher = RecommendedWatcher::new(
+        move |res| {
+            if let Ok(event) = res {
+                let _ = event_tx.blocking_send(event);
+            }
+        },
+        Config::default(),
+    )?;
+    watcher.watch(path, mode)?;
+
+    // 用 oneshot 通知 blocking thread 結束
+    let (shutdown_blocking_tx, mut shutdown_blocking_rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let _ = shutdown_rx.recv().await;
+        let _ = shutdown_blocking_tx.send(());
+    });
+
+    task::spawn_blocking(move || {
+        let _watcher = watcher;
+        while shutdown_blocking_rx.try_recv().is_err() && !tx.is_closed() {
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+
+    Ok(rx)
+}
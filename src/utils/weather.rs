@@ -17,7 +17,7 @@ pub struct Weather {
     pub daily: Vec<WeatherDaily>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct City {
     pub name: String,
     pub latitude: f32,
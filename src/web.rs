@@ -26,8 +26,9 @@ use serde_json::json;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc::Sender; // trait for `.encode()`
 
-use crate::consts::{self, NAS_FOLDER, NAS_NAME, UPLOAD_FOLDER, WEB_PORT};
-use crate::messages::{ACTION_NAS_STATE, Cmd, Data, Log, Msg};
+use crate::cfg;
+use crate::consts::NAS_NAME;
+use crate::messages::{ACTION_NAS_STATE, Command, Data, Log, Msg};
 use crate::utils::{
     self,
     nas_info::{self, FileList},
@@ -36,12 +37,31 @@ use crate::utils::{
 const MODULE: &str = "web";
 const MAX_SIZE: usize = 100 * 1024 * 1024; // 100MB
 const API_V1_UPLOAD: &str = "/api/v1/upload";
+const SHUTDOWN_TIMEOUT: u64 = 60; // secs for in-flight transfers to finish
 
 #[get("/")]
 async fn hello() -> impl Responder {
     HttpResponse::Ok().body("Hello world!")
 }
 
+// e.g. GET /api/v1/query/devices/list
+#[get("/api/v1/query/{plugin}/{action}")]
+async fn query(
+    path: web::Path<(String, String)>,
+    msg_tx: web::Data<Sender<Msg>>,
+) -> impl Responder {
+    let (plugin, action) = path.into_inner();
+
+    match utils::msg::query(&msg_tx, MODULE, Command::new(&plugin, &action)).await {
+        Ok(reply) => HttpResponse::Ok().json(json!({
+            "data": reply
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": e.to_string()
+        })),
+    }
+}
+
 #[derive(Deserialize)]
 struct CheckHashRequest {
     data: CheckHashData,
@@ -61,7 +81,7 @@ pub async fn check_hash(
     let hash_str = &data.data.hash_str;
 
     // get local file_list
-    let file_list = FileList::new(consts::NAS_FOLDER).await;
+    let file_list = FileList::new(&cfg::nas_dir()).await;
 
     let hash_str_same = hash_str == &file_list.hash_str;
 
@@ -73,17 +93,14 @@ pub async fn check_hash(
     )
     .await;
 
-    let msg = Msg {
-        ts: utils::time::ts(),
-        module: MODULE.to_string(),
-        data: Data::Cmd(Cmd {
-            cmd: format!(
-                "p nas {ACTION_NAS_STATE} {name} {}",
-                if hash_str_same { "Synced" } else { "Syncing" }
-            ),
-        }),
-    };
-    let _ = msg_tx.send(msg).await;
+    utils::msg::command(
+        &msg_tx,
+        MODULE,
+        Command::new("nas", ACTION_NAS_STATE)
+            .arg(name)
+            .arg(if hash_str_same { "Synced" } else { "Syncing" }),
+    )
+    .await;
 
     if hash_str_same {
         HttpResponse::Ok().json(json!({
@@ -284,9 +301,10 @@ async fn upload_file(mut payload: Multipart, msg_tx: web::Data<Sender<Msg>>) ->
             .map(sanitize_filename::sanitize)
             .unwrap_or_else(|| format!("upload-{}.bin", uuid::Uuid::new_v4()));
 
-        let _ = fs::create_dir_all(UPLOAD_FOLDER);
+        let upload_dir = cfg::upload_dir();
+        let _ = fs::create_dir_all(&upload_dir);
 
-        let filepath = format!("{UPLOAD_FOLDER}/{filename}");
+        let filepath = format!("{upload_dir}/{filename}");
         info(&msg_tx, format!("[{MODULE}] API: upload_file: {filepath}")).await;
 
         let start_ts = utils::time::ts();
@@ -410,20 +428,24 @@ impl Web {
                 .service(remove)
                 .service(check_hash)
                 .service(verify_hash)
+                .service(query)
                 .wrap(CharsetMiddleware)
                 .service(
-                    Files::new(NAS_NAME, NAS_FOLDER)
+                    Files::new(NAS_NAME, cfg::nas_dir())
                         .show_files_listing()
                         .prefer_utf8(true),
                 )
         })
-        .bind(("0.0.0.0", WEB_PORT))?
+        .shutdown_timeout(SHUTDOWN_TIMEOUT)
+        .bind(("0.0.0.0", cfg::web_port()))?
         .run();
 
         let handle = server.handle();
 
         let server_task = tokio::spawn(server);
 
+        // signalled once the plugins are down; stop accepting and let
+        // in-flight transfers finish
         let shutdown_task = tokio::spawn(async move {
             if shutdown_rx.recv().await.is_ok() {
                 handle.stop(true).await;
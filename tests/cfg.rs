@@ -0,0 +1,183 @@
+use std::fs;
+
+use cng3::cfg::{Cfg, CfgError, Layers, Overrides, SOURCE_DEFAULT, Sources};
+
+fn cfg_file(name: &str, content: Option<&str>) -> String {
+    let path = std::env::temp_dir().join(format!("cng3_cfg_{}_{name}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    if let Some(content) = content {
+        fs::write(&path, content).unwrap();
+    }
+    path.to_str().unwrap().to_string()
+}
+
+// no host file and an empty environment unless given
+fn layers(path: &str) -> Layers {
+    Layers {
+        cfg_file: path.to_string(),
+        host: None,
+        env: vec![],
+        overrides: Overrides::default(),
+    }
+}
+
+fn load(path: &str) -> Result<(Cfg, Sources), Vec<CfgError>> {
+    layers(path).load()
+}
+
+fn load_errors(path: &str) -> Vec<String> {
+    load(path)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| format!("{}: {}", e.path, e.msg))
+        .collect()
+}
+
+#[test]
+fn missing_sections_get_their_defaults() {
+    let path = cfg_file("defaults", None);
+    assert_eq!(load(&path).unwrap().0, Cfg::default());
+    // not created
+    assert!(fs::metadata(&path).is_err());
+
+    let path = cfg_file(
+        "partial",
+        Some(r#"{ "name": "cng3_pi5", "mqtt": { "broker": "localhost" } }"#),
+    );
+    let (cfg, _) = load(&path).unwrap();
+    assert_eq!(cfg.name, "cng3_pi5");
+    assert_eq!(cfg.mqtt.broker, "localhost");
+    assert_eq!(cfg.mqtt.port, Cfg::default().mqtt.port);
+    assert_eq!(cfg.weather, Cfg::default().weather);
+
+    // not written back
+    assert!(!fs::read_to_string(&path).unwrap().contains("port"));
+}
+
+#[test]
+fn later_layers_win_and_are_reported() {
+    let path = cfg_file(
+        "layers",
+        Some(r#"{ "name": "cng3_base", "mqtt": { "broker": "localhost", "port": 1884 } }"#),
+    );
+    let mut layers = layers(&path);
+    layers.host = Some("pi5".to_string());
+    let host_file = layers.host_file().unwrap();
+    assert!(host_file.ends_with(&format!("cng3_cfg_{}_layers.pi5.json", std::process::id())));
+    fs::write(
+        &host_file,
+        r#"{ "name": "cng3_pi5", "mqtt": { "port": 1885 } }"#,
+    )
+    .unwrap();
+    layers.env = vec![
+        ("CNG3_MQTT_PORT".to_string(), "1886".to_string()),
+        ("CNG3_PLUGINS".to_string(), "log, system".to_string()),
+    ];
+    layers.overrides.name = Some("cng3_cli".to_string());
+
+    let (cfg, sources) = layers.load().unwrap();
+    assert_eq!(cfg.name, "cng3_cli");
+    assert_eq!(cfg.mqtt.broker, "localhost");
+    assert_eq!(cfg.mqtt.port, 1886);
+    assert_eq!(
+        cfg.plugins,
+        Some(vec!["log".to_string(), "system".to_string()])
+    );
+    assert_eq!(sources["name"], "command line");
+    assert_eq!(sources["mqtt.broker"], path);
+    assert_eq!(sources["mqtt.port"], "CNG3_MQTT_PORT");
+    assert_eq!(sources["plugins"], "CNG3_PLUGINS");
+    assert_eq!(sources["web.port"], SOURCE_DEFAULT);
+
+    // the host file alone
+    layers.env.clear();
+    let (cfg, sources) = layers.load().unwrap();
+    assert_eq!(cfg.mqtt.port, 1885);
+    assert_eq!(sources["mqtt.port"], host_file);
+
+    let _ = fs::remove_file(&host_file);
+}
+
+#[test]
+fn errors_name_their_layer() {
+    let path = cfg_file("env", None);
+    let mut layers = layers(&path);
+    layers.env = vec![
+        ("CNG3_MQTT_PORT".to_string(), "many".to_string()),
+        ("CNG3_MQTT_BROKR".to_string(), "localhost".to_string()),
+    ];
+    let errors: Vec<String> = layers
+        .load()
+        .unwrap_err()
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(
+        errors[0].starts_with("CNG3_MQTT_PORT: mqtt.port: invalid type"),
+        "{errors:?}"
+    );
+
+    // another tool's variable is no error, only reported
+    layers.env = vec![
+        ("CNG3_MQTT_PORT".to_string(), "1886".to_string()),
+        ("CNG3_MQTT_BROKR".to_string(), "localhost".to_string()),
+    ];
+    let (cfg, _) = layers.load().unwrap();
+    assert_eq!(cfg.mqtt.port, 1886);
+    assert_eq!(layers.ignored_env(), ["CNG3_MQTT_BROKR"]);
+
+    // a valid value in an invalid combination is blamed on where it came from
+    layers.env = vec![("CNG3_WEATHER_POLLING".to_string(), "0".to_string())];
+    let errors = layers.load().unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "CNG3_WEATHER_POLLING: weather.polling: must not be 0"
+    );
+}
+
+#[test]
+fn malformed_files_point_at_the_field() {
+    let path = cfg_file("type", Some(r#"{ "mqtt": { "port": "1883" } }"#));
+    let errors = load_errors(&path);
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].starts_with("mqtt.port: invalid type"),
+        "{errors:?}"
+    );
+
+    let path = cfg_file("unknown", Some(r#"{ "web": { "prot": 80 } }"#));
+    assert!(load_errors(&path)[0].starts_with("web.prot: unknown field"));
+
+    let path = cfg_file(
+        "city",
+        Some(r#"{ "weather": { "cities": [{ "name": "Taipei", "latitude": 25.03 }] } }"#),
+    );
+    assert!(load_errors(&path)[0].starts_with("weather.cities[0]: missing field `longitude`"));
+
+    // left as it was
+    assert!(fs::read_to_string(&path).unwrap().contains("Taipei"));
+}
+
+#[test]
+fn invalid_values_are_all_reported() {
+    let path = cfg_file(
+        "invalid",
+        Some(
+            r#"{
+                "plugins": ["devices", "bogus"],
+                "log": { "level": "loud" },
+                "weather": { "polling": 0, "cities": [{ "name": "Nowhere", "latitude": 123, "longitude": 0 }] }
+            }"#,
+        ),
+    );
+    assert_eq!(
+        load_errors(&path),
+        [
+            "plugins[1]: unknown plugin `bogus`",
+            "log.level: `loud` is not error/warn/info/debug/trace/off",
+            "weather.polling: must not be 0",
+            "weather.cities[0].latitude: 123 is not in -90..=90",
+        ]
+    );
+}
@@ -0,0 +1,101 @@
+use std::process::Command;
+
+fn cng3(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_cng3"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn version_and_help() {
+    let output = cng3(&["--version"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        format!("cng3 {}", env!("CARGO_PKG_VERSION"))
+    );
+
+    let output = cng3(&["--help"]);
+    assert!(output.status.success());
+    let usage = String::from_utf8_lossy(&output.stdout);
+    for flag in [
+        "--name",
+        "--config",
+        "--web-port",
+        "--nas-dir",
+        "--headless",
+        "--log-level",
+    ] {
+        assert!(usage.contains(flag), "{flag} missing in {usage}");
+    }
+}
+
+#[test]
+fn bad_arguments_fail() {
+    for args in [
+        &["--bogus"][..],
+        &["--web-port", "http"],
+        &["--log-level", "loud"],
+        &["--name"],
+        &["--daemon"],
+    ] {
+        let output = cng3(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Error"));
+    }
+}
+
+#[test]
+fn exec_on_a_daemon_that_is_not_running() {
+    let path = std::env::temp_dir().join(format!("cng3_cli_{}.sock", std::process::id()));
+    let output = cng3(&[
+        "--ctl-socket",
+        path.to_str().unwrap(),
+        "--exec",
+        "p devices show",
+        "--daemon",
+    ]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn malformed_cfg_fails_with_the_path() {
+    let path = std::env::temp_dir().join(format!("cng3_cli_{}.json", std::process::id()));
+    std::fs::write(&path, r#"{ "mqtt": { "port": -1 } }"#).unwrap();
+
+    let output = cng3(&[
+        "--config",
+        path.to_str().unwrap(),
+        "--exec",
+        "p devices show",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("mqtt.port: invalid value"));
+}
+
+#[test]
+fn check_script_for_a_host() {
+    let path = std::env::temp_dir().join(format!("cng3_cli_{}.scripts", std::process::id()));
+    std::fs::write(
+        &path,
+        "p weather init\nif host == cng3_test\np weather ad 新店 24.9676 121.542\nend\n",
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let output = cng3(&["--name", "cng3_test", "--check-script", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(&format!("{path}:3: Unknown action (ad)")),
+        "{output:?}"
+    );
+
+    let output = cng3(&["--name", "cng3_other", "--check-script", path]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("{path}:1 p weather init")));
+    assert!(stdout.contains("1 steps, no errors"));
+
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use chrono::{Local, TimeZone};
+use cng3::harness::Harness;
+use cng3::utils::time::{self, MockClock};
+
+// the clock is process wide, so it gets a test binary of its own
+#[tokio::test]
+async fn daily_reminder_fires_at_its_time() {
+    let ts = Local
+        .with_ymd_and_hms(2026, 10, 16, 7, 50, 0)
+        .unwrap()
+        .timestamp() as u64;
+    let clock = Arc::new(MockClock::new(ts));
+    time::set_clock(clock.clone());
+
+    let harness = Harness::start(&["todos"]).await;
+
+    harness
+        .cmd("p todos add task eye-drop daily 08:00 reminder 5")
+        .await;
+    harness.cmd("p todos check").await;
+    // the mailbox is in order: once show answers, check has run
+    harness.cmd("p todos show").await;
+    assert!(harness.wait_for_log("Reminder").await);
+    assert!(
+        !harness
+            .logs()
+            .iter()
+            .any(|(_, msg)| msg.contains("Task reminder"))
+    );
+
+    // 07:55, five minutes ahead of 08:00
+    clock.advance(5 * 60);
+    harness.cmd("p todos check").await;
+    assert!(
+        harness
+            .wait_for_log("[todos] Task reminder: eye-drop 2026-10-16 08:00")
+            .await
+    );
+    assert!(
+        !harness
+            .logs()
+            .iter()
+            .any(|(_, msg)| msg.contains("Task due"))
+    );
+
+    clock.advance(5 * 60);
+    harness.cmd("p todos check").await;
+    assert!(
+        harness
+            .wait_for_log("[todos] Task due: eye-drop 2026-10-16 08:00")
+            .await
+    );
+
+    harness.cmd("p todos done 0").await;
+    assert!(
+        harness
+            .wait_for_log("[todos] Marked task as done: eye-drop")
+            .await
+    );
+    harness.cmd("p todos undone 0").await;
+    assert!(
+        harness
+            .wait_for_log("[todos] Marked task as not done: eye-drop")
+            .await
+    );
+
+    // a bad time is refused, not a panic
+    harness
+        .cmd("p todos add task pill once 2026/13/01-08:00")
+        .await;
+    assert!(
+        harness
+            .wait_for_log(
+                "Invalid argument `task_time` (got `2026/13/01-08:00`, expected yyyy/mm/dd-hh:mm)"
+            )
+            .await
+    );
+
+    assert!(harness.shutdown().await);
+}
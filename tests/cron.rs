@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use cng3::harness::Harness;
+use cng3::record::RecordData;
+use cng3::utils::cron::Schedule;
+use cng3::utils::time::{self, MockClock};
+
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap()
+}
+
+fn next(schedule: &str, after: NaiveDateTime) -> Option<NaiveDateTime> {
+    schedule.parse::<Schedule>().unwrap().next_after(after)
+}
+
+#[test]
+fn schedules_find_their_next_minute() {
+    // 2026-10-16 is a friday
+    let friday = at(2026, 10, 16, 7, 50);
+
+    assert_eq!(next("*/15 * * * *", friday), Some(at(2026, 10, 16, 8, 0)));
+    assert_eq!(next("0 7 * * 1-5", friday), Some(at(2026, 10, 19, 7, 0)));
+    assert_eq!(
+        next("0 7 * * mon-fri", friday),
+        Some(at(2026, 10, 19, 7, 0))
+    );
+    assert_eq!(next("30 9 1 jan *", friday), Some(at(2027, 1, 1, 9, 30)));
+    // either the 1st or a sunday
+    assert_eq!(next("0 0 1 * 7", friday), Some(at(2026, 10, 18, 0, 0)));
+    assert_eq!(next("0 0 29 2 *", friday), Some(at(2028, 2, 29, 0, 0)));
+    assert_eq!(next("@daily", friday), Some(at(2026, 10, 17, 0, 0)));
+    assert_eq!(
+        next("@hourly", at(2026, 10, 16, 8, 0)),
+        Some(at(2026, 10, 16, 9, 0))
+    );
+    assert_eq!(next("0 0 31 2 *", friday), None);
+
+    let boot = "@boot".parse::<Schedule>().unwrap();
+    assert!(boot.is_boot());
+    assert_eq!(boot.next_after(friday), None);
+
+    let errors: Vec<String> = [
+        "60 * * * *",
+        "* * * *",
+        "@often",
+        "*/0 * * * *",
+        "5-1 * * * *",
+        "* * * foo *",
+    ]
+    .into_iter()
+    .map(|text| text.parse::<Schedule>().unwrap_err())
+    .collect();
+    assert_eq!(
+        errors,
+        [
+            "minute: 60 is not in 0-59",
+            "`* * * *` is not `minute hour day-of-month month day-of-week`",
+            "unknown schedule `@often`",
+            "minute: `0` is not a step",
+            "minute: 5-1 is backwards",
+            "month: `foo` is not a number",
+        ]
+    );
+}
+
+// the clock is process wide, so it gets a test binary of its own
+#[tokio::test]
+async fn jobs_run_on_schedule_at_boot_and_now() {
+    let ts = Local
+        .with_ymd_and_hms(2026, 10, 16, 7, 50, 0)
+        .unwrap()
+        .timestamp() as u64;
+    let clock = Arc::new(MockClock::new(ts));
+    time::set_clock(clock.clone());
+
+    let harness = Harness::start(&["cron", "weather"]).await;
+
+    harness
+        .cmd("p cron add \"0 8 * * *\" p weather add 新店 24.9676 121.542")
+        .await;
+    assert!(
+        harness
+            .wait_for_log(
+                "[cron] Add 1: `0 8 * * *` `p weather add 新店 24.9676 121.542`, next 2026-10-16 08:00"
+            )
+            .await
+    );
+    harness
+        .cmd("p cron add @boot \"p weather add 台北 north 121.5654\"")
+        .await;
+    assert!(
+        harness
+            .wait_for_log("[cron] Ran 2 `p weather add 台北 north 121.5654`: failed, ")
+            .await
+    );
+    harness
+        .cmd("p cron add \"*/5 * * * *\" p wether show")
+        .await;
+    assert!(
+        harness
+            .wait_for_log("[cron] Unknown plugin name (`wether`) for cmd `p wether show`")
+            .await
+    );
+    harness
+        .cmd("p cron add \"61 * * * *\" p weather show")
+        .await;
+    assert!(harness.wait_for_log("minute: 61 is not in 0-59").await);
+
+    // not yet
+    harness.cmd("p cron check").await;
+    harness.cmd("p cron list").await;
+    assert!(harness.wait_for_log("Schedule").await);
+    assert!(
+        !harness
+            .logs()
+            .iter()
+            .any(|(_, msg)| msg.contains("[cron] Ran 1"))
+    );
+
+    clock.advance(10 * 60);
+    harness.cmd("p cron check").await;
+    assert!(
+        harness
+            .wait_for_log(
+                "[cron] Ran 1 `p weather add 新店 24.9676 121.542`: ok, [weather] Add: 新店 24.9676 121.542"
+            )
+            .await
+    );
+    harness.cmd("p cron list").await;
+    assert!(harness.wait_for_log("2026-10-17 08:00").await);
+
+    harness.cmd("p cron run-now 1").await;
+    assert!(
+        harness
+            .wait_for(|records| records
+                .iter()
+                .filter(|record| matches!(
+                    &record.data,
+                    RecordData::Log { msg, .. } if msg.starts_with("[cron] Ran 1")
+                ))
+                .count()
+                == 2)
+            .await
+    );
+    harness.cmd("p cron history 1").await;
+    assert!(
+        harness
+            .wait_for_log("[cron] 1: `0 8 * * *` `p weather add 新店 24.9676 121.542`, 2 runs")
+            .await
+    );
+
+    harness.cmd("p cron remove 1").await;
+    assert!(harness.wait_for_log("[cron] Remove 1").await);
+    harness.cmd("p cron run-now 1").await;
+    assert!(harness.wait_for_log("[cron] No job 1").await);
+
+    assert!(harness.shutdown().await);
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use cng3::ctl::{self, Ctl};
+use cng3::harness::Harness;
+
+#[tokio::test]
+async fn runs_commands_on_the_daemon() {
+    let harness = Harness::start(&["devices", "weather"]).await;
+    let path = std::env::temp_dir().join(format!("cng3_ctl_{}.sock", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+
+    let ctl = Ctl::new(harness.msg_tx(), harness.shutdown_tx(), &path).await;
+    let server = tokio::spawn(async move { ctl.run().await });
+    assert!(harness.wait_for_log("[ctl] Listening on").await);
+
+    assert!(
+        ctl::ctl(&path, "p devices onboard cng3_pi5 1")
+            .await
+            .unwrap()
+    );
+    assert!(!ctl::ctl(&path, "p devices onboard cng3_pi5").await.unwrap());
+
+    // through the client binary, the shell quoting survives
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_cng3"))
+        .args(["--ctl-socket", &path, "ctl"])
+        .args(["p", "weather", "add", "New York", "40.71", "-74.0"])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[weather] Add: New York 40.71 -74"));
+
+    // --daemon --exec goes through the socket too
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_cng3"))
+        .args(["--ctl-socket", &path, "--daemon", "--exec"])
+        .arg("p devices onboard cng3_pi5")
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Missing argument `onboard`"));
+
+    // a second daemon leaves the socket alone
+    let other = Ctl::new(harness.msg_tx(), harness.shutdown_tx(), &path).await;
+    assert!(other.run().await.is_err());
+
+    assert!(harness.shutdown().await);
+    let stopped = tokio::time::timeout(Duration::from_secs(5), server).await;
+    assert!(matches!(stopped, Ok(Ok(Ok(())))));
+    assert!(ctl::ctl(&path, "p devices show").await.is_err());
+}
@@ -0,0 +1,37 @@
+use cng3::exec;
+use cng3::harness::Harness;
+
+#[tokio::test]
+async fn collects_the_logs_of_a_command() {
+    let harness = Harness::start(&["devices"]).await;
+
+    let result = exec::exec(&harness.msg_tx(), "p devices onboard cng3_pi5 1").await;
+    assert!(result.ok);
+    assert!(
+        result
+            .logs
+            .iter()
+            .any(|log| log.module == "devices" && log.msg.contains("cng3_pi5 on at"))
+    );
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn warnings_fail_the_command() {
+    let harness = Harness::start(&["devices"]).await;
+
+    let result = exec::exec(&harness.msg_tx(), "p devices onboard cng3_pi5").await;
+    assert!(!result.ok);
+    assert!(
+        result
+            .logs
+            .iter()
+            .any(|log| log.level == "WARN" && log.msg.contains("Missing argument `onboard`"))
+    );
+
+    let result = exec::exec(&harness.msg_tx(), "p nobody show").await;
+    assert!(!result.ok);
+
+    assert!(harness.shutdown().await);
+}
@@ -0,0 +1,222 @@
+use cng3::harness::Harness;
+use cng3::messages::{Command, Event, Reply};
+use cng3::record::RecordData;
+
+#[tokio::test]
+async fn starts_only_selected_and_required_plugins() {
+    let harness = Harness::start(&["devices"]).await;
+
+    let reply = harness.query(Command::new("plugins", "list")).await;
+    match reply {
+        Ok(Reply::Plugins(names)) => assert_eq!(names, ["log", "scripts", "devices"]),
+        other => panic!("unexpected reply {other:?}"),
+    }
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn help_lists_plugin_actions() {
+    let harness = Harness::start(&["devices"]).await;
+
+    harness.cmd("p devices help").await;
+    assert!(harness.wait_for_log("[devices] actions").await);
+    assert!(harness.wait_for_log("onboard <name> <onboard>").await);
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn missing_argument_is_rejected_before_the_plugin() {
+    let harness = Harness::start(&["devices"]).await;
+
+    harness.cmd("p devices onboard cng3_pi5").await;
+    assert!(
+        harness
+            .wait_for_log("[devices] Missing argument `onboard`")
+            .await
+    );
+
+    let reply = harness.query(Command::new("devices", "list")).await;
+    assert!(matches!(reply, Ok(Reply::Devices(devices)) if devices.is_empty()));
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn onboard_emits_event_and_updates_list() {
+    let harness = Harness::start(&["devices"]).await;
+
+    harness
+        .command(Command::new("devices", "onboard").arg("cng3_pi5").arg("1"))
+        .await;
+    assert!(harness.wait_for_log("[devices] cng3_pi5 on at").await);
+    assert!(
+        harness
+            .wait_for(|records| records.iter().any(|record| matches!(
+                &record.data,
+                RecordData::Event(Event::DeviceOnboard { name, onboard: true })
+                    if name == "cng3_pi5"
+            )))
+            .await
+    );
+
+    let reply = harness.query(Command::new("devices", "list")).await;
+    match reply {
+        Ok(Reply::Devices(devices)) => {
+            assert_eq!(devices.len(), 1);
+            assert_eq!(devices[0].name, "cng3_pi5");
+            assert!(devices[0].onboard);
+        }
+        other => panic!("unexpected reply {other:?}"),
+    }
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn infos_shows_the_devices_it_asks_for() {
+    let harness = Harness::start(&["devices", "infos"]).await;
+
+    harness.cmd("p devices onboard cng3_pi5 1").await;
+    assert!(harness.wait_for_log("[devices] cng3_pi5 on at").await);
+
+    harness.cmd("p infos show").await;
+    assert!(
+        harness
+            .wait_for(|records| records.iter().any(|record| matches!(
+                &record.data,
+                RecordData::Log { msg, .. } if msg.starts_with("cng3_pi5 ")
+            )))
+            .await
+    );
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn disable_and_enable_at_runtime() {
+    let harness = Harness::start(&["devices"]).await;
+
+    harness.cmd("p plugins disable devices").await;
+    assert!(harness.wait_for_log("[plugins] `devices` disabled").await);
+
+    harness.cmd("p devices show").await;
+    assert!(harness.wait_for_log("Unknown plugin").await);
+
+    harness.cmd("p plugins enable devices").await;
+    assert!(harness.wait_for_log("[plugins] `devices` enabled").await);
+
+    harness.cmd("p plugins disable log").await;
+    assert!(
+        harness
+            .wait_for_log("a plugin other than log/scripts")
+            .await
+    );
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn log_in_gui_mode_commands_the_panel() {
+    let harness = Harness::start(&["panels"]).await;
+
+    harness.cmd("p log gui log_panel").await;
+    harness.cmd("p plugins enable devices").await;
+    assert!(
+        harness
+            .wait_for(|records| records.iter().any(|record| matches!(
+                &record.data,
+                RecordData::Command(cmd)
+                    if cmd.plugin == "panels"
+                        && cmd.args.first().map(String::as_str) == Some("log_panel")
+                        && cmd.args.iter().any(|arg| arg.contains("`devices` enabled"))
+            )))
+            .await
+    );
+    assert!(
+        harness
+            .commands()
+            .iter()
+            .all(|cmd| cmd.plugin == "panels" && cmd.action == "output_push")
+    );
+
+    // once panels stops, back to stdout
+    harness.cmd("p plugins disable panels").await;
+    assert!(harness.wait_for_log("[plugins] `panels` disabled").await);
+    harness.cmd("p plugins disable devices").await;
+    assert!(harness.wait_for_log("[plugins] `devices` disabled").await);
+    assert!(
+        !harness
+            .logs()
+            .iter()
+            .any(|(_, msg)| msg.contains("Unknown plugin"))
+    );
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn log_waits_for_panels() {
+    let harness = Harness::start(&[]).await;
+
+    harness.cmd("p log gui log_panel").await;
+    assert!(
+        harness
+            .wait_for_log("[log] `log_panel` waits for panels, printing to stdout meanwhile")
+            .await
+    );
+    harness.cmd("p plugins enable devices").await;
+    assert!(harness.wait_for_log("[plugins] `devices` enabled").await);
+    assert!(harness.commands().iter().all(|cmd| cmd.plugin != "panels"));
+
+    harness.cmd("p plugins enable panels").await;
+    assert!(
+        harness
+            .wait_for(|records| records.iter().any(|record| matches!(
+                &record.data,
+                RecordData::Command(cmd)
+                    if cmd.plugin == "panels"
+                        && cmd.args.first().map(String::as_str) == Some("log_panel")
+                        && cmd.args.iter().any(|arg| arg.contains("`panels` enabled"))
+            )))
+            .await
+    );
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn stats_count_messages_and_time_handlers() {
+    let harness = Harness::start(&["devices"]).await;
+
+    for name in ["cng3_pi5", "cng3_pi4"] {
+        harness
+            .command(Command::new("devices", "onboard").arg(name).arg("1"))
+            .await;
+    }
+    let _ = harness.query(Command::new("devices", "list")).await;
+
+    let reply = harness.query(Command::new("plugins", "stats")).await;
+    match reply {
+        Ok(Reply::Stats(stats)) => {
+            assert_eq!(stats.queue.capacity, 4096);
+            assert!(stats.counts.iter().any(|count| count.module == "devices"
+                && count.action == "onboard"
+                && count.count == 2));
+            let devices = stats
+                .latencies
+                .iter()
+                .find(|latency| latency.name == "devices")
+                .unwrap();
+            assert_eq!(devices.latency.count, 3);
+            assert_eq!(devices.latency.buckets.iter().sum::<u64>(), 3);
+        }
+        other => panic!("unexpected reply {other:?}"),
+    }
+
+    harness.cmd("p plugins stats").await;
+    assert!(harness.wait_for_log("[plugins] queue depth").await);
+
+    assert!(harness.shutdown().await);
+}
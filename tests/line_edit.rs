@@ -0,0 +1,70 @@
+use cng3::utils::line_edit::LineEdit;
+
+fn typed(text: &str) -> LineEdit {
+    let mut line = LineEdit::new();
+    text.chars().for_each(|c| line.insert(c));
+    line
+}
+
+#[test]
+fn cursor_moves_by_char_and_counts_cjk_as_two_columns() {
+    let mut line = typed("p weather add 新店");
+    assert_eq!(line.cursor_width(), 18);
+
+    line.left();
+    assert_eq!(line.cursor_width(), 16);
+    line.insert('x');
+    assert_eq!(line.text(), "p weather add 新x店");
+    line.backspace();
+    line.backspace();
+    assert_eq!(line.text(), "p weather add 店");
+    assert_eq!(line.cursor_width(), 14);
+    line.delete();
+    line.delete();
+    assert_eq!(line.text(), "p weather add ");
+
+    line.home();
+    line.left();
+    line.backspace();
+    assert_eq!(line.cursor_width(), 0);
+    line.right();
+    line.delete();
+    assert_eq!(line.text(), "pweather add ");
+    line.end();
+    line.right();
+    assert_eq!(line.cursor_width(), 13);
+
+    line.set("p cron list");
+    assert_eq!(line.cursor_width(), 11);
+    assert_eq!(line.take(), "p cron list");
+    assert_eq!(line, LineEdit::new());
+}
+
+#[test]
+fn words_lines_and_pastes() {
+    let mut line = typed("p weather add  台北  ");
+    line.delete_word();
+    assert_eq!(line.text(), "p weather add  ");
+    line.delete_word();
+    assert_eq!(line.text(), "p weather ");
+
+    let mut line = typed("p weather add 台北");
+    for _ in 0..2 {
+        line.left();
+    }
+    line.delete_word();
+    assert_eq!(line.text(), "p weather 台北");
+    assert_eq!(line.cursor_width(), 10);
+    line.delete_to_start();
+    assert_eq!(line.text(), "台北");
+    assert_eq!(line.cursor_width(), 0);
+    line.delete_word();
+    assert_eq!(line.text(), "台北");
+
+    line.insert_str("p weather add\r\n\t");
+    assert_eq!(line.text(), "p weather add  台北");
+    line.end();
+    line.insert_str(" 25.0330\u{7}\n121.5654\n");
+    assert_eq!(line.text(), "p weather add  台北 25.0330 121.5654");
+    assert_eq!(line.cursor_width(), 36);
+}
@@ -0,0 +1,145 @@
+use std::fs;
+
+use cng3::harness::Harness;
+use cng3::messages::Command;
+use cng3::record::{Record, RecordData};
+use cng3::utils::log_file::LogFile;
+
+// what the log plugin pushed to the panel in gui mode
+fn panel_lines(records: &[Record]) -> Vec<String> {
+    records
+        .iter()
+        .filter_map(|record| match &record.data {
+            RecordData::Command(cmd) if cmd.plugin == "panels" && cmd.action == "output_push" => {
+                cmd.args.get(1).cloned()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn level_filters_per_module() {
+    let harness = Harness::start(&["devices", "panels"]).await;
+
+    harness.cmd("p log gui log_panel").await;
+    harness.cmd("p log level devices warn").await;
+    assert!(harness.wait_for_log("[log] Level of devices: WARN").await);
+
+    harness.cmd("p devices onboard cng3_pi5 1").await;
+    // a round trip through devices: its log is on the way to the log plugin
+    let _ = harness.query(Command::new("devices", "list")).await;
+    harness.cmd("p log level devices trace").await;
+    harness.cmd("p devices onboard cng3_pi4 1").await;
+    assert!(
+        harness
+            .wait_for(|records| panel_lines(records)
+                .iter()
+                .any(|line| line.contains("cng3_pi4 on at")))
+            .await
+    );
+
+    let lines = panel_lines(&harness.records());
+    assert!(!lines.iter().any(|line| line.contains("cng3_pi5 on at")));
+
+    harness.cmd("p log level devices loud").await;
+    assert!(harness.wait_for_log("Invalid argument").await);
+
+    assert!(harness.shutdown().await);
+}
+
+#[tokio::test]
+async fn tail_reads_the_file_back() {
+    let dir = std::env::temp_dir().join(format!("cng3-log-{}", std::process::id()));
+    let path = dir.join("cng3.log");
+    let harness = Harness::start(&["devices", "panels"]).await;
+
+    harness.cmd(&format!("p log file {}", path.display())).await;
+    for name in ["cng3_pi5", "cng3_pi4", "cng3_mac"] {
+        harness.cmd(&format!("p devices onboard {name} 1")).await;
+    }
+    let _ = harness.query(Command::new("devices", "list")).await;
+    harness.cmd("p log gui log_panel").await;
+    harness.cmd("p log tail 2 devices").await;
+    assert!(
+        harness
+            .wait_for(|records| panel_lines(records)
+                .iter()
+                .any(|line| line.contains("INFO devices [devices] cng3_mac on at")))
+            .await
+    );
+
+    let lines = panel_lines(&harness.records());
+    let tail: Vec<&String> = lines
+        .iter()
+        .filter(|line| line.contains(" INFO devices "))
+        .collect();
+    assert_eq!(tail.len(), 2);
+    assert!(tail[0].contains("cng3_pi4"));
+    assert!(tail[1].contains("cng3_mac"));
+
+    assert!(harness.shutdown().await);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn json_lines_carry_the_fields() {
+    let dir = std::env::temp_dir().join(format!("cng3-json-{}", std::process::id()));
+    let path = dir.join("cng3.jsonl");
+    let harness = Harness::start(&["devices"]).await;
+
+    harness.cmd(&format!("p log json {}", path.display())).await;
+    harness.cmd("p devices onboard cng3_pi5 1").await;
+    let _ = harness.query(Command::new("devices", "list")).await;
+    harness.cmd("p log json off").await;
+    assert!(harness.wait_for_log("[log] JSON log file off").await);
+
+    let content = fs::read_to_string(&path).unwrap();
+    let lines: Vec<serde_json::Value> = content
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let onboard = lines
+        .iter()
+        .find(|line| line["message"].as_str().unwrap().contains("cng3_pi5 on at"))
+        .unwrap();
+    assert_eq!(onboard["module"], "devices");
+    assert_eq!(onboard["level"], "INFO");
+    assert!(onboard["device"].is_string());
+    assert!(chrono::DateTime::parse_from_rfc3339(onboard["timestamp"].as_str().unwrap()).is_ok());
+
+    assert!(harness.shutdown().await);
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn old_rotated_files_go_without_a_rotation() {
+    let dir = std::env::temp_dir().join(format!("cng3-prune-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cng3.log");
+    let old = dir.join("cng3.log.2");
+    let recent = dir.join("cng3.log.1");
+    for rotated in [&old, &recent] {
+        fs::write(
+            rotated,
+            "2026-10-01T07:55:00+08:00 INFO devices [devices] ...\n",
+        )
+        .unwrap();
+    }
+    let eight_days = std::time::Duration::from_secs(8 * 24 * 60 * 60);
+    fs::File::options()
+        .write(true)
+        .open(&old)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - eight_days)
+        .unwrap();
+
+    let mut log_file = LogFile::new(path.to_str().unwrap());
+    log_file
+        .write(1_792_000_000, log::Level::Info, "devices", "[devices] ...")
+        .unwrap();
+    assert!(!old.exists());
+    assert!(recent.exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
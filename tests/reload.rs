@@ -0,0 +1,50 @@
+use std::fs;
+
+use cng3::cfg;
+use cng3::harness::Harness;
+
+// one test only: the cfg is global to the process
+#[tokio::test]
+async fn edits_are_applied_or_rejected() {
+    let dir = std::env::temp_dir().join(format!("cng3_reload_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cfg.json");
+    fs::write(&path, r#"{ "name": "cng3_test" }"#).unwrap();
+    cfg::init(path.to_str().unwrap(), cfg::Overrides::default()).unwrap();
+
+    let harness = Harness::start(&["cfg", "system"]).await;
+    assert!(harness.wait_for_log("[cfg] new").await);
+
+    fs::write(
+        &path,
+        r#"{ "name": "cng3_test", "system": { "publish_interval": 60 } }"#,
+    )
+    .unwrap();
+    assert!(harness.wait_for_log("[cfg] Reloaded").await);
+    assert!(harness.wait_for_log("[system] Publish every 60 secs").await);
+    assert_eq!(cfg::get().system.publish_interval, 60);
+
+    harness.cmd("p cfg show").await;
+    let shown = format!("[cfg] system.publish_interval = 60 ({})", path.display());
+    assert!(harness.wait_for_log(&shown).await);
+    assert!(
+        harness
+            .wait_for_log("[cfg] mqtt.port = 1883 (default)")
+            .await
+    );
+
+    fs::write(
+        &path,
+        r#"{ "name": "cng3_test", "system": { "publish_interval": 0 } }"#,
+    )
+    .unwrap();
+    assert!(
+        harness
+            .wait_for_log("system.publish_interval: must not be 0")
+            .await
+    );
+    assert_eq!(cfg::get().system.publish_interval, 60);
+
+    assert!(harness.shutdown().await);
+    let _ = fs::remove_dir_all(&dir);
+}
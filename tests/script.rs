@@ -0,0 +1,293 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use cng3::harness::Harness;
+use cng3::messages::Event;
+use cng3::utils::script::{self, Line, Step};
+
+// a fresh dir per test with the given files
+fn scripts_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cng3_script_{}_{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+fn cmds(lines: Vec<Line>) -> Vec<String> {
+    lines
+        .into_iter()
+        .map(|line| match line.step {
+            Step::Cmd(cmd) => cmd,
+            step => panic!("not a command: {step}"),
+        })
+        .collect()
+}
+
+fn load_errors(file: PathBuf, host: &str) -> Vec<String> {
+    script::load(file.to_str().unwrap(), host)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| format!("{}: {}", e.no, e.msg))
+        .collect()
+}
+
+#[test]
+fn variables_includes_and_host_blocks() {
+    let dir = scripts_dir(
+        "run",
+        &[
+            (
+                "main.scripts",
+                "\
+# the server of the house
+set SERVER cng3_pi5
+
+p nas init $SERVER
+include shared/weather.scripts
+if host == $SERVER
+    p monitor init
+else
+    p cli init cli
+end
+if host != cng3_laptop
+    p music init
+end
+p todos add task ${HOST}_backup once 2025/08/28-18:30 reminder 30
+",
+            ),
+            (
+                "shared/weather.scripts",
+                "p weather init\nset CITY 台北\np weather add $CITY 25.0330 121.5654\n",
+            ),
+        ],
+    );
+    let main = dir.join("main.scripts");
+
+    let lines = script::load(main.to_str().unwrap(), "cng3_pi5").unwrap();
+    assert_eq!(
+        lines[1].file,
+        dir.join("shared/weather.scripts").display().to_string()
+    );
+    assert_eq!(lines[1].no, 1);
+    assert_eq!(
+        cmds(lines),
+        [
+            "p nas init cng3_pi5",
+            "p weather init",
+            "p weather add 台北 25.0330 121.5654",
+            "p monitor init",
+            "p music init",
+            "p todos add task cng3_pi5_backup once 2025/08/28-18:30 reminder 30",
+        ]
+    );
+
+    let lines = script::load(main.to_str().unwrap(), "cng3_laptop").unwrap();
+    assert_eq!(
+        cmds(lines)[3..],
+        [
+            "p cli init cli",
+            "p todos add task cng3_laptop_backup once 2025/08/28-18:30 reminder 30",
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn every_error_has_its_line() {
+    let dir = scripts_dir(
+        "errors",
+        &[(
+            "main.scripts",
+            "\
+p nas init $SERVER
+set 1ST x
+include missing.scripts
+include main.scripts
+if hostname == cng3_pi5
+end
+else
+if host == cng3_pi5
+    p weather add $CITY
+end
+end
+if host != cng3_pi5
+",
+        )],
+    );
+
+    assert_eq!(
+        load_errors(dir.join("main.scripts"), "cng3_pi5"),
+        [
+            "1: unknown variable `$SERVER`",
+            "2: `1ST` is not a variable name",
+            "3: cannot include `missing.scripts` (No such file or directory (os error 2))",
+            "4: `main.scripts` includes itself",
+            "5: unknown subject `hostname`, only `host`",
+            "7: `else` without `if`",
+            "9: unknown variable `$CITY`",
+            "11: `end` without `if`",
+            "12: `if` without `end`",
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+
+    // skipped lines are not expanded
+    let dir = scripts_dir(
+        "skipped",
+        &[(
+            "main.scripts",
+            "if host == cng3_pi5\np nas init $SERVER\nend\n",
+        )],
+    );
+    assert!(script::load(dir.join("main.scripts").to_str().unwrap(), "cng3_mac").is_ok());
+    let _ = fs::remove_dir_all(&dir);
+
+    let dir = scripts_dir(
+        "steps",
+        &[(
+            "main.scripts",
+            "sleep soon\nwait\nwait mqtt.connected 1 2\nsleep 1.5\nwait mqtt.*\n",
+        )],
+    );
+    assert_eq!(
+        load_errors(dir.join("main.scripts"), "cng3_pi5"),
+        [
+            "1: `soon` is not a number of seconds",
+            "2: expected `wait <topic> [timeout]`, got `wait`",
+            "3: expected `wait <topic> [timeout]`, got `wait mqtt.connected 1 2`",
+        ]
+    );
+    fs::write(dir.join("main.scripts"), "sleep 1.5\nwait mqtt.*\n").unwrap();
+    let lines = script::load(dir.join("main.scripts").to_str().unwrap(), "cng3_pi5").unwrap();
+    assert_eq!(lines[0].step, Step::Sleep(Duration::from_millis(1500)));
+    assert_eq!(lines[1].step.to_string(), "wait mqtt.*");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn shipped_scripts_check() {
+    for file in ["init.scripts", "cli.scripts", "gui.scripts"] {
+        let path = format!("{}/{file}", env!("CARGO_MANIFEST_DIR"));
+        for host in ["cng3_pi5", "cng3_laptop"] {
+            if let Err(errors) = script::check(&path, host) {
+                panic!("{file} on {host}: {errors:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn check_knows_plugins_actions_and_arguments() {
+    let dir = scripts_dir(
+        "check",
+        &[(
+            "main.scripts",
+            "\
+p weather init
+p wether init
+p weather int
+p panels create log log 0 0 50
+p weather add 新店 24.9676 121.542 1
+p weather help
+query weather list
+query wether list
+p plugins enable weather
+p \"unclosed
+exit
+",
+        )],
+    );
+
+    let errors: Vec<String> = script::check(dir.join("main.scripts").to_str().unwrap(), "cng3_pi5")
+        .unwrap_err()
+        .into_iter()
+        .map(|e| format!("{}: {}", e.no, e.msg))
+        .collect();
+    assert_eq!(errors.len(), 6, "{errors:?}");
+    assert_eq!(
+        errors[..5],
+        [
+            "2: Unknown plugin name (`wether`) for cmd `p wether init`",
+            "3: Unknown action (int) for cmd `p weather int`",
+            "4: Missing argument `height` for cmd `p panels create log log 0 0 50`",
+            "5: Too many arguments, expected `add <name> <latitude> <longitude>` for cmd `p weather add 新店 24.9676 121.542 1`",
+            "8: Unknown plugin name (`wether`) for cmd `query wether list`",
+        ]
+    );
+    assert!(errors[5].starts_with("10: Syntax error"), "{errors:?}");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn jobs_wait_sleep_and_can_be_cancelled() {
+    let dir = scripts_dir(
+        "jobs",
+        &[
+            ("wait.scripts", "wait nas.* 5\nsleep 0.1\np scripts jobs\n"),
+            ("forever.scripts", "wait nas.server\n"),
+            ("timeout.scripts", "wait nas.server 0.1\np scripts jobs\n"),
+        ],
+    );
+    let file = |name: &str| dir.join(name).display().to_string();
+    let harness = Harness::start(&[]).await;
+
+    harness
+        .cmd(&format!("p scripts check {}", file("wait.scripts")))
+        .await;
+    assert!(
+        harness
+            .wait_for_log(&format!("`{}`: 3 steps, no errors", file("wait.scripts")))
+            .await
+    );
+
+    harness
+        .cmd(&format!("p scripts run {}", file("wait.scripts")))
+        .await;
+    assert!(harness.wait_for_log("[scripts] Job 1 started").await);
+    harness.cmd("p scripts jobs").await;
+    assert!(
+        harness
+            .wait_for_log(&format!("{}:1 wait nas.* 5", file("wait.scripts")))
+            .await
+    );
+    harness
+        .emit(Event::NasServer {
+            nas_server: "cng3_pi5".to_string(),
+        })
+        .await;
+    assert!(harness.wait_for_log("[scripts] Job 1 done").await);
+
+    harness
+        .cmd(&format!("p scripts run {}", file("forever.scripts")))
+        .await;
+    assert!(harness.wait_for_log("[scripts] Job 2 started").await);
+    harness.cmd("p scripts cancel 2").await;
+    assert!(harness.wait_for_log("[scripts] Job 2 cancelled").await);
+    harness.cmd("p scripts cancel 2").await;
+    assert!(harness.wait_for_log("[scripts] No running job 2").await);
+
+    harness
+        .cmd(&format!("p scripts run {}", file("timeout.scripts")))
+        .await;
+    assert!(
+        harness
+            .wait_for_log(&format!(
+                "[scripts] Job 3 stopped. {}:1 timed out waiting for `nas.server`",
+                file("timeout.scripts")
+            ))
+            .await
+    );
+
+    assert!(harness.shutdown().await);
+    let _ = fs::remove_dir_all(&dir);
+}